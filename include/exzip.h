@@ -0,0 +1,67 @@
+/*
+ * The C API of exzip, from src/ffi.rs. Link with libexzip (target/release/
+ * libexzip.so or libexzip.a).
+ *
+ * Strings are UTF-8. Functions that fail return NULL or -1, and
+ * exzip_last_error() tells why.
+ */
+
+#ifndef EXZIP_H
+#define EXZIP_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct ExzipArchive ExzipArchive;
+
+/* The message of the last error on the calling thread, or NULL. Valid until
+ * the next failing call on the thread. */
+const char *exzip_last_error(void);
+
+/* Open an archive and read its entry names, decoded with the detected
+ * encoding. Returns NULL on failure. */
+ExzipArchive *exzip_archive_open(const char *path);
+
+void exzip_archive_close(ExzipArchive *archive);
+
+/* The encoding detected for names without the UTF-8 flag, e.g. "Shift_JIS". */
+const char *exzip_archive_encoding(const ExzipArchive *archive);
+
+size_t exzip_archive_entry_count(const ExzipArchive *archive);
+
+/* The decoded name of entry `index`, or NULL past the end. Owned by the
+ * archive. */
+const char *exzip_archive_entry_name(const ExzipArchive *archive, size_t index);
+
+/* The uncompressed size of entry `index`. */
+uint64_t exzip_archive_entry_size(const ExzipArchive *archive, size_t index);
+
+/* Called when an entry is started and as file data is written, with the
+ * entry's name and the bytes written so far for the whole archive. With
+ * more than one thread writing file data, calls may come from several of
+ * them at once. `entry` is valid only during the call. */
+typedef void (*exzip_progress_fn)(void *user_data, const char *entry, uint64_t written);
+
+/* Extract `zipfile` as the exzip command does, into a directory named after
+ * it in `destination`, or next to it if NULL. `encoding` overrides the
+ * detected one if not NULL. `progress` may be NULL; nothing is printed
+ * either way.
+ *
+ * Returns 0 when extracted, 1 when skipped because the directory exists,
+ * and -1 on failure. Extractions may run at the same time on different
+ * threads, each reporting to its own callback. */
+int exzip_extract(const char *zipfile,
+                  const char *destination,
+                  const char *encoding,
+                  exzip_progress_fn progress,
+                  void *user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* EXZIP_H */
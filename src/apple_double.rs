@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+
+// AppleDouble files as written into __MACOSX by macOS Archive Utility / ditto.
+// https://datatracker.ietf.org/doc/html/rfc1740 (Appendix B)
+// All integers are big-endian.
+
+const APPLE_DOUBLE_MAGIC: u32 = 0x00051607;
+const ENTRY_RESOURCE_FORK: u32 = 2;
+const ENTRY_FINDER_INFO: u32 = 9;
+const FINDER_INFO_SIZE: usize = 32;
+const ATTR_MAGIC: u32 = 0x41545452; // "ATTR"
+
+// Map "__MACOSX/dir/._name" to "dir/name".
+pub fn apple_double_target(path: &Path) -> Option<PathBuf> {
+    let mut components = path.iter();
+    if components.next()? != "__MACOSX" {
+        return None;
+    }
+    let rest: PathBuf = components.collect();
+    let name = rest.file_name()?.to_str()?.strip_prefix("._")?;
+    if name.is_empty() {
+        return None;
+    }
+    Some(rest.with_file_name(name))
+}
+
+// Extract the metadata as (xattr name, value) pairs.
+pub fn parse_apple_double(data: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+    // magic(4) version(4) filler(16) entry_count(2) entries(12 each)
+    if read_u32(data, 0)? != APPLE_DOUBLE_MAGIC {
+        return None;
+    }
+    let entry_count = read_u16(data, 24)? as usize;
+    let mut attributes = Vec::new();
+    for i in 0..entry_count {
+        let header = 26 + i * 12;
+        let id = read_u32(data, header)?;
+        let offset = read_u32(data, header + 4)? as usize;
+        let length = read_u32(data, header + 8)? as usize;
+        let body = data.get(offset..offset.checked_add(length)?)?;
+        match id {
+            ENTRY_FINDER_INFO => {
+                let finder_info = body.get(..FINDER_INFO_SIZE)?;
+                if finder_info.iter().any(|&b| b != 0) {
+                    attributes.push((b"com.apple.FinderInfo".to_vec(), finder_info.to_vec()));
+                }
+                // Extended attributes follow the Finder info.
+                // Their offsets are relative to the start of the file.
+                if body.len() > FINDER_INFO_SIZE {
+                    parse_attr_header(data, offset + FINDER_INFO_SIZE, &mut attributes)?;
+                }
+            }
+            ENTRY_RESOURCE_FORK if length > 0 => {
+                attributes.push((b"com.apple.ResourceFork".to_vec(), body.to_vec()));
+            }
+            _ => {}
+        }
+    }
+    Some(attributes)
+}
+
+fn parse_attr_header(
+    data: &[u8],
+    start: usize,
+    attributes: &mut Vec<(Vec<u8>, Vec<u8>)>,
+) -> Option<()> {
+    // The header is aligned to 4 bytes after 2 bytes of padding.
+    let mut pos = start + 2;
+    if read_u32(data, pos)? != ATTR_MAGIC {
+        return Some(());
+    }
+    // magic(4) debug_tag(4) total_size(4) data_start(4) data_length(4)
+    // reserved(12) flags(2) attr_count(2)
+    let attr_count = read_u16(data, pos + 34)? as usize;
+    pos += 36;
+    for _ in 0..attr_count {
+        // offset(4) length(4) flags(2) name_length(1) name, aligned to 4
+        let offset = read_u32(data, pos)? as usize;
+        let length = read_u32(data, pos + 4)? as usize;
+        let name_length = *data.get(pos + 10)? as usize;
+        let name = data.get(pos + 11..pos + 11 + name_length)?;
+        let name = name.strip_suffix(b"\0").unwrap_or(name);
+        let value = data.get(offset..offset.checked_add(length)?)?;
+        attributes.push((name.to_vec(), value.to_vec()));
+        pos = (pos + 11 + name_length + 3) & !3;
+    }
+    Some(())
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(pos..pos + 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(pos..pos + 4)?.try_into().unwrap(),
+    ))
+}
@@ -0,0 +1,262 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, Context as _, Result};
+
+use crate::config::BackendConfig;
+use crate::control_chars::display_path;
+use crate::ignore::IgnoreRules;
+use crate::interrupt::CancellationToken;
+use crate::limits::format_size;
+use crate::output::{self, notice, say, summary, Event};
+use crate::{create_staging_dir, move_into_place, staging_parent, Extracted, Options};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Something other than exzip itself that can extract an archive.
+pub trait Backend: Send + Sync {
+    fn name(&self) -> &str;
+    fn handles(&self, archive: &Path) -> bool;
+    // Extract `archive` into the empty directory `dir`, giving up once
+    // `cancel` is cancelled.
+    fn extract(&self, archive: &Path, dir: &Path, cancel: &CancellationToken) -> Result<()>;
+}
+
+// An extractor run as a command, such as unar or 7z, set up in the
+// configuration file.
+struct ExternalBackend {
+    name: String,
+    command: Vec<String>,
+    extensions: Vec<String>,
+}
+
+impl Backend for ExternalBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn handles(&self, archive: &Path) -> bool {
+        let Some(file_name) = archive.file_name() else {
+            return false;
+        };
+        let file_name = file_name.to_string_lossy().to_lowercase();
+        self.extensions.iter().any(|extension| {
+            file_name.ends_with(&format!(
+                ".{}",
+                extension.trim_start_matches('.').to_lowercase()
+            ))
+        })
+    }
+
+    fn extract(&self, archive: &Path, dir: &Path, cancel: &CancellationToken) -> Result<()> {
+        let args: Vec<OsString> = self.command[1..]
+            .iter()
+            .map(|arg| match arg.as_str() {
+                "{archive}" => archive.as_os_str().to_owned(),
+                "{dir}" => dir.as_os_str().to_owned(),
+                arg => arg
+                    .replace("{archive}", &archive.to_string_lossy())
+                    .replace("{dir}", &dir.to_string_lossy())
+                    .into(),
+            })
+            .collect();
+        let mut command = Command::new(&self.command[0]);
+        command.args(args).stdin(Stdio::null());
+        // Stdout is for events alone with --output=json.
+        if output::is_json() {
+            command.stdout(io::stderr());
+        }
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to run {}", self.command[0]))?;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if cancel.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!("Interrupted");
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        if cancel.is_cancelled() {
+            bail!("Interrupted");
+        }
+        if !status.success() {
+            bail!("{} exited with {}", self.command[0], status);
+        }
+        Ok(())
+    }
+}
+
+static BACKENDS: OnceLock<Vec<Box<dyn Backend>>> = OnceLock::new();
+
+pub fn register_backends(configs: Vec<BackendConfig>) {
+    let backends = configs
+        .into_iter()
+        .map(|config| {
+            Box::new(ExternalBackend {
+                name: config.name,
+                command: config.command,
+                extensions: config.extensions,
+            }) as Box<dyn Backend>
+        })
+        .collect();
+    let _ = BACKENDS.set(backends);
+}
+
+// The backend to extract `archive` with instead of reading it as a zip file.
+pub fn find_backend(archive: &Path) -> Option<&'static dyn Backend> {
+    BACKENDS
+        .get()?
+        .iter()
+        .find(|backend| backend.handles(archive))
+        .map(|backend| backend.as_ref())
+}
+
+// Whether the symbolic link at `path` (relative to `root`) leads out of
+// `root`. Dangling links are judged by their target alone.
+fn escapes(root: &Path, path: &Path) -> io::Result<bool> {
+    match fs::canonicalize(root.join(path)) {
+        Ok(resolved) => Ok(!resolved.starts_with(fs::canonicalize(root)?)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let target = fs::read_link(root.join(path))?;
+            let mut depth = path.components().count() - 1;
+            for component in target.components() {
+                match component {
+                    Component::Normal(_) => depth += 1,
+                    Component::CurDir => {}
+                    Component::ParentDir if depth > 0 => depth -= 1,
+                    _ => return Ok(true),
+                }
+            }
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// Hold the output of an external extractor to the rules of exzip's own:
+// junk files are removed, as are special files and symbolic links leading
+// out of `root`, and setuid and setgid bits are dropped. Returns the number
+// of entries left and their size.
+fn sanitize(root: &Path, ignore_rules: &IgnoreRules) -> Result<(usize, u64)> {
+    let mut entries = 0;
+    let mut size = 0;
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(root.join(&dir))? {
+            let entry = entry?;
+            let path = dir.join(entry.file_name());
+            let full_path = entry.path();
+            let metadata = fs::symlink_metadata(&full_path)?;
+            let file_type = metadata.file_type();
+            if ignore_rules.is_ignored(&path) {
+                if file_type.is_dir() {
+                    fs::remove_dir_all(&full_path)?;
+                } else {
+                    fs::remove_file(&full_path)?;
+                }
+                continue;
+            }
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if file_type.is_file() {
+                let mode = metadata.permissions().mode();
+                if mode & 0o6000 != 0 {
+                    fs::set_permissions(&full_path, fs::Permissions::from_mode(mode & 0o1777))?;
+                }
+                size += metadata.len();
+            } else if file_type.is_symlink() {
+                if escapes(root, &path)? {
+                    notice!("Removed a link leading outside: {}", display_path(&path));
+                    fs::remove_file(&full_path)?;
+                    continue;
+                }
+            } else {
+                notice!("Removed a special file: {}", display_path(&path));
+                fs::remove_file(&full_path)?;
+                continue;
+            }
+            entries += 1;
+        }
+    }
+    Ok((entries, size))
+}
+
+// A single directory at the top is the root of the archive, as with zip
+// files, and its contents are moved up. Returns whether there was one.
+fn strip_inner_root(root: &Path) -> Result<bool> {
+    const MOVED_ROOT: &str = ".exzip-root";
+    let entries = fs::read_dir(root)?.collect::<io::Result<Vec<_>>>()?;
+    let [entry] = &entries[..] else {
+        return Ok(false);
+    };
+    if !entry.file_type()?.is_dir() || entry.path().join(MOVED_ROOT).exists() {
+        return Ok(false);
+    }
+    let inner_root = root.join(MOVED_ROOT);
+    fs::rename(entry.path(), &inner_root)?;
+    for child in fs::read_dir(&inner_root)? {
+        let child = child?;
+        fs::rename(child.path(), root.join(child.file_name()))?;
+    }
+    fs::remove_dir(&inner_root)?;
+    Ok(true)
+}
+
+// Extract with a backend into a staging directory, which is checked and
+// moved into place as exzip's own extractions are.
+pub fn extract_with_backend(
+    backend: &dyn Backend,
+    zipfile: &Path,
+    target_path: &Path,
+    options: &Options,
+) -> Result<Extracted> {
+    let (temp_dir_obj, _lock) = create_staging_dir(staging_parent(target_path))?;
+    say!(options.reporter, "{} {}", backend.name(), zipfile.display());
+    backend
+        .extract(zipfile, temp_dir_obj.path(), &options.cancel)
+        .with_context(|| format!("Failed to extract with {}", backend.name()))?;
+
+    let ignore_rules = IgnoreRules::new(
+        options.no_ignore,
+        options.keep_macosx,
+        &options.ignore_pattern,
+    );
+    let (mut entries, mut size) = sanitize(temp_dir_obj.path(), &ignore_rules)?;
+    // Links are checked again from where they end up.
+    if strip_inner_root(temp_dir_obj.path())? {
+        (entries, size) = sanitize(temp_dir_obj.path(), &ignore_rules)?;
+    }
+    move_into_place(temp_dir_obj, target_path, options)?;
+
+    options.reporter.emit(
+        Event::new("done")
+            .str("archive", &zipfile.to_string_lossy())
+            .str("target", &target_path.to_string_lossy())
+            .num("entries", entries as u64)
+            .num("bytes", size)
+            .bool("ok", true),
+    );
+    summary!(
+        options.reporter,
+        "Extracted {} entries ({}) to {}",
+        entries,
+        format_size(size),
+        target_path.display()
+    );
+    Ok(Extracted {
+        target_path: target_path.to_path_buf(),
+        entries,
+        encoding: None,
+    })
+}
@@ -0,0 +1,235 @@
+use std::fs::{self, File};
+use std::io::{self, Write as _};
+use std::os::unix::io::AsRawFd as _;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use cap_std::ambient_authority;
+use cap_std::fs::Dir;
+
+use crate::cli::Args;
+use crate::encoding::get_encoding;
+use crate::ignore::IgnoreRules;
+use crate::interrupt::{interrupted, process_token};
+use crate::limits::format_size;
+use crate::name_index::{decode_names, read_raw_names};
+use crate::output::{outln, Reporter};
+use crate::{
+    create_staging_dir, detect_filename_encoding, get_inner_root, interruptable_copy,
+    is_case_insensitive, open_archive, staging_parent, unzip, UnzipContext, DEFAULT_BUFFER_SIZE,
+};
+
+const PHASES: [&str; 8] = [
+    "open",
+    "detection",
+    "root scan",
+    "inflate",
+    "extract",
+    "write (extract - inflate)",
+    "rename",
+    "cleanup",
+];
+
+#[derive(Default, Clone, Copy)]
+struct Counters {
+    read_calls: u64,
+    write_calls: u64,
+    user: Duration,
+    system: Duration,
+}
+
+// The read and write system calls so far, from /proc/self/io, and the CPU
+// time from getrusage. Other system calls are not counted by the kernel.
+fn counters() -> Counters {
+    let mut counters = Counters::default();
+    if let Ok(io) = fs::read_to_string("/proc/self/io") {
+        for line in io.lines() {
+            match line.split_once(": ") {
+                Some(("syscr", value)) => counters.read_calls = value.parse().unwrap_or(0),
+                Some(("syscw", value)) => counters.write_calls = value.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+    }
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } == 0 {
+        let duration =
+            |time: libc::timeval| Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000);
+        counters.user = duration(usage.ru_utime);
+        counters.system = duration(usage.ru_stime);
+    }
+    counters
+}
+
+// Send stdout to /dev/null while extracting, which lists every entry.
+struct SilencedStdout {
+    saved: libc::c_int,
+}
+
+impl SilencedStdout {
+    fn new() -> io::Result<Self> {
+        io::stdout().flush()?;
+        let null = File::options().write(true).open("/dev/null")?;
+        let saved = unsafe { libc::dup(libc::STDOUT_FILENO) };
+        if saved < 0 || unsafe { libc::dup2(null.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(SilencedStdout { saved })
+    }
+}
+
+impl Drop for SilencedStdout {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        unsafe {
+            libc::dup2(self.saved, libc::STDOUT_FILENO);
+            libc::close(self.saved);
+        }
+    }
+}
+
+// One extraction into a throwaway directory next to the archive, or only
+// decompression to a sink. Returns the time of each phase.
+fn run_once(zipfile: &Path, args: &Args, sink: bool) -> Result<[Option<Duration>; 8]> {
+    let mut times = [None; 8];
+    let mut start = Instant::now();
+    let mut lap = |phase: usize| {
+        let now = Instant::now();
+        times[phase] = Some(now - start);
+        start = now;
+    };
+
+    let mut archive = open_archive(zipfile, &Reporter::default(), !args.no_mmap)?;
+    lap(0);
+
+    let raw_names = read_raw_names(&mut archive)?;
+    let encoding = match &args.oenc {
+        Some(encoding_name) => get_encoding(encoding_name).unwrap(),
+        None => detect_filename_encoding(&raw_names),
+    };
+    let names = decode_names(&raw_names, encoding);
+    lap(1);
+
+    let ignore_rules = IgnoreRules::new(args.no_ignore, args.keep_macosx, &args.ignore_pattern);
+    let inner_root = get_inner_root(&names, &ignore_rules)?;
+    lap(2);
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        interruptable_copy(
+            &mut file,
+            &mut io::sink(),
+            u64::MAX,
+            DEFAULT_BUFFER_SIZE,
+            process_token(),
+        )?;
+        if interrupted() {
+            bail!("Interrupted");
+        }
+    }
+    lap(3);
+    if sink {
+        return Ok(times);
+    }
+
+    let (outer_dir, _lock) = create_staging_dir(staging_parent(zipfile))?;
+    let staging_path = outer_dir.path().join("staging");
+    fs::create_dir(&staging_path)?;
+    let staging_dir = Dir::open_ambient_dir(&staging_path, ambient_authority())?;
+    let case_insensitive = is_case_insensitive(&staging_dir)?;
+    let options = args.options();
+    let context = UnzipContext {
+        zipfile,
+        encoding,
+        names: &names,
+        inner_root: &inner_root,
+        ignore_rules: &ignore_rules,
+        options: &options,
+        case_insensitive,
+        quarantine: None,
+        previous: None,
+        nonempty_dirs: None,
+    };
+    let mut start_extract = Instant::now();
+    {
+        let _silenced = SilencedStdout::new()?;
+        unzip(&mut archive, &staging_dir, context, None, None, None)?;
+    }
+    times[4] = Some(start_extract.elapsed());
+    times[5] = Some(times[4].unwrap().saturating_sub(times[3].unwrap()));
+
+    start_extract = Instant::now();
+    let target_path = outer_dir.path().join("target");
+    fs::rename(&staging_path, &target_path)?;
+    times[6] = Some(start_extract.elapsed());
+
+    start_extract = Instant::now();
+    drop(staging_dir);
+    fs::remove_dir_all(&target_path)?;
+    times[7] = Some(start_extract.elapsed());
+    Ok(times)
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+// Extract an archive several times and report how long each phase took.
+pub fn bench(zipfile: &Path, args: &Args, iterations: usize, sink: bool) -> Result<()> {
+    outln!("bench {}", zipfile.display());
+    let mut archive = open_archive(zipfile, &Reporter::TERMINAL, !args.no_mmap)?;
+    let raw_names = read_raw_names(&mut archive)?;
+    let total_size: u64 = raw_names.iter().map(|raw_name| raw_name.size).sum();
+    drop(archive);
+
+    let mut runs = Vec::new();
+    let before = counters();
+    for _ in 0..iterations.max(1) {
+        runs.push(run_once(zipfile, args, sink)?);
+    }
+    let after = counters();
+    let count = runs.len() as u64;
+
+    outln!(
+        "{} entries, {}, {} iterations",
+        raw_names.len(),
+        format_size(total_size),
+        count
+    );
+    outln!("{:<26}{:>12}{:>12}{:>12}", "phase", "min", "median", "max");
+    for (phase, name) in PHASES.iter().enumerate() {
+        let mut times: Vec<Duration> = runs.iter().filter_map(|run| run[phase]).collect();
+        if times.is_empty() {
+            continue;
+        }
+        times.sort();
+        outln!(
+            "{:<26}{:>12}{:>12}{:>12}",
+            name,
+            millis(times[0]),
+            millis(times[times.len() / 2]),
+            millis(times[times.len() - 1])
+        );
+    }
+
+    // Throughput of the extraction, or of inflating alone into the sink.
+    let phase = if sink { 3 } else { 4 };
+    let mut times: Vec<Duration> = runs.iter().filter_map(|run| run[phase]).collect();
+    times.sort();
+    let median = times[times.len() / 2].as_secs_f64();
+    if median > 0.0 {
+        outln!(
+            "throughput: {}/s",
+            format_size((total_size as f64 / median) as u64)
+        );
+    }
+    outln!(
+        "per iteration: {} read and {} write system calls, {} user and {} system CPU",
+        (after.read_calls - before.read_calls) / count,
+        (after.write_calls - before.write_calls) / count,
+        millis((after.user - before.user) / count as u32),
+        millis((after.system - before.system) / count as u32)
+    );
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd as _;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cap_fs_ext::{DirExt as _, SystemTimeSpec};
+use cap_primitives::time::SystemTime as CapSystemTime;
+use cap_std::fs::Dir;
+use rustix::fs::{
+    linkat, openat, openat2, unlinkat, utimensat, AtFlags, Mode, OFlags, ResolveFlags, Timespec,
+    Timestamps, CWD, UTIME_OMIT,
+};
+use rustix::io::Errno;
+
+// Writes inside the staging directory are resolved with openat2 so that no
+// symlink is followed, even one swapped in after the path was checked.
+// Kernels without openat2 fall back to the cap-std sandbox.
+
+fn open_beneath(dir: &Dir, path: &Path, flags: OFlags, mode: Mode) -> io::Result<Option<File>> {
+    let resolve = ResolveFlags::BENEATH | ResolveFlags::NO_SYMLINKS;
+    match openat2(
+        dir,
+        path,
+        flags | OFlags::CLOEXEC | OFlags::NOFOLLOW,
+        mode,
+        resolve,
+    ) {
+        Ok(fd) => Ok(Some(File::from(fd))),
+        Err(Errno::NOSYS) => Ok(None),
+        Err(Errno::LOOP) | Err(Errno::XDEV) => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Refusing to follow a symlink at {}", path.display()),
+        )),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn create_file(dir: &Dir, path: &Path) -> io::Result<File> {
+    let flags = OFlags::WRONLY | OFlags::CREATE | OFlags::TRUNC;
+    match open_beneath(dir, path, flags, Mode::from_bits_truncate(0o666))? {
+        Some(file) => Ok(file),
+        None => Ok(dir.create(path)?.into_std()),
+    }
+}
+
+// A file created without a name with O_TMPFILE and linked into place only
+// once its data has been checked, so that an interrupted or failed copy
+// never leaves a truncated file behind.
+pub struct TmpFile {
+    pub file: File,
+    parent: File,
+}
+
+// Returns None when the kernel or the filesystem does not support O_TMPFILE.
+pub fn create_tmpfile(dir: &Dir, path: &Path) -> io::Result<Option<TmpFile>> {
+    let flags = OFlags::PATH | OFlags::DIRECTORY;
+    let Some(parent) = open_beneath(dir, parent_of(path), flags, Mode::empty())? else {
+        return Ok(None);
+    };
+    match openat(
+        &parent,
+        ".",
+        OFlags::WRONLY | OFlags::TMPFILE | OFlags::CLOEXEC,
+        Mode::from_bits_truncate(0o666),
+    ) {
+        Ok(fd) => Ok(Some(TmpFile {
+            file: File::from(fd),
+            parent,
+        })),
+        Err(Errno::OPNOTSUPP | Errno::ISDIR | Errno::INVAL) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+impl TmpFile {
+    // Give the file a name in the directory it was created in.
+    pub fn link(&self, path: &Path) -> io::Result<()> {
+        let name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Cannot link a file as \".\"")
+        })?;
+        // Linking with AT_EMPTY_PATH needs CAP_DAC_READ_SEARCH on older
+        // kernels, while this works for any process which has /proc.
+        let source = format!("/proc/self/fd/{}", self.file.as_raw_fd());
+        let link = || {
+            linkat(
+                CWD,
+                source.as_str(),
+                &self.parent,
+                name,
+                AtFlags::SYMLINK_FOLLOW,
+            )
+        };
+        match link() {
+            // Replace a duplicate entry written earlier, as O_TRUNC would.
+            Err(Errno::EXIST) => {
+                unlinkat(&self.parent, name, AtFlags::empty())?;
+                Ok(link()?)
+            }
+            result => Ok(result?),
+        }
+    }
+}
+
+fn parent_of(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    }
+}
+
+fn timespec(time: Option<SystemTime>) -> Timespec {
+    let Some(time) = time else {
+        return Timespec {
+            tv_sec: 0,
+            tv_nsec: UTIME_OMIT,
+        };
+    };
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => Timespec {
+            tv_sec: duration.as_secs() as i64,
+            tv_nsec: duration.subsec_nanos() as _,
+        },
+        Err(err) => {
+            let duration = err.duration();
+            let (sec, nsec) = match duration.subsec_nanos() {
+                0 => (-(duration.as_secs() as i64), 0),
+                nsec => (-(duration.as_secs() as i64) - 1, 1_000_000_000 - nsec),
+            };
+            Timespec {
+                tv_sec: sec,
+                tv_nsec: nsec as _,
+            }
+        }
+    }
+}
+
+// Set the times of an entry without following symlinks. Only the parent is
+// opened, so this works on symlinks and on entries we cannot read.
+pub fn set_times(
+    dir: &Dir,
+    path: &Path,
+    accessed: Option<SystemTime>,
+    modified: Option<SystemTime>,
+) -> io::Result<()> {
+    // The inner root, stripped to ".", is the extraction directory itself.
+    let Some(name) = path.file_name() else {
+        return Ok(());
+    };
+    let flags = OFlags::PATH | OFlags::DIRECTORY;
+    match open_beneath(dir, parent_of(path), flags, Mode::empty())? {
+        Some(parent) => {
+            let times = Timestamps {
+                last_access: timespec(accessed),
+                last_modification: timespec(modified),
+            };
+            utimensat(&parent, name, &times, AtFlags::SYMLINK_NOFOLLOW)?;
+            Ok(())
+        }
+        None => {
+            let to_spec =
+                |time: SystemTime| SystemTimeSpec::Absolute(CapSystemTime::from_std(time));
+            dir.set_symlink_times(path, accessed.map(to_spec), modified.map(to_spec))
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+use crate::cli::Args;
+use crate::control_chars::display_path;
+use crate::encoding::get_encoding;
+use crate::interrupt::process_token;
+use crate::name_index::{decode_names, read_raw_names};
+use crate::output::Reporter;
+use crate::{detect_filename_encoding, interruptable_copy, open_archive, DEFAULT_BUFFER_SIZE};
+
+// Write the data of the named entries to stdout, in the order given. Names
+// are matched as decoded, as `exzip test` lists them, or as sanitized.
+pub fn cat_entries(zipfile: &Path, names: &[PathBuf], args: &Args) -> Result<()> {
+    let mut archive = open_archive(zipfile, &Reporter::TERMINAL, !args.no_mmap)?;
+    let raw_names = read_raw_names(&mut archive)?;
+    let encoding = match args.oenc.as_deref() {
+        Some(encoding_name) => get_encoding(encoding_name).unwrap(),
+        None => detect_filename_encoding(&raw_names),
+    };
+    let decoded_names = decode_names(&raw_names, encoding);
+
+    let mut indices = Vec::with_capacity(names.len());
+    for name in names {
+        let index = decoded_names.iter().position(|decoded_name| {
+            !decoded_name.is_dir
+                && (decoded_name.decoded == *name
+                    || decoded_name.sanitized.as_deref() == Some(name.as_path()))
+        });
+        match index {
+            Some(index) => indices.push(index),
+            None => bail!("No such entry: {}", display_path(name)),
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    for (index, name) in indices.into_iter().zip(names) {
+        let mut file = archive.by_index(index)?;
+        let outcome = match interruptable_copy(
+            &mut file,
+            &mut stdout,
+            u64::MAX,
+            DEFAULT_BUFFER_SIZE,
+            process_token(),
+        ) {
+            Ok(outcome) => outcome,
+            // The reader has seen enough, as with `exzip cat a.zip x | head`.
+            Err(err)
+                if err
+                    .downcast_ref::<io::Error>()
+                    .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe) =>
+            {
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+        if outcome.crc32 != file.crc32() {
+            bail!("CRC mismatch {}", display_path(name));
+        }
+    }
+    Ok(())
+}
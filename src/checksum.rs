@@ -0,0 +1,182 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context as _, Result};
+use sha2::{Digest as _, Sha256};
+
+use crate::interrupt::CancellationToken;
+use crate::md5::Md5;
+use crate::output::say;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "md5",
+            Algorithm::Sha256 => "sha256",
+        }
+    }
+
+    fn digest_len(self) -> usize {
+        match self {
+            Algorithm::Md5 => 16,
+            Algorithm::Sha256 => 32,
+        }
+    }
+}
+
+pub enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Md5 => Hasher::Md5(Md5::default()),
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    pub digest: Vec<u8>,
+}
+
+// A hex digest, with the algorithm told by its length or a "sha256:" or
+// "md5:" prefix.
+pub fn parse_checksum(s: &str) -> Result<Checksum, String> {
+    let (algorithm, hex) = match s.split_once(':') {
+        Some(("sha256", hex)) => (Some(Algorithm::Sha256), hex),
+        Some(("md5", hex)) => (Some(Algorithm::Md5), hex),
+        Some((name, _)) => return Err(format!("unknown algorithm: {}", name)),
+        None => (None, s),
+    };
+    let digest = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| format!("invalid digest: {}", hex))?;
+    let algorithm = match algorithm {
+        Some(algorithm) => algorithm,
+        None => [Algorithm::Sha256, Algorithm::Md5]
+            .into_iter()
+            .find(|algorithm| algorithm.digest_len() == digest.len())
+            .ok_or_else(|| format!("not a SHA-256 or MD5 digest: {}", hex))?,
+    };
+    if digest.len() != algorithm.digest_len() {
+        return Err(format!("invalid {} digest: {}", algorithm.name(), hex));
+    }
+    Ok(Checksum { algorithm, digest })
+}
+
+// The checksum in ZIPFILE.sha256 or ZIPFILE.md5, in the format of sha256sum
+// and md5sum, or a bare digest.
+pub fn sidecar_checksum(zipfile: &Path) -> Result<Option<(PathBuf, Checksum)>> {
+    for algorithm in [Algorithm::Sha256, Algorithm::Md5] {
+        let mut path = zipfile.as_os_str().to_os_string();
+        path.push(".");
+        path.push(algorithm.name());
+        let path = PathBuf::from(path);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", path.display()))
+            }
+        };
+        let hex = content.split_whitespace().next().unwrap_or_default();
+        let checksum = parse_checksum(hex)
+            .ok()
+            .filter(|checksum| checksum.algorithm == algorithm)
+            .with_context(|| format!("No {} digest in {}", algorithm.name(), path.display()))?;
+        return Ok(Some((path, checksum)));
+    }
+    Ok(None)
+}
+
+pub fn file_digest(
+    path: &Path,
+    algorithm: Algorithm,
+    cancel: &CancellationToken,
+) -> Result<Vec<u8>> {
+    reader_digest(&mut File::open(path)?, algorithm, cancel)
+}
+
+pub fn reader_digest(
+    file: &mut impl Read,
+    algorithm: Algorithm,
+    cancel: &CancellationToken,
+) -> Result<Vec<u8>> {
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0; 128 * 1024];
+    loop {
+        if cancel.is_cancelled() {
+            bail!("Interrupted");
+        }
+        match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => hasher.update(&buffer[..len]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(hasher.finalize())
+}
+
+// Check `zipfile` against `expected` or its sidecar file, if any.
+pub fn verify_checksum(
+    zipfile: &Path,
+    expected: Option<&Checksum>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let (source, expected) = match expected {
+        Some(expected) => ("--checksum".to_string(), expected.clone()),
+        None => match sidecar_checksum(zipfile)? {
+            Some((path, checksum)) => (path.display().to_string(), checksum),
+            None => return Ok(()),
+        },
+    };
+    say!("verify {}", zipfile.display());
+    let digest = file_digest(zipfile, expected.algorithm, cancel)
+        .with_context(|| format!("Failed to read {}", zipfile.display()))?;
+    if digest != expected.digest {
+        bail!(
+            "Checksum mismatch for {}: {} expected by {}, got {}",
+            zipfile.display(),
+            to_hex(&expected.digest),
+            source,
+            to_hex(&digest)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,1397 @@
+use std::fs::{self, File};
+use std::io::{self, IsTerminal as _};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context as _, Result};
+use chrono::FixedOffset;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory as _, FromArgMatches as _, Parser};
+use zip::result::ZipError;
+
+use crate::backend::{extract_with_backend, find_backend, register_backends};
+use crate::bench::bench;
+use crate::cat::cat_entries;
+use crate::checksum::{parse_checksum, verify_checksum, Checksum};
+use crate::completions::print_completions;
+use crate::config::{load_config, Config};
+use crate::file_list::{format_file_list, read_file_list};
+use crate::hook::run_exec;
+use crate::i18n::tr;
+use crate::ignore::{read_ignore_file, user_ignore_file};
+use crate::info::archive_info;
+use crate::interrupt::{self, interrupted, register_ctrlc};
+use crate::jobs::run_jobs;
+use crate::libdeflate;
+use crate::limits::{parse_buffer_size, parse_size};
+use crate::list::list_archive;
+use crate::man::print_man_page;
+use crate::mount::mount;
+use crate::name_map::NameMap;
+use crate::notify::notify;
+use crate::output::{self, diag, error, notice, say, status, summary, Event, Reporter, Style};
+use crate::remote::{download, is_download, is_url};
+use crate::report::Report;
+use crate::sandbox::restrict_writes;
+use crate::scan::{expand_glob, find_archives, is_glob};
+use crate::serve::serve;
+use crate::stale::{find_stale, remove_stale};
+use crate::throttle;
+use crate::trash::move_to_trash;
+use crate::tree::print_tree;
+use crate::verify::test_archive;
+use crate::watch::watch;
+use crate::zip_ext::DosTimezone;
+use crate::{
+    extract_into, get_encoding, staging_parent, target_path, unique_path, AppleMetadata,
+    CaseCollisionPolicy, ConflictPolicy, ControlCharPolicy, DuplicatePolicy, Inflater, IoBackend,
+    ManifestAlgorithm, Options, PathTraversalPolicy, Sparse, SpecialFilePolicy,
+};
+// Exit statuses, listed in EXIT_STATUS_HELP.
+pub(crate) const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_UNSUPPORTED: i32 = 3;
+const EXIT_INTERRUPT: i32 = 130;
+
+pub(crate) const EXIT_STATUS_HELP: &str = "\
+Exit status:
+  0    All archives were extracted
+  1    Some archives failed
+  2    Bad arguments, missing archives or a bad configuration file
+  3    All the archives that failed are not zip files or use unsupported features
+  130  Interrupted";
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+#[command(after_help = EXIT_STATUS_HELP)]
+#[command(override_usage = "exzip [OPTIONS] [ZIPFILES]...\n       exzip [OPTIONS] <COMMAND>")]
+// A flag given twice takes the last value, so that --jobs can append to the
+// arguments it passes on.
+#[command(args_override_self = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    // Without a subcommand, `exzip FILE.zip` is `exzip extract FILE.zip`.
+    #[command(flatten)]
+    args: Args,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Args {
+    /// Encoding of entry names without the UTF-8 flag (detected by default)
+    #[arg(short = 'O', env = "EXZIP_ENCODING", global = true)]
+    pub(crate) oenc: Option<String>,
+
+    /// Print only problems and a summary; twice for errors only
+    #[arg(short, long, action = clap::ArgAction::Count, env = "EXZIP_QUIET", global = true)]
+    pub(crate) quiet: u8,
+
+    /// Format of the output on stdout
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    pub(crate) output: OutputFormat,
+
+    /// Also write every message to FILE, whatever -q and --output say
+    #[arg(long, value_name = "FILE", global = true)]
+    pub(crate) log_file: Option<PathBuf>,
+
+    /// When to color the output
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    pub(crate) color: ColorChoice,
+
+    /// Show the sizes, method, timestamp and name encoding of each entry
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    pub(crate) verbose: bool,
+
+    /// Read archives with read(2) instead of mapping them into memory
+    #[arg(long, global = true)]
+    pub(crate) no_mmap: bool,
+
+    /// Read archives given as URLs with HTTP range requests instead of downloading them (list, tree, info and cat)
+    #[arg(long, global = true)]
+    pub(crate) remote_seek: bool,
+
+    /// Write a tab separated map of raw entry names to extracted paths
+    #[arg(long, value_name = "FILE")]
+    pub(crate) write_name_map: Option<PathBuf>,
+
+    /// Write a JSON report of each archive: encoding, inner root, entries and timings
+    #[arg(long, value_name = "FILE")]
+    pub(crate) report: Option<PathBuf>,
+
+    /// Apply the permission bits stored in the archive (setuid, setgid and sticky bits are dropped)
+    #[arg(long)]
+    pub(crate) preserve_permissions: bool,
+
+    /// Create symlink entries as symlinks if their targets stay inside the extracted directory
+    #[arg(long)]
+    pub(crate) allow_symlinks: bool,
+
+    /// Restore the owner and group stored in the archive (requires privileges)
+    #[arg(long)]
+    pub(crate) preserve_owner: bool,
+
+    /// Timezone in which DOS timestamps were recorded: local, UTC or +HH:MM
+    #[arg(long, value_name = "TZ", default_value = "local")]
+    pub(crate) mtime_timezone: DosTimezone,
+
+    /// Interpret DOS timestamps as UTC (same as --mtime-timezone UTC)
+    #[arg(long, conflicts_with = "mtime_timezone")]
+    pub(crate) utc: bool,
+
+    /// Do not restore timestamps; extracted files get the current time
+    #[arg(long)]
+    pub(crate) no_mtime: bool,
+
+    /// What to set the modification time of the target directory to
+    #[arg(long, value_name = "TIME", value_enum, default_value_t = TargetMtime::Archive)]
+    pub(crate) target_mtime: TargetMtime,
+
+    /// Apply extended attributes stored in extra fields (OS/2 EAs)
+    #[arg(long)]
+    pub(crate) xattrs: bool,
+
+    /// How to handle AppleDouble files in __MACOSX
+    #[arg(long, value_enum, default_value_t = AppleMetadata::Skip)]
+    pub(crate) apple_metadata: AppleMetadata,
+
+    /// Extract junk files (__MACOSX, Thumbs.db, .DS_Store) too
+    #[arg(long)]
+    pub(crate) no_ignore: bool,
+
+    /// Extract __MACOSX but still skip the other junk files
+    #[arg(long)]
+    pub(crate) keep_macosx: bool,
+
+    /// Also skip entries matching the glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub(crate) ignore_pattern: Vec<String>,
+
+    /// Also skip entries matching the gitignore-style patterns in FILE, on top of ~/.exzipignore (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub(crate) ignore_file: Vec<PathBuf>,
+
+    /// Do not copy the archive's quarantine attribute to extracted files
+    #[arg(long)]
+    pub(crate) no_quarantine: bool,
+
+    /// Refuse to extract unless the archive has this SHA-256 or MD5 digest; ZIPFILE.sha256 or .md5 is checked otherwise
+    #[arg(long, value_name = "HASH", value_parser = parse_checksum)]
+    pub(crate) checksum: Option<Checksum>,
+
+    /// Write the digests of the extracted files to SHA256SUMS or MD5SUMS in the target directory
+    #[arg(long, value_name = "ALGORITHM")]
+    pub(crate) manifest: Option<ManifestAlgorithm>,
+
+    /// Abort when the extracted data exceeds SIZE in total (e.g. 10G)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub(crate) max_output_size: Option<u64>,
+
+    /// Abort when an entry inflates more than RATIO times its compressed size
+    #[arg(long, value_name = "RATIO")]
+    pub(crate) max_compression_ratio: Option<f64>,
+
+    /// Abort when the archive has more than N entries
+    #[arg(long, value_name = "N")]
+    pub(crate) max_entries: Option<usize>,
+
+    /// Do not check for free disk space before extracting
+    #[arg(long)]
+    pub(crate) no_space_check: bool,
+
+    /// Keep entries failing the CRC check, renamed to *.corrupt
+    #[arg(long)]
+    pub(crate) keep_corrupt: bool,
+
+    /// Salvage what can be read from damaged archives instead of aborting
+    #[arg(long)]
+    pub(crate) best_effort: bool,
+
+    /// What to do when a path appears more than once in the archive
+    #[arg(long, value_enum, default_value_t = DuplicatePolicy::Last)]
+    pub(crate) on_duplicate: DuplicatePolicy,
+
+    /// What to do with names differing only in case on case-insensitive filesystems
+    #[arg(long, value_enum, default_value_t = CaseCollisionPolicy::Error)]
+    pub(crate) on_case_collision: CaseCollisionPolicy,
+
+    /// Rewrite names that are invalid on Windows or exFAT
+    #[arg(long)]
+    pub(crate) portable_names: bool,
+
+    /// What to do with control and bidi override characters in names
+    #[arg(long, value_enum, default_value_t = ControlCharPolicy::Escape)]
+    pub(crate) control_chars: ControlCharPolicy,
+
+    /// Shorten names longer than NAME_MAX, keeping the extension and adding a hash
+    #[arg(long)]
+    pub(crate) truncate_long_names: bool,
+
+    /// How to treat absolute paths and ".." components in entry names
+    #[arg(long, value_enum, default_value_t = PathTraversalPolicy::Sanitize)]
+    pub(crate) path_traversal: PathTraversalPolicy,
+
+    /// What to do with FIFO, device and socket entries
+    #[arg(long, value_enum, default_value_t = SpecialFilePolicy::Skip)]
+    pub(crate) special_files: SpecialFilePolicy,
+
+    /// Recreate FIFO entries instead of applying --special-files to them
+    #[arg(long)]
+    pub(crate) recreate_fifos: bool,
+
+    /// Sync the extracted files and directories to disk before and after the final rename
+    #[arg(long)]
+    pub(crate) fsync: bool,
+
+    /// When replacing a directory, keep its files whose size and CRC match instead of rewriting them
+    #[arg(long)]
+    pub(crate) skip_unchanged: bool,
+
+    /// Do not create directory entries with no files extracted into them
+    #[arg(long)]
+    pub(crate) skip_empty_dirs: bool,
+
+    /// Hard link extracted files with identical contents to the first copy
+    #[arg(long)]
+    pub(crate) dedupe: bool,
+
+    /// Remove staging directories left behind by killed runs without asking
+    #[arg(long)]
+    pub(crate) clean_stale: bool,
+
+    /// Number of threads writing file data (0 for one per CPU)
+    #[arg(long, default_value_t = 1, env = "EXZIP_THREADS", global = true)]
+    pub(crate) threads: usize,
+
+    /// Size of the buffer used to copy file data
+    #[arg(long, value_name = "SIZE", value_parser = parse_buffer_size, default_value = "128K", global = true)]
+    pub(crate) buffer_size: usize,
+
+    /// Overlap decompression with writing, queueing up to SIZE of data (e.g. 8M)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, global = true)]
+    pub(crate) pipeline_buffer: Option<u64>,
+
+    /// Hint the kernel to read the archive ahead and drop extracted data from the page cache
+    #[arg(long, global = true)]
+    pub(crate) fadvise: bool,
+
+    /// Deflate decoder to use
+    #[arg(long, value_enum, default_value_t = Inflater::Miniz, global = true)]
+    pub(crate) inflate: Inflater,
+
+    /// When to leave holes for blocks of zeros in the output; auto does for large entries that compress well
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t = Sparse::Auto, global = true)]
+    pub(crate) sparse: Sparse,
+
+    /// How to write file data (uring is not used with --threads)
+    #[arg(long, value_enum, default_value_t = IoBackend::Std, global = true)]
+    pub(crate) io_backend: IoBackend,
+
+    /// Limit writing to RATE bytes per second (e.g. 50M), shared by --jobs
+    #[arg(long, value_name = "RATE", value_parser = parse_size)]
+    pub(crate) bwlimit: Option<u64>,
+
+    /// Show a progress bar on stderr
+    #[arg(long)]
+    pub(crate) progress: bool,
+
+    /// Send a desktop notification when each archive is done
+    #[arg(long)]
+    pub(crate) notify: bool,
+
+    /// Number of archives to extract at once in separate processes (0 for one per CPU)
+    #[arg(long, default_value_t = 1, conflicts_with_all = ["write_name_map", "report"])]
+    pub(crate) jobs: usize,
+
+    /// Go on with the remaining archives when one fails
+    #[arg(short, long)]
+    pub(crate) keep_going: bool,
+
+    /// Ignore the configuration file
+    #[arg(long, global = true)]
+    pub(crate) no_config: bool,
+
+    // Set for the child processes of --jobs.
+    #[arg(long, hide = true)]
+    pub(crate) job_index: Option<usize>,
+
+    /// Answer yes to every question
+    #[arg(short, long, conflicts_with = "no")]
+    pub(crate) yes: bool,
+
+    /// Answer no to every question
+    #[arg(short, long)]
+    pub(crate) no: bool,
+
+    /// What to do when the target directory already exists
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Prompt, env = "EXZIP_ON_CONFLICT")]
+    pub(crate) on_conflict: ConflictPolicy,
+
+    /// Create the target directories in DIR instead of next to the archives
+    #[arg(short, long, value_name = "DIR", env = "EXZIP_DESTINATION")]
+    pub(crate) destination: Option<PathBuf>,
+
+    /// Name the target directory NAME instead of after the archive (one archive only)
+    #[arg(long = "as", value_name = "NAME")]
+    pub(crate) as_name: Option<PathBuf>,
+
+    /// Replace existing targets without asking (same as --on-conflict=replace)
+    #[arg(short, long)]
+    pub(crate) force: bool,
+
+    /// Remove each archive once its extraction is in place
+    #[arg(
+        long,
+        value_enum,
+        value_name = "HOW",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "delete",
+        conflicts_with_all = ["best_effort", "keep_corrupt"]
+    )]
+    pub(crate) remove_archive: Option<RemoveArchive>,
+
+    /// Run CMD with the shell after each extraction, {} replaced by the target directory
+    #[arg(long, value_name = "CMD")]
+    pub(crate) exec: Option<String>,
+
+    /// Use Landlock to forbid writes outside the directories of the archives
+    #[arg(long)]
+    pub(crate) sandbox: bool,
+
+    /// Also extract the archives listed in FILE, one per line ("-" for stdin)
+    #[arg(long, value_name = "FILE")]
+    pub(crate) files_from: Option<PathBuf>,
+
+    /// Archives in --files-from are separated by NUL, as find -print0 writes them
+    #[arg(short = '0', long, requires = "files_from")]
+    pub(crate) null: bool,
+
+    /// Extract the archives found under directories given as ZIPFILES
+    #[arg(short, long)]
+    pub(crate) recursive: bool,
+
+    /// With -r, only extract archives matching the glob (repeatable)
+    #[arg(long, value_name = "GLOB", requires = "recursive")]
+    pub(crate) include: Vec<String>,
+
+    /// With -r, skip archives and directories matching the glob (repeatable)
+    #[arg(long, value_name = "GLOB", requires = "recursive")]
+    pub(crate) exclude: Vec<String>,
+
+    pub(crate) zipfiles: Vec<PathBuf>,
+}
+
+impl Args {
+    // What extraction needs of the arguments, with the aliases among them
+    // resolved.
+    pub(crate) fn options(&self) -> Options {
+        let (yes, no) = (self.yes, self.no);
+        Options {
+            oenc: self.oenc.clone(),
+            verbose: self.verbose,
+            no_mmap: self.no_mmap,
+            progress: self.progress,
+            preserve_permissions: self.preserve_permissions,
+            allow_symlinks: self.allow_symlinks,
+            preserve_owner: self.preserve_owner,
+            mtime_timezone: if self.utc {
+                DosTimezone::Fixed(FixedOffset::east_opt(0).unwrap())
+            } else {
+                self.mtime_timezone
+            },
+            no_mtime: self.no_mtime,
+            xattrs: self.xattrs,
+            apple_metadata: self.apple_metadata,
+            no_ignore: self.no_ignore,
+            keep_macosx: self.keep_macosx,
+            ignore_pattern: self.ignore_pattern.clone(),
+            no_quarantine: self.no_quarantine,
+            manifest: self.manifest,
+            max_output_size: self.max_output_size,
+            max_compression_ratio: self.max_compression_ratio,
+            max_entries: self.max_entries,
+            no_space_check: self.no_space_check,
+            keep_corrupt: self.keep_corrupt,
+            best_effort: self.best_effort,
+            on_duplicate: self.on_duplicate,
+            on_case_collision: self.on_case_collision,
+            portable_names: self.portable_names,
+            control_chars: self.control_chars,
+            truncate_long_names: self.truncate_long_names,
+            path_traversal: self.path_traversal,
+            special_files: self.special_files,
+            recreate_fifos: self.recreate_fifos,
+            fsync: self.fsync,
+            skip_unchanged: self.skip_unchanged,
+            skip_empty_dirs: self.skip_empty_dirs,
+            dedupe: self.dedupe,
+            threads: self.threads,
+            buffer_size: self.buffer_size,
+            pipeline_buffer: self.pipeline_buffer,
+            fadvise: self.fadvise,
+            inflate: self.inflate,
+            sparse: self.sparse,
+            io_backend: self.io_backend,
+            on_conflict: conflict_policy(self),
+            destination: self.destination.clone(),
+            as_name: self.as_name.clone(),
+            cancel: interrupt::process_token().clone(),
+            reporter: Reporter::TERMINAL,
+            confirm: Some(Box::new(move |prompt| confirm(prompt, yes, no))),
+        }
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Extract archives next to them (the default)
+    #[command(after_help = EXIT_STATUS_HELP)]
+    #[command(args_override_self = true)]
+    Extract(Box<Args>),
+    /// Check the integrity of archives without extracting them
+    Test { zipfiles: Vec<PathBuf> },
+    /// List the entries of an archive with their names decoded
+    List {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+        /// Order of the entries
+        #[arg(long, value_enum, default_value_t = ListSort::Archive)]
+        sort: ListSort,
+        /// Reverse the order
+        #[arg(short, long)]
+        reverse: bool,
+        zipfile: PathBuf,
+        /// Only list entries matching any of these globs
+        patterns: Vec<String>,
+    },
+    /// Show the directory tree extraction would create, with skipped entries
+    Tree { zipfile: PathBuf },
+    /// Show the encoding, sizes and layout of archives
+    Info { zipfiles: Vec<PathBuf> },
+    /// Write the data of entries to stdout
+    Cat {
+        zipfile: PathBuf,
+        /// Entry names as listed, after decoding
+        #[arg(required = true)]
+        names: Vec<PathBuf>,
+    },
+    /// Print a completion script for a shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the man page in roff
+    Man,
+    /// Extract an archive to a throwaway directory repeatedly and report timings
+    Bench {
+        /// Number of extractions
+        #[arg(long, default_value_t = 3)]
+        iterations: usize,
+        /// Only decompress the data, without writing files
+        #[arg(long)]
+        sink: bool,
+        zipfile: PathBuf,
+    },
+    /// Extract zip files as they are completed in DIR, until interrupted
+    Watch {
+        /// Seconds an archive must be left alone before it is extracted
+        #[arg(long, value_name = "SECONDS", default_value_t = 2)]
+        settle: u64,
+        dir: PathBuf,
+    },
+    /// Take extraction requests over a Unix socket as JSON-RPC, until interrupted
+    ///
+    /// Extractions run with the permissions of the server, so only the user
+    /// running it may connect: the socket is made accessible to that user
+    /// alone, and connections from other users are refused.
+    Serve {
+        /// Path of the socket to listen on
+        #[arg(long, value_name = "PATH")]
+        socket: PathBuf,
+    },
+    /// Mount an archive as a read-only filesystem until interrupted
+    Mount {
+        zipfile: PathBuf,
+        mountpoint: PathBuf,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum RemoveArchive {
+    /// Delete the archive
+    Delete,
+    /// Move the archive to the trash
+    Trash,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum TargetMtime {
+    /// The modification time of the archive, so that the two sort together by date
+    Archive,
+    /// The newest modification time of what was extracted
+    Newest,
+    /// Leave it at the time of extraction
+    Now,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ColorChoice {
+    /// Color output to terminals unless NO_COLOR is set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum OutputFormat {
+    /// Lines for people
+    Text,
+    /// One JSON event per line
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ListFormat {
+    /// Aligned columns with sizes for people
+    Table,
+    /// A JSON array of entries
+    Json,
+    /// Comma separated values with a header line
+    Csv,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ListSort {
+    /// As stored in the archive
+    Archive,
+    Name,
+    Size,
+    /// By modification time
+    Time,
+}
+
+// Answers of "always" or "never", by prompt, for the rest of the run.
+static STANDING_ANSWERS: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
+// Returns the answer and whether it stands for later prompts.
+fn parse_answer(input: &str) -> Option<(bool, bool)> {
+    match input.trim().to_lowercase().as_str() {
+        "y" | "yes" => Some((true, false)),
+        "n" | "no" | "" => Some((false, false)),
+        "a" | "always" => Some((true, true)),
+        "never" => Some((false, true)),
+        _ => None,
+    }
+}
+
+fn confirm(prompt: &str, yes: bool, no: bool) -> Result<bool> {
+    if yes || no {
+        return Ok(yes);
+    }
+    let standing = STANDING_ANSWERS.lock().unwrap();
+    if let Some((_, answer)) = standing.iter().find(|(asked, _)| asked == prompt) {
+        return Ok(*answer);
+    }
+    drop(standing);
+    let (answer, stands) = ask(prompt)?;
+    if stands {
+        STANDING_ANSWERS
+            .lock()
+            .unwrap()
+            .push((prompt.to_string(), answer));
+    }
+    Ok(answer)
+}
+
+fn ask(prompt: &str) -> Result<(bool, bool)> {
+    // A frontend answers on stdin with a line of y, n, always or never.
+    if output::is_json() {
+        Event::new("prompt-request").str("prompt", prompt).emit();
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        return Ok(parse_answer(&answer).unwrap_or((false, false)));
+    }
+    // Waiting on a pipe or /dev/null would hang or answer at random.
+    if !io::stdin().is_terminal() {
+        bail!(
+            "Cannot ask \"{}\" without a terminal (use --yes, --no or --force)",
+            prompt
+        );
+    }
+    let answer: String = dialoguer::Input::new()
+        .with_prompt(format!("{} [y/N/always/never]", prompt))
+        .allow_empty(true)
+        .validate_with(|input: &String| -> Result<(), String> {
+            parse_answer(input)
+                .map(|_| ())
+                .ok_or_else(|| tr!("Answer y, n, always or never"))
+        })
+        .interact_text()
+        .map_err(|err| match err {
+            dialoguer::Error::IO(ref inner) if inner.kind() == io::ErrorKind::Interrupted => {
+                anyhow::anyhow!("Interrupted")
+            }
+            _ => anyhow::Error::from(err),
+        })?;
+    Ok(parse_answer(&answer).unwrap())
+}
+
+// Offer to remove staging directories of earlier runs that were killed.
+fn clean_stale_dirs(staging_parent: &Path, args: &Args) -> Result<()> {
+    let stale = find_stale(staging_parent)?;
+    if stale.is_empty() {
+        return Ok(());
+    }
+    for path in &stale {
+        notice!("Stale staging directory: {}", path.display());
+    }
+    let remove = if args.clean_stale || args.yes || args.no {
+        args.clean_stale || args.yes
+    } else if io::stdin().is_terminal() {
+        confirm(&tr!("Remove?"), args.yes, args.no)?
+    } else {
+        notice!("Use --clean-stale to remove them");
+        false
+    };
+    if remove {
+        for path in &stale {
+            remove_stale(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+            say!("Removed {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn conflict_policy(args: &Args) -> ConflictPolicy {
+    if args.force {
+        ConflictPolicy::Replace
+    } else {
+        args.on_conflict
+    }
+}
+
+pub(crate) fn extract(
+    zipfile: &Path,
+    args: &Args,
+    name_map: Option<&mut NameMap>,
+    report: Option<&mut Report>,
+) -> Result<()> {
+    say!("unzip {}", zipfile.display());
+
+    let options = args.options();
+    let mut target_path = target_path(zipfile, &options);
+
+    clean_stale_dirs(staging_parent(&target_path), args)?;
+
+    // Replace, merge and backup act when the extraction is moved into place.
+    if target_path.exists() {
+        notice!("Already exists: {}", target_path.display());
+        match options.on_conflict {
+            ConflictPolicy::Prompt => {
+                if !confirm(&tr!("Replace?"), args.yes, args.no)? {
+                    return Ok(());
+                }
+            }
+            ConflictPolicy::Skip => {
+                status!(Style::Skip, "Skip", "{}", zipfile.display());
+                return Ok(());
+            }
+            ConflictPolicy::Number => {
+                target_path = unique_path(&target_path, |candidate| candidate.exists());
+                notice!("Extracting to {}", target_path.display());
+            }
+            ConflictPolicy::Replace | ConflictPolicy::Merge | ConflictPolicy::Backup => {}
+        }
+    }
+    verify_checksum(zipfile, args.checksum.as_ref(), &options.cancel)?;
+    Event::new("archive-start")
+        .str("archive", &zipfile.to_string_lossy())
+        .str("target", &target_path.to_string_lossy())
+        .emit();
+
+    let extracted = match find_backend(zipfile) {
+        Some(backend) => extract_with_backend(backend, zipfile, &target_path, &options)?,
+        None => extract_into(zipfile, &target_path, &options, name_map, report)?,
+    };
+    if !args.no_mtime {
+        set_target_mtime(zipfile, &extracted.target_path, args.target_mtime).with_context(
+            || {
+                format!(
+                    "Failed to set the time of {}",
+                    extracted.target_path.display()
+                )
+            },
+        )?;
+    }
+    if let Some(command) = &args.exec {
+        run_exec(command, zipfile, &extracted)?;
+    }
+    match args.remove_archive {
+        Some(RemoveArchive::Delete) => {
+            say!("remove {}", zipfile.display());
+            fs::remove_file(zipfile)
+                .with_context(|| format!("Failed to remove {}", zipfile.display()))?;
+        }
+        Some(RemoveArchive::Trash) => {
+            say!("trash {}", zipfile.display());
+            move_to_trash(zipfile)?;
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+// The newest modification time of the files under `dir`, not following
+// symlinks. Directories are left out since those without an entry of their
+// own were just created.
+fn newest_mtime(dir: &Path) -> io::Result<Option<SystemTime>> {
+    let mut newest = None;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let mtime = if metadata.is_dir() {
+            newest_mtime(&entry.path())?
+        } else {
+            Some(metadata.modified()?)
+        };
+        newest = newest.max(mtime);
+    }
+    Ok(newest)
+}
+
+fn set_target_mtime(zipfile: &Path, target_path: &Path, target_mtime: TargetMtime) -> Result<()> {
+    let mtime = match target_mtime {
+        TargetMtime::Archive => Some(fs::metadata(zipfile)?.modified()?),
+        TargetMtime::Newest => newest_mtime(target_path)?,
+        TargetMtime::Now => None,
+    };
+    if let Some(mtime) = mtime {
+        File::open(target_path)?.set_modified(mtime)?;
+    }
+    Ok(())
+}
+
+fn notify_done(zipfile: &Path, success: bool, args: &Args) {
+    let name = zipfile
+        .file_name()
+        .map_or_else(|| zipfile.to_string_lossy(), |name| name.to_string_lossy());
+    let (title, body) = if success {
+        (
+            tr!("Extracted {}", name),
+            target_path(zipfile, &args.options()),
+        )
+    } else {
+        (tr!("Failed to extract {}", name), zipfile.to_path_buf())
+    };
+    if let Err(err) = notify(&title, &body.to_string_lossy()) {
+        notice!("Failed to send a notification: {}", err);
+    }
+}
+
+// EXIT_UNSUPPORTED when the archive is not a zip file or needs a feature
+// the zip crate lacks, such as encryption or a compression method.
+fn failure_status(err: &anyhow::Error) -> i32 {
+    let unsupported = err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<ZipError>(),
+            Some(ZipError::InvalidArchive(_) | ZipError::UnsupportedArchive(_))
+        )
+    });
+    if unsupported {
+        EXIT_UNSUPPORTED
+    } else {
+        EXIT_ERROR
+    }
+}
+
+// The status of a run so far, after another archive failed with `failure`.
+fn combine_status(status: i32, failure: i32) -> i32 {
+    if status == 0 || status == failure {
+        failure
+    } else {
+        EXIT_ERROR
+    }
+}
+
+// Archives given as URLs are downloaded first, unless `remote_seek` says to
+// read them in place.
+fn fetch_zipfiles(zipfiles: &[PathBuf], remote_seek: bool) -> Vec<PathBuf> {
+    let fetch = |zipfile: &PathBuf| {
+        if !is_url(zipfile) || remote_seek {
+            return zipfile.clone();
+        }
+        download(zipfile).unwrap_or_else(|err| {
+            error!("{:?}", err);
+            std::process::exit(if interrupted() {
+                EXIT_INTERRUPT
+            } else {
+                EXIT_ERROR
+            });
+        })
+    };
+    zipfiles.iter().map(fetch).collect()
+}
+
+fn validate_zipfiles(zipfiles: &[PathBuf], need_extension: bool) {
+    for filepath in zipfiles.iter().filter(|filepath| !is_url(filepath)) {
+        if need_extension && filepath.extension().is_none() {
+            diag!("Bad filename {}", filepath.display());
+            std::process::exit(EXIT_USAGE);
+        }
+        if !filepath.exists() {
+            diag!("Not found {}", filepath.display());
+            std::process::exit(EXIT_USAGE);
+        }
+        if !filepath.is_file() {
+            diag!("Not a file {}", filepath.display());
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+}
+
+fn run_jobs_or_exit(args: &Args, jobs: usize) -> ! {
+    // The child processes cannot prompt, so ask about existing targets first.
+    let mut selected = Vec::new();
+    for (index, zipfile) in args.zipfiles.iter().enumerate() {
+        let target_path = target_path(zipfile, &args.options());
+        if target_path.exists() && conflict_policy(args) == ConflictPolicy::Prompt {
+            notice!("Already exists: {}", target_path.display());
+            match confirm(&tr!("Replace?"), args.yes, args.no) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    error!("{:?}", err);
+                    std::process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        selected.push((index, zipfile.as_path()));
+    }
+
+    let failed = run_jobs(
+        &selected,
+        jobs,
+        conflict_policy(args) == ConflictPolicy::Prompt,
+        args.keep_going,
+        &format_file_list(&args.zipfiles, true),
+    );
+    if interrupted() {
+        std::process::exit(EXIT_INTERRUPT);
+    }
+    if args.recursive {
+        summary!(
+            "{} of {} archives extracted",
+            selected.len() - failed.len(),
+            selected.len()
+        );
+    }
+    let mut status = 0;
+    if !failed.is_empty() {
+        diag!("{} of {} archives failed:", failed.len(), selected.len());
+        for (zipfile, failure) in failed {
+            diag!("  {}", zipfile.display());
+            status = combine_status(status, failure);
+        }
+    }
+    std::process::exit(status);
+}
+
+// The archives given, with globs the shell left alone expanded, those listed
+// in --files-from, and with -r those found under the directories given. The
+// children of --jobs read them from stdin instead, as extraction changes the
+// directories.
+fn collect_zipfiles(args: &Args) -> Vec<PathBuf> {
+    let read_list = |path: &Path, null: bool| {
+        read_file_list(path, null).unwrap_or_else(|err| {
+            error!("Failed to read {}: {}", path.display(), err);
+            std::process::exit(EXIT_USAGE);
+        })
+    };
+    if args.job_index.is_some() {
+        return read_list(Path::new("-"), true);
+    }
+    let mut given = args.zipfiles.clone();
+    if let Some(path) = &args.files_from {
+        given.extend(read_list(path, args.null));
+    }
+    let mut zipfiles = Vec::new();
+    for path in given {
+        // Without a match, the glob is reported as not found.
+        if let Some(pattern) = path
+            .to_str()
+            .filter(|path| is_glob(path) && !is_url(Path::new(path)))
+        {
+            if !path.exists() {
+                match expand_glob(pattern) {
+                    Ok(found) if !found.is_empty() => {
+                        zipfiles.extend(found);
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        error!("Failed to expand {}: {}", pattern, err);
+                        std::process::exit(EXIT_ERROR);
+                    }
+                }
+            }
+        }
+        if !(args.recursive && path.is_dir()) {
+            zipfiles.push(path);
+            continue;
+        }
+        match find_archives(&path, &args.include, &args.exclude) {
+            Ok(found) => {
+                notice!("Found {} archives in {}", found.len(), path.display());
+                zipfiles.extend(found);
+            }
+            Err(err) => {
+                error!("Failed to search {}: {}", path.display(), err);
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+    }
+    zipfiles
+}
+
+// Options of extraction before a subcommand, as in `exzip --force test`,
+// would be ignored, so they are an error.
+// The matches of the options in effect are returned too, for telling the
+// options given from their defaults.
+fn parse_cli() -> (Cli, ArgMatches) {
+    let mut command = Cli::command();
+    let mut matches = command.get_matches_mut();
+    if let Some((name, _)) = matches.subcommand() {
+        let misplaced = command
+            .get_arguments()
+            .filter(|arg| !arg.is_global_set())
+            .find(|arg| {
+                matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+            });
+        if let Some(arg) = misplaced {
+            command
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("{} cannot be used before the {} subcommand", arg, name),
+                )
+                .exit();
+        }
+    }
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let matches = match matches.remove_subcommand() {
+        Some((name, extract_matches)) if name == "extract" => extract_matches,
+        _ => matches,
+    };
+    (cli, matches)
+}
+
+// Settings of the configuration file apply where no option says otherwise.
+fn apply_config(args: &mut Args, config: Config, matches: &ArgMatches) {
+    let given = |id: &str| {
+        matches!(
+            matches.value_source(id),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        )
+    };
+    if args.oenc.is_none() {
+        args.oenc = config.encoding;
+    }
+    args.ignore_pattern.extend(config.ignore_patterns);
+    if let Some(on_conflict) = config.on_conflict {
+        if !given("on_conflict") {
+            args.on_conflict = on_conflict;
+        }
+    }
+    if let Some(threads) = config.threads {
+        if !given("threads") {
+            args.threads = threads;
+        }
+    }
+    if let Some(progress) = config.progress {
+        if !given("progress") {
+            args.progress = progress;
+        }
+    }
+    register_backends(config.backends);
+}
+
+// Patterns of ~/.exzipignore and --ignore-file go before the others, so
+// that options can override them with `!`.
+fn load_ignore_files(args: &mut Args) {
+    let mut patterns = Vec::new();
+    if let Some(path) = user_ignore_file().filter(|_| !args.no_config) {
+        match read_ignore_file(&path) {
+            Ok(found) => patterns.extend(found),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                error!("Failed to read {}: {}", path.display(), err);
+                std::process::exit(EXIT_USAGE);
+            }
+        }
+    }
+    for path in &args.ignore_file {
+        match read_ignore_file(path) {
+            Ok(found) => patterns.extend(found),
+            Err(err) => {
+                error!("Failed to read {}: {}", path.display(), err);
+                std::process::exit(EXIT_USAGE);
+            }
+        }
+    }
+    patterns.append(&mut args.ignore_pattern);
+    args.ignore_pattern = patterns;
+}
+
+/// Run the exzip command with the arguments of the process. It exits the
+/// process when done.
+pub fn run() {
+    register_ctrlc();
+
+    let (Cli { command, args }, matches) = parse_cli();
+    let (mut args, command) = match command {
+        Some(Command::Extract(args)) => (*args, None),
+        command => (args, command),
+    };
+    output::set_quiet(args.quiet);
+    output::set_json(args.output == OutputFormat::Json);
+    if let Some(path) = &args.log_file {
+        if let Err(err) = output::open_log(path, args.job_index.is_some()) {
+            error!("Failed to open {}: {}", path.display(), err);
+            std::process::exit(EXIT_ERROR);
+        }
+    }
+    output::set_color(
+        args.color == ColorChoice::Always,
+        args.color == ColorChoice::Never,
+    );
+
+    if let Some(Command::Completions { shell }) = &command {
+        print_completions(Cli::command(), *shell);
+        std::process::exit(0);
+    }
+    if let Some(Command::Man) = &command {
+        print_man_page(Cli::command());
+        std::process::exit(0);
+    }
+
+    if !args.no_config {
+        let config = load_config().unwrap_or_else(|err| {
+            error!("{:?}", err);
+            std::process::exit(EXIT_USAGE);
+        });
+        apply_config(&mut args, config, &matches);
+    }
+    load_ignore_files(&mut args);
+
+    if let Some(encoding_name) = &args.oenc {
+        if get_encoding(encoding_name).is_none() {
+            error!("Unknown encoding {}", encoding_name);
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+
+    if args.inflate == Inflater::Libdeflate && !libdeflate::is_available() {
+        error!("libdeflate is not installed");
+        std::process::exit(EXIT_ERROR);
+    }
+
+    if let Some(Command::Test { zipfiles }) = &command {
+        let zipfiles = &fetch_zipfiles(zipfiles, false);
+        validate_zipfiles(zipfiles, false);
+        let mut status = 0;
+        for filepath in zipfiles {
+            match test_archive(filepath, args.oenc.as_deref(), !args.no_mmap) {
+                Ok(true) => {}
+                Ok(false) => status = combine_status(status, EXIT_ERROR),
+                Err(err) => {
+                    error!("{:?}", err);
+                    status = combine_status(status, failure_status(&err));
+                }
+            }
+            if interrupted() {
+                std::process::exit(EXIT_INTERRUPT);
+            }
+        }
+        std::process::exit(status);
+    }
+
+    if let Some(Command::List {
+        format,
+        sort,
+        reverse,
+        zipfile,
+        patterns,
+    }) = &command
+    {
+        let zipfile = &fetch_zipfiles(std::slice::from_ref(zipfile), args.remote_seek)[0];
+        validate_zipfiles(std::slice::from_ref(zipfile), false);
+        if let Err(err) = list_archive(zipfile, patterns, *format, *sort, *reverse, &args) {
+            error!("{:?}", err);
+            std::process::exit(failure_status(&err));
+        }
+        std::process::exit(0);
+    }
+
+    if let Some(Command::Tree { zipfile }) = &command {
+        let zipfile = &fetch_zipfiles(std::slice::from_ref(zipfile), args.remote_seek)[0];
+        validate_zipfiles(std::slice::from_ref(zipfile), false);
+        if let Err(err) = print_tree(zipfile, &args) {
+            error!("{:?}", err);
+            std::process::exit(failure_status(&err));
+        }
+        std::process::exit(0);
+    }
+
+    if let Some(Command::Info { zipfiles }) = &command {
+        let zipfiles = &fetch_zipfiles(zipfiles, args.remote_seek);
+        validate_zipfiles(zipfiles, false);
+        let mut status = 0;
+        for filepath in zipfiles {
+            if let Err(err) = archive_info(filepath, &args) {
+                error!("{:?}", err);
+                status = combine_status(status, failure_status(&err));
+            }
+        }
+        std::process::exit(status);
+    }
+
+    if let Some(Command::Cat { zipfile, names }) = &command {
+        let zipfile = &fetch_zipfiles(std::slice::from_ref(zipfile), args.remote_seek)[0];
+        validate_zipfiles(std::slice::from_ref(zipfile), false);
+        if let Err(err) = cat_entries(zipfile, names, &args) {
+            error!("{:?}", err);
+            std::process::exit(if interrupted() {
+                EXIT_INTERRUPT
+            } else {
+                failure_status(&err)
+            });
+        }
+        std::process::exit(0);
+    }
+
+    if let Some(Command::Bench {
+        iterations,
+        sink,
+        zipfile,
+    }) = &command
+    {
+        let zipfile = &fetch_zipfiles(std::slice::from_ref(zipfile), false)[0];
+        validate_zipfiles(std::slice::from_ref(zipfile), false);
+        if let Err(err) = bench(zipfile, &args, *iterations, *sink) {
+            error!("{:?}", err);
+            std::process::exit(if interrupted() {
+                EXIT_INTERRUPT
+            } else {
+                failure_status(&err)
+            });
+        }
+        std::process::exit(0);
+    }
+
+    if let Some(Command::Watch { settle, dir }) = &command {
+        if !dir.is_dir() {
+            diag!("Not a directory {}", dir.display());
+            std::process::exit(EXIT_USAGE);
+        }
+        if let Err(err) = watch(dir, Duration::from_secs(*settle), &args) {
+            error!("{:?}", err);
+            std::process::exit(EXIT_ERROR);
+        }
+        std::process::exit(EXIT_INTERRUPT);
+    }
+
+    if let Some(Command::Serve { socket }) = &command {
+        if let Err(err) = serve(socket) {
+            error!("{:?}", err);
+            std::process::exit(EXIT_ERROR);
+        }
+        std::process::exit(EXIT_INTERRUPT);
+    }
+
+    if let Some(Command::Mount {
+        zipfile,
+        mountpoint,
+    }) = &command
+    {
+        let zipfile = &fetch_zipfiles(std::slice::from_ref(zipfile), false)[0];
+        validate_zipfiles(std::slice::from_ref(zipfile), false);
+        if !mountpoint.is_dir() {
+            diag!("Not a directory {}", mountpoint.display());
+            std::process::exit(EXIT_USAGE);
+        }
+        match mount(zipfile, mountpoint, &args) {
+            Ok(true) => std::process::exit(EXIT_INTERRUPT),
+            Ok(false) => std::process::exit(0),
+            Err(err) => {
+                error!("{:?}", err);
+                std::process::exit(failure_status(&err));
+            }
+        }
+    }
+
+    args.zipfiles = fetch_zipfiles(&collect_zipfiles(&args), false);
+    validate_zipfiles(&args.zipfiles, true);
+    if args.checksum.is_some() && args.zipfiles.len() > 1 {
+        diag!("--checksum cannot be used with more than one archive");
+        std::process::exit(EXIT_USAGE);
+    }
+    if let Some(name) = &args.as_name {
+        if args.zipfiles.len() > 1 {
+            diag!("--as cannot be used with more than one archive");
+            std::process::exit(EXIT_USAGE);
+        }
+        // A name, not a path, so that it stays next to the archive or in -d.
+        let mut components = name.components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(std::path::Component::Normal(_)), None)
+        ) {
+            diag!(
+                "--as takes a directory name, not a path: {}",
+                name.display()
+            );
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+    if let Some(dir) = &args.destination {
+        if !dir.is_dir() {
+            diag!("Not a directory {}", dir.display());
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+
+    let mut name_map = match &args.write_name_map {
+        Some(path) => match NameMap::create(path) {
+            Ok(name_map) => Some(name_map),
+            Err(err) => {
+                error!("Failed to create {}: {}", path.display(), err);
+                std::process::exit(EXIT_ERROR);
+            }
+        },
+        None => None,
+    };
+    let mut report = match &args.report {
+        Some(path) => match Report::create(path) {
+            Ok(report) => Some(report),
+            Err(err) => {
+                error!("Failed to create {}: {}", path.display(), err);
+                std::process::exit(EXIT_ERROR);
+            }
+        },
+        None => None,
+    };
+
+    if args.sandbox {
+        // Moving the archive out of its directory is not allowed in there.
+        if args.remove_archive == Some(RemoveArchive::Trash) {
+            diag!("--remove-archive=trash cannot be used with --sandbox");
+            std::process::exit(EXIT_USAGE);
+        }
+        // Where the targets are staged, which with --as or -d need not be
+        // next to the archive.
+        let options = args.options();
+        let targets: Vec<PathBuf> = args
+            .zipfiles
+            .iter()
+            .map(|z| target_path(z, &options))
+            .collect();
+        let mut writable_dirs: Vec<&Path> = targets.iter().map(|t| staging_parent(t)).collect();
+        if args.remove_archive.is_some() {
+            writable_dirs.extend(args.zipfiles.iter().map(|z| staging_parent(z)));
+        }
+        // Downloads are removed from the temporary directory at exit.
+        let temp_dir = std::env::temp_dir();
+        if args.zipfiles.iter().any(|z| is_download(z)) {
+            writable_dirs.push(&temp_dir);
+        }
+        // The report is replaced through a temporary file next to it.
+        if let Some(path) = &args.report {
+            writable_dirs.push(staging_parent(path));
+        }
+        writable_dirs.sort();
+        writable_dirs.dedup();
+        restrict_writes(&writable_dirs).unwrap_or_else(|err| {
+            error!("{:?}", err);
+            std::process::exit(EXIT_ERROR);
+        });
+    }
+
+    let jobs = match args.jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    };
+    if let Some(rate) = args.bwlimit {
+        // Each child of --jobs takes its share of the limit.
+        let processes = match args.job_index {
+            Some(_) => jobs.min(args.zipfiles.len()).max(1) as u64,
+            None => 1,
+        };
+        throttle::set_limit(rate / processes);
+    }
+    if args.job_index.is_none() && jobs > 1 && args.zipfiles.len() > 1 {
+        run_jobs_or_exit(&args, jobs);
+    }
+
+    let zipfiles = match args.job_index {
+        Some(index) => &args.zipfiles[index..=index],
+        None => &args.zipfiles[..],
+    };
+    let mut status = 0;
+    let mut failed = Vec::new();
+    for filepath in zipfiles {
+        let mut success = true;
+        extract(filepath, &args, name_map.as_mut(), report.as_mut()).unwrap_or_else(|err| {
+            error!("{:?}", err);
+            if let Some(report) = report.as_mut() {
+                if let Err(err) = report.add_failure(filepath, &format!("{:#}", err)) {
+                    error!("Failed to write the report: {}", err);
+                }
+            }
+            Event::new("done")
+                .str("archive", &filepath.to_string_lossy())
+                .bool("ok", false)
+                .str("error", &format!("{:#}", err))
+                .emit();
+            status = combine_status(status, failure_status(&err));
+            success = false;
+        });
+
+        if interrupted() {
+            std::process::exit(EXIT_INTERRUPT);
+        }
+        if args.notify {
+            notify_done(filepath, success, &args);
+        }
+        if !success {
+            failed.push(filepath);
+            if !args.keep_going {
+                break;
+            }
+        }
+    }
+    if args.recursive && args.job_index.is_none() {
+        summary!(
+            "{} of {} archives extracted",
+            zipfiles.len() - failed.len(),
+            zipfiles.len()
+        );
+        if !failed.is_empty() {
+            diag!("{} of {} archives failed:", failed.len(), zipfiles.len());
+            for zipfile in failed {
+                diag!("  {}", zipfile.display());
+            }
+        }
+    }
+    std::process::exit(status);
+}
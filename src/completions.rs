@@ -0,0 +1,347 @@
+use clap::{Arg, Command, ValueHint};
+
+use crate::cli::Shell;
+use crate::encoding::encoding_names;
+use crate::output::outln;
+
+// What to complete as the value of an option or a positional argument.
+enum Values {
+    None,
+    Words(Vec<String>),
+    Files,
+    Zipfiles,
+}
+
+fn values(arg: &Arg) -> Values {
+    if arg.get_id() == "oenc" {
+        return Values::Words(encoding_names().into_iter().map(String::from).collect());
+    }
+    if !arg.get_action().takes_values() {
+        return Values::None;
+    }
+    let possible_values: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if !possible_values.is_empty() {
+        return Values::Words(possible_values);
+    }
+    match (arg.get_id().as_str(), arg.get_value_hint()) {
+        ("zipfile" | "zipfiles", _) => Values::Zipfiles,
+        (_, ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath) => Values::Files,
+        _ => Values::Words(Vec::new()),
+    }
+}
+
+fn visible_args(command: &Command) -> impl Iterator<Item = &Arg> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set() && arg.get_id() != "help" && arg.get_id() != "version")
+}
+
+fn option_names(arg: &Arg) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Some(short) = arg.get_short() {
+        names.push(format!("-{}", short));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(format!("--{}", long));
+    }
+    names
+}
+
+fn help(arg: &Arg) -> String {
+    arg.get_help()
+        .map(|help| help.to_string())
+        .unwrap_or_default()
+}
+
+fn subcommands(command: &Command) -> impl Iterator<Item = &Command> {
+    command
+        .get_subcommands()
+        .filter(|subcommand| subcommand.get_name() != "help")
+}
+
+// Print a completion script for `shell`, built from the argument definitions.
+pub fn print_completions(mut command: Command, shell: Shell) {
+    command.build();
+    let script = match shell {
+        Shell::Bash => bash(&command),
+        Shell::Zsh => zsh(&command),
+        Shell::Fish => fish(&command),
+    };
+    outln!("{}", script.trim_end());
+}
+
+fn bash(command: &Command) -> String {
+    let name = command.get_name();
+    let commands: Vec<&Command> = std::iter::once(command)
+        .chain(subcommands(command))
+        .collect();
+
+    let mut script = format!(
+        "_{name}() {{
+    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"
+    local command=\"\" i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case \"${{COMP_WORDS[i]}}\" in
+            {}) command=\"${{COMP_WORDS[i]}}\"; break ;;
+        esac
+    done
+
+    case \"$command:$prev\" in
+",
+        subcommands(command)
+            .map(|subcommand| subcommand.get_name())
+            .collect::<Vec<_>>()
+            .join("|")
+    );
+    for (i, cmd) in commands.iter().enumerate() {
+        let key = if i == 0 { "" } else { cmd.get_name() };
+        for arg in visible_args(cmd).filter(|arg| !arg.is_positional()) {
+            let reply = match values(arg) {
+                Values::None => continue,
+                Values::Words(words) => format!("compgen -W \"{}\" -- \"$cur\"", words.join(" ")),
+                Values::Files | Values::Zipfiles => "compgen -f -- \"$cur\"".to_string(),
+            };
+            for option in option_names(arg) {
+                script.push_str(&format!(
+                    "        \"{}:{}\") COMPREPLY=($({})); return ;;\n",
+                    key, option, reply
+                ));
+            }
+        }
+    }
+    script.push_str("    esac\n\n    local words values\n    case \"$command\" in\n");
+    for (i, cmd) in commands.iter().enumerate() {
+        let words: Vec<String> = visible_args(cmd)
+            .filter(|arg| !arg.is_positional())
+            .flat_map(option_names)
+            .collect();
+        // Positional arguments with fixed values, such as the shell here.
+        let values: Vec<String> = visible_args(cmd)
+            .filter(|arg| arg.is_positional())
+            .flat_map(|arg| match values(arg) {
+                Values::Words(words) => words,
+                _ => Vec::new(),
+            })
+            .collect();
+        script.push_str(&format!(
+            "        {}) words=\"{}\" values=\"{}\" ;;\n",
+            if i == 0 { "\"\"" } else { cmd.get_name() },
+            words.join(" "),
+            values.join(" ")
+        ));
+    }
+    script.push_str(&format!(
+        "    esac
+    if [[ \"$cur\" != -* && -n \"$values\" ]]; then
+        COMPREPLY=($(compgen -W \"$values\" -- \"$cur\"))
+        return
+    fi
+    if [[ \"$cur\" == -* ]]; then
+        COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))
+    elif [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))
+    fi
+    if [[ \"$cur\" != -* ]]; then
+        COMPREPLY+=($(compgen -f -X '!*.[zZ][iI][pP]' -- \"$cur\") $(compgen -d -- \"$cur\"))
+    fi
+}}
+complete -o filenames -F _{name} {name}
+",
+        subcommands(command)
+            .map(|subcommand| subcommand.get_name())
+            .collect::<Vec<_>>()
+            .join(" ")
+    ));
+    script
+}
+
+// A single quoted zsh string.
+fn zsh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn zsh_escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh_specs(command: &Command) -> Vec<String> {
+    let mut specs = Vec::new();
+    for arg in visible_args(command) {
+        let action = match values(arg) {
+            Values::None => String::new(),
+            Values::Words(words) if words.is_empty() => ": :".to_string(),
+            Values::Words(words) => format!(": :({})", words.join(" ")),
+            Values::Files => ": :_files".to_string(),
+            Values::Zipfiles => ": :_files -g '*.(#i)zip'".to_string(),
+        };
+        if arg.is_positional() {
+            let repeat = if arg
+                .get_num_args()
+                .is_some_and(|range| range.max_values() > 1)
+            {
+                "*"
+            } else {
+                ""
+            };
+            specs.push(zsh_quote(&format!("{}{}", repeat, action)));
+            continue;
+        }
+        let repeat = match arg.get_action() {
+            clap::ArgAction::Count | clap::ArgAction::Append => "*",
+            _ => "",
+        };
+        for option in option_names(arg) {
+            let separator = match (action.is_empty(), option.starts_with("--")) {
+                (true, _) => "",
+                (false, true) => "=",
+                (false, false) => "+",
+            };
+            specs.push(zsh_quote(&format!(
+                "{}{}{}[{}]{}",
+                repeat,
+                option,
+                separator,
+                zsh_escape_help(&help(arg)),
+                action
+            )));
+        }
+    }
+    specs
+}
+
+fn zsh(command: &Command) -> String {
+    let name = command.get_name();
+    let mut script = format!("#compdef {name}\n\n");
+    for subcommand in subcommands(command) {
+        script.push_str(&format!(
+            "_{}_{}() {{\n    _arguments -s \\\n        {}\n}}\n\n",
+            name,
+            subcommand.get_name(),
+            zsh_specs(subcommand).join(" \\\n        ")
+        ));
+    }
+    let subcommand_list: Vec<String> = subcommands(command)
+        .map(|subcommand| {
+            let about = subcommand
+                .get_about()
+                .map(|about| about.to_string())
+                .unwrap_or_default();
+            zsh_quote(&format!(
+                "{}:{}",
+                subcommand.get_name(),
+                about.replace(':', "\\:")
+            ))
+        })
+        .collect();
+    let top_level: Vec<String> = zsh_specs(command)
+        .into_iter()
+        .filter(|spec| !spec.starts_with("'*:") && !spec.starts_with("':"))
+        .collect();
+    script.push_str(&format!(
+        "_{name}() {{
+    local curcontext=\"$curcontext\" state line
+    local -a subcommands=(
+        {}
+    )
+    _arguments -C -s \\
+        {} \\
+        '*:: :->rest'
+    case $state in
+        rest)
+            if (( $+functions[_{name}_$words[1]] )); then
+                curcontext=\"${{curcontext%:*}}-$words[1]:\"
+                _{name}_$words[1]
+            else
+                if (( CURRENT == 1 )); then
+                    _describe -t commands command subcommands
+                fi
+                _files -g '*.(#i)zip'
+            fi
+            ;;
+    esac
+}}
+
+_{name} \"$@\"
+",
+        subcommand_list.join("\n        "),
+        top_level.join(" \\\n        ")
+    ));
+    script
+}
+
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish(command: &Command) -> String {
+    let name = command.get_name();
+    let subcommand_names: Vec<&str> = subcommands(command)
+        .map(|subcommand| subcommand.get_name())
+        .collect();
+    let mut script = String::new();
+    // Subcommands are only offered before any other word.
+    for subcommand in subcommands(command) {
+        let about = subcommand
+            .get_about()
+            .map(|about| about.to_string())
+            .unwrap_or_default();
+        script.push_str(&format!(
+            "complete -c {} -n __fish_use_subcommand -f -a {} -d {}\n",
+            name,
+            subcommand.get_name(),
+            fish_quote(&about)
+        ));
+    }
+    let top_level = format!(
+        "'not __fish_seen_subcommand_from {}'",
+        subcommand_names.join(" ")
+    );
+    let conditions =
+        std::iter::once((command, top_level)).chain(subcommands(command).map(|subcommand| {
+            (
+                subcommand,
+                format!("'__fish_seen_subcommand_from {}'", subcommand.get_name()),
+            )
+        }));
+    for (cmd, condition) in conditions {
+        for arg in visible_args(cmd) {
+            if arg.is_positional() {
+                if matches!(values(arg), Values::Zipfiles) {
+                    script.push_str(&format!(
+                        "complete -c {} -n {} -k -a '(__fish_complete_suffix .zip)'\n",
+                        name, condition
+                    ));
+                }
+                continue;
+            }
+            let mut line = format!("complete -c {} -n {}", name, condition);
+            if let Some(short) = arg.get_short() {
+                line.push_str(&format!(" -s {}", short));
+            }
+            if let Some(long) = arg.get_long() {
+                line.push_str(&format!(" -l {}", long));
+            }
+            match values(arg) {
+                Values::None => {}
+                Values::Words(words) => {
+                    line.push_str(&format!(" -x -a {}", fish_quote(&words.join(" "))))
+                }
+                Values::Files | Values::Zipfiles => line.push_str(" -r -F"),
+            }
+            if arg.get_help().is_some() {
+                line.push_str(&format!(" -d {}", fish_quote(&help(arg))));
+            }
+            script.push_str(&line);
+            script.push('\n');
+        }
+    }
+    script
+}
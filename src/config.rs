@@ -0,0 +1,175 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use clap::ValueEnum as _;
+
+use crate::ConflictPolicy;
+
+// Only a flat subset of TOML is understood: `key = "value"`, `key = 4`,
+// `key = true` and `key = ["value", ...]` lines and comments. Options given
+// on the command line win over these.
+#[derive(Default, Debug)]
+pub struct Config {
+    pub encoding: Option<String>,
+    pub ignore_patterns: Vec<String>,
+    pub on_conflict: Option<ConflictPolicy>,
+    pub threads: Option<usize>,
+    pub progress: Option<bool>,
+    pub backends: Vec<BackendConfig>,
+}
+
+// An external extractor, from `backend.NAME.command` and
+// `backend.NAME.extensions`.
+#[derive(Default, Debug)]
+pub struct BackendConfig {
+    pub name: String,
+    // {archive} and {dir} are replaced in each argument.
+    pub command: Vec<String>,
+    pub extensions: Vec<String>,
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("exzip").join("config.toml"))
+}
+
+pub fn load_config() -> Result<Config> {
+    let Some(path) = config_path() else {
+        return Ok(Config::default());
+    };
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    parse_config(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn parse_config(content: &str) -> Result<Config> {
+    let mut config = Config::default();
+    for (lineno, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("line {}: expected `key = value`", lineno + 1);
+        };
+        let key = key.trim();
+        let value = value.trim();
+        let context = || format!("line {}: invalid value for {}", lineno + 1, key);
+        match key {
+            "encoding" => config.encoding = Some(parse_string(value).with_context(context)?),
+            "ignore_patterns" => {
+                config.ignore_patterns = parse_array(value).with_context(context)?
+            }
+            "on_conflict" => {
+                let value = parse_string(value).with_context(context)?;
+                let policy = ConflictPolicy::from_str(&value, false)
+                    .map_err(|err| anyhow!(err))
+                    .with_context(context)?;
+                config.on_conflict = Some(policy);
+            }
+            "threads" => {
+                let value = parse_string(value).with_context(context)?;
+                config.threads = Some(value.parse().with_context(context)?);
+            }
+            "progress" => {
+                let value = parse_string(value).with_context(context)?;
+                config.progress = Some(value.parse().with_context(context)?);
+            }
+            _ => {
+                let Some((name, field)) = key
+                    .strip_prefix("backend.")
+                    .and_then(|rest| rest.rsplit_once('.'))
+                else {
+                    bail!("line {}: unknown key {}", lineno + 1, key);
+                };
+                let index = match config.backends.iter().position(|b| b.name == name) {
+                    Some(index) => index,
+                    None => {
+                        config.backends.push(BackendConfig {
+                            name: name.to_owned(),
+                            ..Default::default()
+                        });
+                        config.backends.len() - 1
+                    }
+                };
+                let backend = &mut config.backends[index];
+                match field {
+                    "command" => backend.command = parse_array(value).with_context(context)?,
+                    "extensions" => {
+                        backend.extensions = parse_array(value).with_context(context)?
+                    }
+                    _ => bail!("line {}: unknown key {}", lineno + 1, key),
+                }
+            }
+        }
+    }
+    for backend in &config.backends {
+        if backend.command.is_empty() || backend.extensions.is_empty() {
+            bail!(
+                "backend.{} needs both a command and extensions",
+                backend.name
+            );
+        }
+    }
+    Ok(config)
+}
+
+fn parse_array(value: &str) -> Result<Vec<String>> {
+    let Some(rest) = value.strip_prefix('[') else {
+        bail!("expected an array");
+    };
+    let mut items = Vec::new();
+    let mut rest = rest.trim_start();
+    loop {
+        if let Some(after) = rest.strip_prefix(']') {
+            let trailing = after.trim();
+            if !trailing.is_empty() && !trailing.starts_with('#') {
+                bail!("unexpected trailing characters");
+            }
+            return Ok(items);
+        }
+        let Some(quoted) = rest.strip_prefix('"') else {
+            bail!("expected a string");
+        };
+        let Some(end) = quoted.find('"') else {
+            bail!("unterminated string");
+        };
+        items.push(quoted[..end].to_owned());
+        rest = quoted[end + 1..].trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after.trim_start();
+        } else if !rest.starts_with(']') {
+            bail!("expected `,` or `]`");
+        }
+    }
+}
+
+fn parse_string(value: &str) -> Result<String> {
+    let value = match value.find(" #") {
+        Some(pos) if !value.starts_with('"') => value[..pos].trim_end(),
+        _ => value,
+    };
+    if let Some(rest) = value.strip_prefix('"') {
+        let Some(end) = rest.find('"') else {
+            bail!("unterminated string");
+        };
+        let trailing = rest[end + 1..].trim();
+        if !trailing.is_empty() && !trailing.starts_with('#') {
+            bail!("unexpected trailing characters");
+        }
+        Ok(rest[..end].to_owned())
+    } else {
+        Ok(value.to_owned())
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+// C0/C1 control characters and the bidirectional formatting characters
+// that can disguise an extension (e.g. "invoice\u{202e}fdp.exe").
+fn is_suspicious(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+        )
+}
+
+pub fn contains_suspicious(path: &Path) -> bool {
+    path.to_string_lossy().chars().any(is_suspicious)
+}
+
+pub fn escape_str(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        if !is_suspicious(c) {
+            result.push(c);
+        } else if c.is_ascii() {
+            write!(result, "\\x{:02x}", c as u32).unwrap();
+        } else {
+            write!(result, "\\u{{{:04x}}}", c as u32).unwrap();
+        }
+    }
+    result
+}
+
+// For printing entry names without letting them drive the terminal.
+pub fn display_path(path: &Path) -> String {
+    escape_str(&path.to_string_lossy())
+}
+
+pub fn escape_path(path: &Path) -> PathBuf {
+    path.iter()
+        .map(|name| escape_str(&name.to_string_lossy()))
+        .collect()
+}
+
+pub fn strip_path(path: &Path) -> PathBuf {
+    path.iter()
+        .map(|name| {
+            let stripped: String = name
+                .to_string_lossy()
+                .chars()
+                .filter(|&c| !is_suspicious(c))
+                .collect();
+            // Nor may stripping turn a name into one that means another
+            // directory.
+            match stripped.as_str() {
+                "" | "." | ".." => "_".to_owned(),
+                _ => stripped,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_control_and_bidi_characters() {
+        assert!(contains_suspicious(Path::new("a\nb.txt")));
+        assert!(contains_suspicious(Path::new("invoice\u{202e}fdp.exe")));
+        assert!(!contains_suspicious(Path::new("日本語/ファイル.txt")));
+    }
+
+    #[test]
+    fn escapes_each_component() {
+        assert_eq!(
+            escape_path(Path::new("d\x1b/invoice\u{202e}fdp.exe")),
+            Path::new("d\\x1b/invoice\\u{202e}fdp.exe")
+        );
+    }
+
+    #[test]
+    fn strips_to_an_underscore_when_nothing_is_left() {
+        assert_eq!(strip_path(Path::new("\u{200f}/a\tb")), Path::new("_/ab"));
+    }
+
+    #[test]
+    fn strips_to_an_underscore_instead_of_a_dot_or_dot_dot() {
+        assert_eq!(strip_path(Path::new("a/\u{202e}../b")), Path::new("a/_/b"));
+        assert_eq!(strip_path(Path::new(".\u{200e}/b")), Path::new("_/b"));
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::fs::MetadataExt as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use cap_std::fs::Dir;
+
+use crate::checksum::{reader_digest, Algorithm};
+use crate::interrupt::CancellationToken;
+use crate::output::{status, Reporter, Style};
+
+// For --dedupe: once the entries are written, replace each file with the
+// same contents as an earlier one by a hard link to it. Only files sharing
+// a CRC are read, and the contents are compared by SHA-256. Files differing
+// in mode or owner are kept apart, but a link takes the times of the first
+// copy.
+#[derive(Default)]
+pub struct Dedupe {
+    // The intact files written, with their CRCs, in the order of the archive.
+    files: Vec<(u32, PathBuf)>,
+}
+
+impl Dedupe {
+    pub fn add(&mut self, path: &Path, crc32: u32) {
+        self.files.push((crc32, path.to_path_buf()));
+    }
+
+    // Returns the number of files replaced and the bytes freed.
+    pub fn link(
+        self,
+        dst_root: &Dir,
+        cancel: &CancellationToken,
+        reporter: &Reporter,
+    ) -> Result<(usize, u64)> {
+        let mut counts = HashMap::new();
+        for (crc32, _) in &self.files {
+            *counts.entry(*crc32).or_insert(0) += 1;
+        }
+        let mut firsts = HashMap::new();
+        let mut linked = 0;
+        let mut freed = 0;
+        for (crc32, path) in self.files {
+            if counts[&crc32] < 2 {
+                continue;
+            }
+            let metadata = dst_root.symlink_metadata(&path)?;
+            if !metadata.is_file() || metadata.len() == 0 {
+                continue;
+            }
+            // With --preserve-permissions a file may no longer be readable
+            // by the user; it is left as it is.
+            let mut file = match dst_root.open(&path) {
+                Ok(file) => file,
+                Err(err) if err.kind() == io::ErrorKind::PermissionDenied => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let digest = reader_digest(&mut file, Algorithm::Sha256, cancel)?;
+            let key = (
+                metadata.len(),
+                metadata.mode(),
+                metadata.uid(),
+                metadata.gid(),
+                digest,
+            );
+            match firsts.get(&key) {
+                None => {
+                    firsts.insert(key, (path, metadata.ino()));
+                }
+                // Already a hard link to it.
+                Some((_, ino)) if *ino == metadata.ino() => {}
+                Some((first, _)) => {
+                    if !replace_with_link(dst_root, first, &path)? {
+                        continue;
+                    }
+                    status!(
+                        reporter,
+                        Style::Plain,
+                        "Dedupe",
+                        "{} -> {}",
+                        path.display(),
+                        first.display()
+                    );
+                    linked += 1;
+                    if metadata.nlink() == 1 {
+                        freed += metadata.len();
+                    }
+                }
+            }
+        }
+        Ok((linked, freed))
+    }
+}
+
+// Link under a temporary name and rename over `path`, so that the file is
+// kept when the link cannot be made. Returns false in that case.
+fn replace_with_link(dst_root: &Dir, first: &Path, path: &Path) -> io::Result<bool> {
+    let mut temp_name = OsString::from(".exzip-dedupe-");
+    temp_name.push(path.file_name().unwrap());
+    let temp_path = path.with_file_name(temp_name);
+    match dst_root.hard_link(first, dst_root, &temp_path) {
+        Ok(()) => {}
+        // Too many links to the first copy, or not allowed here.
+        Err(err)
+            if err.raw_os_error() == Some(libc::EMLINK)
+                || err.kind() == io::ErrorKind::PermissionDenied =>
+        {
+            return Ok(false)
+        }
+        Err(err) => return Err(err),
+    }
+    dst_root.rename(&temp_path, dst_root, path)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use cap_std::ambient_authority;
+
+    use super::*;
+
+    fn ino(dir: &Dir, path: &str) -> u64 {
+        dir.metadata(path).unwrap().ino()
+    }
+
+    #[test]
+    fn links_identical_files_to_the_first() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = Dir::open_ambient_dir(temp_dir.path(), ambient_authority()).unwrap();
+        let mut dedupe = Dedupe::default();
+        for (path, contents) in [
+            ("a", "same"),
+            ("b", "other"),
+            ("c", "same"),
+            ("d", "same"),
+            ("e", ""),
+            ("f", ""),
+        ] {
+            dir.write(path, contents).unwrap();
+            dedupe.add(Path::new(path), crc32fast::hash(contents.as_bytes()));
+        }
+        let (linked, freed) = dedupe
+            .link(&dir, &CancellationToken::new(), &Reporter::default())
+            .unwrap();
+        assert_eq!((linked, freed), (2, 8));
+        assert_eq!(ino(&dir, "c"), ino(&dir, "a"));
+        assert_eq!(ino(&dir, "d"), ino(&dir, "a"));
+        assert_ne!(ino(&dir, "b"), ino(&dir, "a"));
+        // Empty files cost nothing and are left alone.
+        assert_ne!(ino(&dir, "f"), ino(&dir, "e"));
+        assert_eq!(dir.read_to_string("d").unwrap(), "same");
+    }
+
+    #[test]
+    fn keeps_files_sharing_only_a_crc() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = Dir::open_ambient_dir(temp_dir.path(), ambient_authority()).unwrap();
+        let mut dedupe = Dedupe::default();
+        dir.write("a", "one").unwrap();
+        dir.write("b", "two").unwrap();
+        dedupe.add(Path::new("a"), 1);
+        dedupe.add(Path::new("b"), 1);
+        let (linked, _) = dedupe
+            .link(&dir, &CancellationToken::new(), &Reporter::default())
+            .unwrap();
+        assert_eq!(linked, 0);
+        assert_ne!(ino(&dir, "b"), ino(&dir, "a"));
+    }
+}
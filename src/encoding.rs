@@ -3,25 +3,146 @@ use std::sync::LazyLock;
 
 use encoding_rs::Encoding;
 
+/// An encoding of entry names without the UTF-8 flag.
 #[derive(Clone, Copy)]
 pub enum ZipEncoding {
     Cp437,
     EncodingRs(&'static Encoding),
 }
 
+impl ZipEncoding {
+    /// The canonical name, such as "Shift_JIS" or "cp437".
+    pub fn name(&self) -> &'static str {
+        match self {
+            ZipEncoding::Cp437 => "cp437",
+            ZipEncoding::EncodingRs(encoding) => encoding.name(),
+        }
+    }
+
+    /// Decode a name, replacing what cannot be decoded.
+    pub fn decode_lossy(&self, bytes: &[u8]) -> String {
+        match self {
+            ZipEncoding::Cp437 => decode_cp437(bytes),
+            ZipEncoding::EncodingRs(encoding) => {
+                let (decoded_cow, _, _malformed) = encoding.decode(bytes);
+                decoded_cow.into_owned()
+            }
+        }
+    }
+}
+
+// Keys are normalized by normalize_name().
 static NAME_TABLE: LazyLock<HashMap<&'static str, ZipEncoding>> = LazyLock::new(|| {
     let mut m = HashMap::new();
-    m.insert("cp437", ZipEncoding::Cp437);
-    m.insert("cp932", ZipEncoding::EncodingRs(encoding_rs::SHIFT_JIS));
+    for name in ["cp437", "ibm437", "dos437", "oem437"] {
+        m.insert(name, ZipEncoding::Cp437);
+    }
+    for name in [
+        "cp932",
+        "ms932",
+        "windows31j",
+        "sjis",
+        "shiftjis",
+        "mskanji",
+        "windows932",
+    ] {
+        m.insert(name, ZipEncoding::EncodingRs(encoding_rs::SHIFT_JIS));
+    }
+    for name in ["eucjp", "ujis"] {
+        m.insert(name, ZipEncoding::EncodingRs(encoding_rs::EUC_JP));
+    }
+    for name in ["latin1", "iso88591", "cp1252", "windows1252"] {
+        m.insert(name, ZipEncoding::EncodingRs(encoding_rs::WINDOWS_1252));
+    }
+    for name in ["utf8", "cp65001"] {
+        m.insert(name, ZipEncoding::EncodingRs(encoding_rs::UTF_8));
+    }
+    for name in ["cp936", "gbk"] {
+        m.insert(name, ZipEncoding::EncodingRs(encoding_rs::GBK));
+    }
+    for name in ["cp949", "uhc"] {
+        m.insert(name, ZipEncoding::EncodingRs(encoding_rs::EUC_KR));
+    }
+    for name in ["cp950", "big5"] {
+        m.insert(name, ZipEncoding::EncodingRs(encoding_rs::BIG5));
+    }
     m
 });
 
+// The names -O accepts besides the WHATWG labels, for shell completion.
+pub fn encoding_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = NAME_TABLE.keys().copied().collect();
+    names.sort_unstable();
+    names
+}
+
+// Lowercase and drop separators so that "Shift_JIS", "shift-jis" and
+// "SHIFT JIS" look up the same entry.
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' ' | '.'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// CP437 code points for 0x80..=0xFF. The lower half is identical to ASCII.
+static CP437_HIGH: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E5}', '\u{00E7}',
+    '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}', '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}',
+    '\u{00C9}', '\u{00E6}', '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00A2}', '\u{00A3}', '\u{00A5}', '\u{20A7}', '\u{0192}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}',
+    '\u{00BF}', '\u{2310}', '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}', '\u{2562}', '\u{2556}',
+    '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{255C}', '\u{255B}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{255E}', '\u{255F}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{2567}',
+    '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256B}',
+    '\u{256A}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{258C}', '\u{2590}', '\u{2580}',
+    '\u{03B1}', '\u{00DF}', '\u{0393}', '\u{03C0}', '\u{03A3}', '\u{03C3}', '\u{00B5}', '\u{03C4}',
+    '\u{03A6}', '\u{0398}', '\u{03A9}', '\u{03B4}', '\u{221E}', '\u{03C6}', '\u{03B5}', '\u{2229}',
+    '\u{2261}', '\u{00B1}', '\u{2265}', '\u{2264}', '\u{2320}', '\u{2321}', '\u{00F7}', '\u{2248}',
+    '\u{00B0}', '\u{2219}', '\u{00B7}', '\u{221A}', '\u{207F}', '\u{00B2}', '\u{25A0}', '\u{00A0}',
+];
+
+pub fn decode_cp437(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b < 0x80 {
+                b as char
+            } else {
+                CP437_HIGH[(b - 0x80) as usize]
+            }
+        })
+        .collect()
+}
+
+/// The encoding called `name`, such as "cp932", "sjis" or "euc-jp". Case,
+/// hyphens and underscores do not matter.
 pub fn get_encoding(name: &str) -> Option<ZipEncoding> {
     let name_label = name.as_bytes();
-    let from_name_table = NAME_TABLE.get(&name.to_lowercase() as &str);
+    let from_name_table = NAME_TABLE.get(&normalize_name(name) as &str);
     if let Some(&encoding) = from_name_table {
         Some(encoding)
     } else {
         Encoding::for_label(name_label).map(ZipEncoding::EncodingRs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cp437_keeps_ascii() {
+        assert_eq!(decode_cp437(b"Readme 1.txt"), "Readme 1.txt");
+    }
+
+    #[test]
+    fn cp437_maps_the_high_half() {
+        assert_eq!(decode_cp437(&[0x80]), "\u{00C7}");
+        assert_eq!(decode_cp437(&[0x81, 0xE1]), "\u{00FC}\u{00DF}");
+        assert_eq!(decode_cp437(&[0xFF]), "\u{00A0}");
+    }
+}
@@ -0,0 +1,216 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+const EOCD_SIGNATURE: &[u8; 4] = b"PK\x05\x06";
+const EOCD_SIZE: u64 = 22;
+const CENTRAL_HEADER_SIGNATURE: &[u8; 4] = b"PK\x01\x02";
+const ZIP64_LOCATOR_SIGNATURE: &[u8; 4] = b"PK\x06\x07";
+const ZIP64_LOCATOR_SIZE: u64 = 20;
+
+// Where the archive ends inside a file, and the corrected comment length
+// when the recorded one runs past the end of the file.
+#[derive(Clone, Copy, Debug)]
+pub struct ArchiveEnd {
+    pub end: u64,
+    pub comment_length_fix: Option<(u64, u16)>,
+}
+
+// A reader which only exposes the archive part of the inner reader, with the
+// comment length fixed up if needed.
+pub struct Bounded<R> {
+    inner: R,
+    archive_end: ArchiveEnd,
+    pos: u64,
+}
+
+impl<R: Seek> Bounded<R> {
+    pub fn new(mut inner: R, archive_end: ArchiveEnd) -> io::Result<Self> {
+        inner.seek(SeekFrom::Start(0))?;
+        Ok(Bounded {
+            inner,
+            archive_end,
+            pos: 0,
+        })
+    }
+}
+
+impl<R: Read> Read for Bounded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.archive_end.end.saturating_sub(self.pos);
+        let max = (buf.len() as u64).min(remaining) as usize;
+        let length = self.inner.read(&mut buf[..max])?;
+        if let Some((fix_pos, comment_length)) = self.archive_end.comment_length_fix {
+            for (i, byte) in comment_length.to_le_bytes().into_iter().enumerate() {
+                let offset = fix_pos + i as u64;
+                if offset >= self.pos && offset < self.pos + length as u64 {
+                    buf[(offset - self.pos) as usize] = byte;
+                }
+            }
+        }
+        self.pos += length as u64;
+        Ok(length)
+    }
+}
+
+impl<R: Seek> Seek for Bounded<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.archive_end.end.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        let target = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Seek to a negative position")
+        })?;
+        self.pos = self.inner.seek(SeekFrom::Start(target))?;
+        Ok(self.pos)
+    }
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, pos: u64, buf: &mut [u8]) -> io::Result<bool> {
+    reader.seek(SeekFrom::Start(pos))?;
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+// Whether the record at `pos` looks like a real end of central directory
+// rather than a stray signature in file data or trailing garbage.
+fn is_plausible_eocd<R: Read + Seek>(reader: &mut R, pos: u64) -> io::Result<bool> {
+    let mut record = [0u8; EOCD_SIZE as usize];
+    if !read_at(reader, pos, &mut record)? {
+        return Ok(false);
+    }
+    let entries = u16::from_le_bytes([record[10], record[11]]);
+    let cd_size = u32::from_le_bytes(record[12..16].try_into().unwrap());
+    let cd_offset = u32::from_le_bytes(record[16..20].try_into().unwrap());
+    if entries == u16::MAX || cd_size == u32::MAX || cd_offset == u32::MAX {
+        let mut signature = [0u8; 4];
+        return Ok(pos >= ZIP64_LOCATOR_SIZE
+            && read_at(reader, pos - ZIP64_LOCATOR_SIZE, &mut signature)?
+            && &signature == ZIP64_LOCATOR_SIGNATURE);
+    }
+    if entries == 0 {
+        return Ok(cd_size == 0);
+    }
+    // Data prepended to the archive shifts the central directory, so it is
+    // located from the end of the record rather than from cd_offset.
+    let Some(cd_start) = pos.checked_sub(cd_size as u64) else {
+        return Ok(false);
+    };
+    let mut signature = [0u8; 4];
+    Ok(read_at(reader, cd_start, &mut signature)? && &signature == CENTRAL_HEADER_SIGNATURE)
+}
+
+// Scan backwards over the whole file for the end of central directory record
+// and return the offset just past it and its comment, so that archives with
+// data appended after them can be opened.
+pub fn find_archive_end<R: Read + Seek>(reader: &mut R) -> io::Result<Option<ArchiveEnd>> {
+    const CHUNK: u64 = 64 * 1024;
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    let sig_len = EOCD_SIGNATURE.len() as u64;
+    let mut buf = vec![0u8; (CHUNK + sig_len) as usize];
+    let mut chunk_end = file_len;
+    while chunk_end > 0 {
+        let chunk_start = chunk_end.saturating_sub(CHUNK);
+        // Overlap with the next chunk so a signature across the boundary is found.
+        let read_end = (chunk_end + sig_len - 1).min(file_len);
+        let length = (read_end - chunk_start) as usize;
+        reader.seek(SeekFrom::Start(chunk_start))?;
+        reader.read_exact(&mut buf[..length])?;
+        let candidates = length.saturating_sub(sig_len as usize - 1);
+        for i in (0..candidates.min((chunk_end - chunk_start) as usize)).rev() {
+            if &buf[i..i + sig_len as usize] != EOCD_SIGNATURE {
+                continue;
+            }
+            let pos = chunk_start + i as u64;
+            if is_plausible_eocd(reader, pos)? {
+                let mut comment_length = [0u8; 2];
+                read_at(reader, pos + 20, &mut comment_length)?;
+                let comment_length = u16::from_le_bytes(comment_length) as u64;
+                let available = file_len - pos - EOCD_SIZE;
+                return Ok(Some(if comment_length > available {
+                    ArchiveEnd {
+                        end: file_len,
+                        comment_length_fix: Some((pos + 20, available as u16)),
+                    }
+                } else {
+                    ArchiveEnd {
+                        end: pos + EOCD_SIZE + comment_length,
+                        comment_length_fix: None,
+                    }
+                }));
+            }
+        }
+        chunk_end = chunk_start;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write as _};
+
+    use zip::write::{FileOptions, ZipWriter};
+
+    use super::*;
+
+    fn archive(comment: &str) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_comment(comment);
+        writer.start_file("a.txt", FileOptions::default()).unwrap();
+        writer.write_all(b"PK\x05\x06 in the data").unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn find(data: &[u8]) -> Option<ArchiveEnd> {
+        find_archive_end(&mut Cursor::new(data)).unwrap()
+    }
+
+    #[test]
+    fn ends_after_the_comment() {
+        let data = archive("a comment");
+        let end = find(&data).unwrap();
+        assert_eq!(end.end, data.len() as u64);
+        assert!(end.comment_length_fix.is_none());
+    }
+
+    #[test]
+    fn ignores_data_appended_after_the_archive() {
+        let mut data = archive("a comment");
+        let len = data.len() as u64;
+        data.extend_from_slice(b"appended");
+        let end = find(&data).unwrap();
+        assert_eq!(end.end, len);
+    }
+
+    #[test]
+    fn finds_the_end_after_a_junk_prefix() {
+        let mut data = b"#!/bin/sh\nexit 0\n".to_vec();
+        data.extend(archive(""));
+        let end = find(&data).unwrap();
+        assert_eq!(end.end, data.len() as u64);
+        let archive = zip::ZipArchive::new(Bounded::new(Cursor::new(&data), end).unwrap());
+        assert_eq!(archive.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn fixes_a_comment_running_past_the_end() {
+        let mut data = archive("a comment");
+        data.truncate(data.len() - 4);
+        let end = find(&data).unwrap();
+        assert_eq!(end.end, data.len() as u64);
+        let (pos, length) = end.comment_length_fix.unwrap();
+        assert_eq!(pos, data.len() as u64 - 5 - 2);
+        assert_eq!(length, 5);
+        let archive = zip::ZipArchive::new(Bounded::new(Cursor::new(&data), end).unwrap());
+        assert_eq!(archive.unwrap().comment(), b"a com");
+    }
+
+    #[test]
+    fn finds_nothing_without_an_archive() {
+        assert!(find(b"not a zip file").is_none());
+        assert!(find(b"").is_none());
+    }
+}
@@ -0,0 +1,196 @@
+// Parsers for the extra fields stored in the central directory and local
+// headers.
+// https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT (4.5, 4.6)
+
+use std::io::Read as _;
+
+use flate2::read::DeflateDecoder;
+
+pub const OS2_EXTENDED_ATTRIBUTES: u16 = 0x0009;
+pub const NTFS: u16 = 0x000a;
+pub const PKWARE_UNIX: u16 = 0x000d;
+pub const EXTENDED_TIMESTAMP: u16 = 0x5455;
+pub const INFOZIP_UNIX_NEW: u16 = 0x7875;
+
+pub struct ExtraFields<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ExtraFields<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+}
+
+pub fn find_extra_field(data: &[u8], header_id: u16) -> Option<&[u8]> {
+    ExtraFields::new(data)
+        .find(|&(id, _)| id == header_id)
+        .map(|(_, body)| body)
+}
+
+impl<'a> Iterator for ExtraFields<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < 4 {
+            return None;
+        }
+        let header_id = u16::from_le_bytes([self.data[0], self.data[1]]);
+        let size = u16::from_le_bytes([self.data[2], self.data[3]]) as usize;
+        let Some(body) = self.data.get(4..4 + size) else {
+            // Truncated field. Stop iterating rather than reading garbage.
+            self.data = &[];
+            return None;
+        };
+        self.data = &self.data[4 + size..];
+        Some((header_id, body))
+    }
+}
+
+// PKWARE Unix extra field (0x000d)
+pub struct PkwareUnix<'a> {
+    pub uid: u16,
+    pub gid: u16,
+    // Target of a hard link or a symbolic link, stored as the raw entry name.
+    pub link_target: &'a [u8],
+}
+
+pub fn parse_pkware_unix(data: &[u8]) -> Option<PkwareUnix<'_>> {
+    // atime(4) mtime(4) uid(2) gid(2) variable length data
+    if data.len() < 12 {
+        return None;
+    }
+    Some(PkwareUnix {
+        uid: u16::from_le_bytes([data[8], data[9]]),
+        gid: u16::from_le_bytes([data[10], data[11]]),
+        link_target: &data[12..],
+    })
+}
+
+// Info-ZIP new Unix extra field (0x7875). Returns (uid, gid).
+pub fn parse_infozip_unix_new(data: &[u8]) -> Option<(u32, u32)> {
+    // version(1) uid_size(1) uid(uid_size) gid_size(1) gid(gid_size)
+    if *data.first()? != 1 {
+        return None;
+    }
+    let (uid, rest) = read_var_uint(&data[1..])?;
+    let (gid, _) = read_var_uint(rest)?;
+    Some((uid, gid))
+}
+
+fn read_var_uint(data: &[u8]) -> Option<(u32, &[u8])> {
+    let size = *data.first()? as usize;
+    let bytes = data.get(1..1 + size)?;
+    // Larger sizes are allowed by the format but ids beyond u32 are not
+    // representable on the platforms we support.
+    if bytes.iter().skip(4).any(|&b| b != 0) {
+        return None;
+    }
+    let value = bytes
+        .iter()
+        .take(4)
+        .rev()
+        .fold(0u32, |acc, &b| (acc << 8) | b as u32);
+    Some((value, &data[1 + size..]))
+}
+
+// Extended timestamp extra field (0x5455). Times are seconds since the
+// Unix epoch in UTC. The central directory copy carries only mtime even
+// when the flags announce atime.
+pub struct ExtendedTimestamp {
+    pub mtime: Option<i32>,
+    pub atime: Option<i32>,
+}
+
+pub fn parse_extended_timestamp(data: &[u8]) -> Option<ExtendedTimestamp> {
+    let flags = *data.first()?;
+    let mut rest = &data[1..];
+    let mut read_time = |present: bool| -> Option<i32> {
+        if !present || rest.len() < 4 {
+            return None;
+        }
+        let value = i32::from_le_bytes(rest[..4].try_into().unwrap());
+        rest = &rest[4..];
+        Some(value)
+    };
+    let mtime = read_time(flags & 0x01 != 0);
+    let atime = read_time(flags & 0x02 != 0);
+    Some(ExtendedTimestamp { mtime, atime })
+}
+
+// NTFS extra field (0x000a). Times are FILETIME values: 100ns intervals
+// since 1601-01-01 UTC.
+pub struct NtfsTimestamp {
+    pub mtime: u64,
+    pub atime: u64,
+}
+
+pub fn parse_ntfs(data: &[u8]) -> Option<NtfsTimestamp> {
+    // reserved(4) then tag(2) size(2) attribute(size) repeated
+    let mut rest = data.get(4..)?;
+    while rest.len() >= 4 {
+        let tag = u16::from_le_bytes([rest[0], rest[1]]);
+        let size = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let attribute = rest.get(4..4 + size)?;
+        if tag == 0x0001 && size >= 24 {
+            let read_u64 =
+                |pos: usize| u64::from_le_bytes(attribute[pos..pos + 8].try_into().unwrap());
+            return Some(NtfsTimestamp {
+                mtime: read_u64(0),
+                atime: read_u64(8),
+            });
+        }
+        rest = &rest[4 + size..];
+    }
+    None
+}
+
+// OS/2 extended attributes extra field (0x0009). Only the local header
+// carries the attribute data; the central directory copy is just the size.
+// Returns (name, value) pairs.
+pub fn parse_os2_extended_attributes(data: &[u8]) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+    // uncompressed_size(4) compression_type(2) crc(4) data
+    if data.len() < 10 {
+        return None;
+    }
+    let uncompressed_size = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+    let compression_type = u16::from_le_bytes([data[4], data[5]]);
+    let crc = u32::from_le_bytes(data[6..10].try_into().unwrap());
+    let list = match compression_type {
+        0 => data[10..].to_vec(),
+        8 => {
+            let mut list = Vec::with_capacity(uncompressed_size);
+            DeflateDecoder::new(&data[10..])
+                .take(uncompressed_size as u64)
+                .read_to_end(&mut list)
+                .ok()?;
+            list
+        }
+        _ => return None,
+    };
+    if list.len() != uncompressed_size || crc32fast::hash(&list) != crc {
+        return None;
+    }
+
+    // FEA2LIST: list_size(4) then FEA2 entries of
+    // next_offset(4) flags(1) name_size(1) value_size(2) name NUL value
+    let mut attributes = Vec::new();
+    let mut pos = 4usize;
+    loop {
+        let entry = list.get(pos..)?;
+        if entry.len() < 8 {
+            return None;
+        }
+        let next_offset = u32::from_le_bytes(entry[0..4].try_into().unwrap()) as usize;
+        let name_size = entry[5] as usize;
+        let value_size = u16::from_le_bytes([entry[6], entry[7]]) as usize;
+        let name = entry.get(8..8 + name_size)?;
+        let value = entry.get(9 + name_size..9 + name_size + value_size)?;
+        attributes.push((name.to_vec(), value.to_vec()));
+        if next_offset == 0 {
+            break;
+        }
+        pos += next_offset;
+    }
+    Some(attributes)
+}
@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::encoding::{get_encoding, ZipEncoding};
+use crate::interrupt::CancellationToken;
+use crate::name_index::read_raw_names;
+use crate::observer::ExtractObserver;
+use crate::output::Reporter;
+use crate::task::ExtractTask;
+use crate::{
+    detect_filename_encoding, extract_into, open_archive, target_path, unique_path, ConflictPolicy,
+    Extracted, Options,
+};
+
+/// Options of an extraction. The defaults are those of the `exzip` command.
+#[derive(Default)]
+pub struct ExtractOptions {
+    pub(crate) options: Options,
+}
+
+impl ExtractOptions {
+    /// The encoding of entry names without the UTF-8 flag, instead of
+    /// detecting it. Returns `None` for an unknown encoding.
+    pub fn encoding(mut self, name: &str) -> Option<Self> {
+        get_encoding(name)?;
+        self.options.oenc = Some(name.to_owned());
+        Some(self)
+    }
+
+    /// Also skip entries matching these globs. A glob with a slash matches
+    /// the whole path in the archive, one without matches any component.
+    pub fn ignore_patterns<S: AsRef<str>>(mut self, patterns: &[S]) -> Self {
+        self.options
+            .ignore_pattern
+            .extend(patterns.iter().map(|pattern| pattern.as_ref().to_owned()));
+        self
+    }
+
+    /// Extract junk files (__MACOSX, Thumbs.db, .DS_Store) too.
+    pub fn keep_junk(mut self, keep: bool) -> Self {
+        self.options.no_ignore = keep;
+        self
+    }
+
+    /// What to do when the target directory already exists. With
+    /// [`ConflictPolicy::Prompt`], the default, nobody is asked and the
+    /// archive is skipped.
+    pub fn on_conflict(mut self, policy: ConflictPolicy) -> Self {
+        self.options.on_conflict = policy;
+        self
+    }
+
+    /// Create the target directories in `dir` instead of next to the
+    /// archives.
+    pub fn destination(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.destination = Some(dir.into());
+        self
+    }
+
+    /// The number of threads writing file data, 0 for one per CPU.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.options.threads = threads;
+        self
+    }
+
+    /// Apply the permission bits stored in the archive, without the setuid,
+    /// setgid and sticky bits.
+    pub fn preserve_permissions(mut self, preserve: bool) -> Self {
+        self.options.preserve_permissions = preserve;
+        self
+    }
+
+    /// Create symbolic links stored in the archive which stay inside the
+    /// target directory.
+    pub fn allow_symlinks(mut self, allow: bool) -> Self {
+        self.options.allow_symlinks = allow;
+        self
+    }
+
+    /// Give up on archives which would expand to more than `bytes`.
+    pub fn max_output_size(mut self, bytes: u64) -> Self {
+        self.options.max_output_size = Some(bytes);
+        self
+    }
+
+    /// Give up on archives with more than `entries` entries.
+    pub fn max_entries(mut self, entries: usize) -> Self {
+        self.options.max_entries = Some(entries);
+        self
+    }
+
+    /// Flush the extracted files to disk before the target is moved into
+    /// place.
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        self.options.fsync = fsync;
+        self
+    }
+
+    /// Stop extracting once `token` is cancelled, with an "Interrupted"
+    /// error. The target directory is left as it was.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.options.cancel = token;
+        self
+    }
+
+    /// Report the extraction to `observer`. Extractions running at the same
+    /// time report to their own observers.
+    pub fn observer(mut self, observer: Arc<dyn ExtractObserver>) -> Self {
+        self.options.reporter = Reporter::observed(Some(observer));
+        self
+    }
+}
+
+/// Extracts zip archives as the `exzip` command does, into a staging
+/// directory next to the target which is renamed into place when complete.
+/// Nothing is printed; an [`ExtractObserver`] is told what happens. Clones
+/// share the options.
+#[derive(Clone)]
+pub struct Extractor {
+    options: Arc<ExtractOptions>,
+}
+
+impl Extractor {
+    pub fn new(options: ExtractOptions) -> Self {
+        Extractor {
+            options: Arc::new(options),
+        }
+    }
+
+    pub(crate) fn cancellation_token(&self) -> &CancellationToken {
+        &self.options.options.cancel
+    }
+
+    /// The directory `zipfile` is extracted to: its path without the
+    /// extension, in the destination directory if one is set.
+    pub fn target_path(&self, zipfile: &Path) -> PathBuf {
+        target_path(zipfile, &self.options.options)
+    }
+
+    /// Extract `zipfile`. Returns `None` when the archive is skipped because
+    /// its target exists.
+    pub fn extract(&self, zipfile: &Path) -> Result<Option<Extracted>> {
+        let options = &self.options.options;
+        let mut target_path = self.target_path(zipfile);
+        if target_path.exists() {
+            match options.on_conflict {
+                ConflictPolicy::Prompt | ConflictPolicy::Skip => return Ok(None),
+                ConflictPolicy::Number => {
+                    target_path = unique_path(&target_path, |candidate| candidate.exists());
+                }
+                ConflictPolicy::Replace | ConflictPolicy::Merge | ConflictPolicy::Backup => {}
+            }
+        }
+        let result = extract_into(zipfile, &target_path, options, None, None);
+        options.reporter.finished(result.as_ref());
+        result.map(Some)
+    }
+
+    /// Extract `zipfile` on another thread, for async code which must not
+    /// block. The observer is called from that thread, so an observer
+    /// waiting for a slow consumer holds the extraction back. Extractions
+    /// started while others run report separately, one per observer.
+    pub fn extract_async(&self, zipfile: impl Into<PathBuf>) -> ExtractTask {
+        ExtractTask::spawn(self.clone(), zipfile.into())
+    }
+}
+
+/// The encoding extraction decodes the entry names of `zipfile` with, when
+/// they lack the UTF-8 flag: UTF-8 or Shift_JIS if all the names decode
+/// without errors, and cp437 otherwise.
+pub fn detect_encoding(zipfile: &Path) -> Result<ZipEncoding> {
+    let mut archive = open_archive(zipfile, &Reporter::default(), true)?;
+    let raw_names = read_raw_names(&mut archive)?;
+    Ok(detect_filename_encoding(&raw_names))
+}
@@ -0,0 +1,45 @@
+// Page cache hints for --fadvise, so that extracting a huge archive does not
+// push everything else out of the cache. They are only hints: errors are
+// ignored, and other systems than Linux do nothing.
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd as _;
+
+    use rustix::fs::{fadvise, Advice};
+
+    pub fn advise_archive(archive_file: &File) {
+        let _ = fadvise(archive_file, 0, 0, Advice::Sequential);
+    }
+
+    pub fn will_read(archive_file: &File, offset: u64, len: u64) {
+        let _ = fadvise(archive_file, offset, len, Advice::WillNeed);
+    }
+
+    pub fn done_reading(archive_file: &File, offset: u64, len: u64) {
+        let _ = fadvise(archive_file, offset, len, Advice::DontNeed);
+    }
+
+    // Dirty pages cannot be dropped, so wait for the writeback first.
+    pub fn done_writing(outfile: &File) {
+        let flags = libc::SYNC_FILE_RANGE_WAIT_BEFORE
+            | libc::SYNC_FILE_RANGE_WRITE
+            | libc::SYNC_FILE_RANGE_WAIT_AFTER;
+        if unsafe { libc::sync_file_range(outfile.as_raw_fd(), 0, 0, flags) } == 0 {
+            let _ = fadvise(outfile, 0, 0, Advice::DontNeed);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::fs::File;
+
+    pub fn advise_archive(_archive_file: &File) {}
+    pub fn will_read(_archive_file: &File, _offset: u64, _len: u64) {}
+    pub fn done_reading(_archive_file: &File, _offset: u64, _len: u64) {}
+    pub fn done_writing(_outfile: &File) {}
+}
+
+pub use imp::*;
@@ -0,0 +1,260 @@
+// The C API, declared in include/exzip.h, which a test checks against the
+// functions here. Errors are kept per thread for exzip_last_error(), and
+// panics are stopped at the boundary.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context as _, Result};
+
+use crate::name_index::{decode_names, read_raw_names};
+use crate::observer::ExtractObserver;
+use crate::output::{error_message, Reporter};
+use crate::{detect_filename_encoding, open_archive, ExtractOptions, Extractor};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(err: &anyhow::Error) {
+    let message = c_string(&error_message(err));
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+// Run `f`, turning errors and panics into `failure` and a message for
+// exzip_last_error().
+fn guard<T>(failure: T, f: impl FnOnce() -> Result<T>) -> T {
+    let result =
+        panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| Err(anyhow!("Internal error")));
+    result.unwrap_or_else(|err| {
+        set_last_error(&err);
+        failure
+    })
+}
+
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', " ")).unwrap()
+}
+
+unsafe fn path_arg(path: *const c_char, what: &str) -> Result<PathBuf> {
+    if path.is_null() {
+        return Err(anyhow!("{} is NULL", what));
+    }
+    let path = CStr::from_ptr(path)
+        .to_str()
+        .with_context(|| format!("{} is not UTF-8", what))?;
+    Ok(PathBuf::from(path))
+}
+
+pub struct ExzipArchive {
+    encoding: CString,
+    names: Vec<CString>,
+    sizes: Vec<u64>,
+}
+
+/// The message of the last error on this thread, or NULL.
+#[no_mangle]
+pub extern "C" fn exzip_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// # Safety
+/// `path` is NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn exzip_archive_open(path: *const c_char) -> *mut ExzipArchive {
+    guard(ptr::null_mut(), || {
+        let path = path_arg(path, "path")?;
+        let mut archive = open_archive(&path, &Reporter::default(), true)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let raw_names = read_raw_names(&mut archive)?;
+        let encoding = detect_filename_encoding(&raw_names);
+        let names = decode_names(&raw_names, encoding);
+        Ok(Box::into_raw(Box::new(ExzipArchive {
+            encoding: c_string(encoding.name()),
+            names: names
+                .iter()
+                .map(|name| c_string(&name.decoded.to_string_lossy()))
+                .collect(),
+            sizes: raw_names.iter().map(|raw_name| raw_name.size).collect(),
+        })))
+    })
+}
+
+/// # Safety
+/// `archive` is NULL or was returned by exzip_archive_open() and not closed.
+#[no_mangle]
+pub unsafe extern "C" fn exzip_archive_close(archive: *mut ExzipArchive) {
+    if !archive.is_null() {
+        drop(Box::from_raw(archive));
+    }
+}
+
+/// # Safety
+/// `archive` was returned by exzip_archive_open() and not closed.
+#[no_mangle]
+pub unsafe extern "C" fn exzip_archive_encoding(archive: *const ExzipArchive) -> *const c_char {
+    (*archive).encoding.as_ptr()
+}
+
+/// # Safety
+/// `archive` was returned by exzip_archive_open() and not closed.
+#[no_mangle]
+pub unsafe extern "C" fn exzip_archive_entry_count(archive: *const ExzipArchive) -> usize {
+    (*archive).names.len()
+}
+
+/// # Safety
+/// `archive` was returned by exzip_archive_open() and not closed.
+#[no_mangle]
+pub unsafe extern "C" fn exzip_archive_entry_name(
+    archive: *const ExzipArchive,
+    index: usize,
+) -> *const c_char {
+    let archive = &*archive;
+    archive
+        .names
+        .get(index)
+        .map_or(ptr::null(), |name| name.as_ptr())
+}
+
+/// # Safety
+/// `archive` was returned by exzip_archive_open() and not closed.
+#[no_mangle]
+pub unsafe extern "C" fn exzip_archive_entry_size(
+    archive: *const ExzipArchive,
+    index: usize,
+) -> u64 {
+    let archive = &*archive;
+    archive.sizes.get(index).copied().unwrap_or(0)
+}
+
+pub type ExzipProgressFn =
+    Option<unsafe extern "C" fn(user_data: *mut c_void, entry: *const c_char, written: u64)>;
+
+// Calls the progress callback with the entry being extracted and the bytes
+// written so far.
+struct Progress {
+    callback: ExzipProgressFn,
+    user_data: *mut c_void,
+    state: Mutex<(CString, u64)>,
+}
+
+// The caller vouches for user_data being usable from the extraction thread.
+unsafe impl Send for Progress {}
+unsafe impl Sync for Progress {}
+
+impl Progress {
+    // Called with the lock released, so that a slow callback does not hold
+    // up the other threads writing file data.
+    fn report(&self, (entry, written): (CString, u64)) {
+        if let Some(callback) = self.callback {
+            unsafe { callback(self.user_data, entry.as_ptr(), written) };
+        }
+    }
+}
+
+impl ExtractObserver for Progress {
+    fn entry_started(&self, name: &Path, _size: u64) {
+        let state = {
+            let mut state = self.state.lock().unwrap();
+            state.0 = c_string(&name.to_string_lossy());
+            state.clone()
+        };
+        self.report(state);
+    }
+
+    fn bytes_written(&self, bytes: u64) {
+        let state = {
+            let mut state = self.state.lock().unwrap();
+            state.1 += bytes;
+            state.clone()
+        };
+        self.report(state);
+    }
+}
+
+/// # Safety
+/// `zipfile` is a NUL-terminated string; `destination` and `encoding` are
+/// NULL or NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn exzip_extract(
+    zipfile: *const c_char,
+    destination: *const c_char,
+    encoding: *const c_char,
+    progress: ExzipProgressFn,
+    user_data: *mut c_void,
+) -> c_int {
+    guard(-1, || {
+        let zipfile = path_arg(zipfile, "zipfile")?;
+        let mut options = ExtractOptions::default();
+        if !destination.is_null() {
+            options = options.destination(path_arg(destination, "destination")?);
+        }
+        if !encoding.is_null() {
+            let name = CStr::from_ptr(encoding).to_string_lossy();
+            options = options
+                .encoding(&name)
+                .with_context(|| format!("Unknown encoding {}", name))?;
+        }
+        // The application is told of the progress through the callback.
+        let options = options.observer(Arc::new(Progress {
+            callback: progress,
+            user_data,
+            state: Mutex::new((CString::default(), 0)),
+        }));
+        match Extractor::new(options).extract(&zipfile)? {
+            Some(_) => Ok(0),
+            None => Ok(1),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    // The functions declared in C or exported from Rust, with the number of
+    // parameters each takes.
+    fn functions(source: &str, marker: &str) -> Vec<(String, usize)> {
+        let mut functions = Vec::new();
+        for (start, _) in source.match_indices(marker) {
+            let rest = &source[start + marker.len()..];
+            let Some(open) = rest.find(|c: char| !c.is_alphanumeric() && c != '_') else {
+                continue;
+            };
+            if !rest[open..].starts_with('(') {
+                continue;
+            }
+            let close = rest[open..].find(')').unwrap();
+            let params = rest[open + 1..open + close]
+                .split(',')
+                .filter(|param| !matches!(param.trim(), "" | "void"))
+                .count();
+            functions.push((format!("{}{}", marker, &rest[..open]), params));
+        }
+        functions.sort();
+        functions
+    }
+
+    fn strip_comments(mut source: &str) -> String {
+        let mut stripped = String::new();
+        while let Some(start) = source.find("/*") {
+            stripped.push_str(&source[..start]);
+            let end = source[start..].find("*/").unwrap();
+            source = &source[start + end + 2..];
+        }
+        stripped + source
+    }
+
+    #[test]
+    fn header_declares_every_export() {
+        let header = strip_comments(include_str!("../include/exzip.h"));
+        let exports: Vec<_> = functions(include_str!("ffi.rs"), "extern \"C\" fn exzip_")
+            .into_iter()
+            .map(|(name, params)| (name.replace("extern \"C\" fn ", ""), params))
+            .collect();
+        assert_eq!(functions(&header, "exzip_"), exports);
+    }
+}
@@ -0,0 +1,39 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::ffi::{OsStrExt as _, OsStringExt as _};
+use std::path::{Path, PathBuf};
+
+fn separator(null: bool) -> u8 {
+    if null {
+        b'\0'
+    } else {
+        b'\n'
+    }
+}
+
+// Read the archive paths of --files-from, one per line, or separated by NUL
+// as `find -print0` writes them with `null`. "-" is stdin.
+pub fn read_file_list(path: &Path, null: bool) -> io::Result<Vec<PathBuf>> {
+    let mut content = Vec::new();
+    if path == Path::new("-") {
+        io::stdin().lock().read_to_end(&mut content)?;
+    } else {
+        File::open(path)?.read_to_end(&mut content)?;
+    }
+    Ok(content
+        .split(|&byte| byte == separator(null))
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| PathBuf::from(OsString::from_vec(entry.to_vec())))
+        .collect())
+}
+
+// The list again, for the children of --jobs which read it from their stdin.
+pub fn format_file_list(paths: &[PathBuf], null: bool) -> Vec<u8> {
+    let mut content = Vec::new();
+    for path in paths {
+        content.extend_from_slice(path.as_os_str().as_bytes());
+        content.push(separator(null));
+    }
+    content
+}
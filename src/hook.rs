@@ -0,0 +1,44 @@
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context as _, Result};
+
+use crate::output;
+use crate::Extracted;
+
+// A single quoted shell word.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// Run the command of --exec through the shell after an archive is extracted,
+// with {} replaced by the target directory, quoted. The details are in the
+// environment as well.
+pub fn run_exec(command: &str, zipfile: &Path, extracted: &Extracted) -> Result<()> {
+    let target_path = &extracted.target_path;
+    let command_line = command.replace("{}", &shell_quote(&target_path.to_string_lossy()));
+    let mut child = Command::new("sh");
+    child
+        .arg("-c")
+        .arg(&command_line)
+        .env("EXZIP_ARCHIVE", zipfile)
+        .env("EXZIP_TARGET", target_path)
+        .env("EXZIP_FILE_COUNT", extracted.entries.to_string())
+        .stdin(Stdio::null());
+    // Stdout is for events alone with --output=json.
+    if output::is_json() {
+        child.stdout(io::stderr());
+    }
+    match extracted.encoding {
+        Some(encoding) => child.env("EXZIP_NAME_ENCODING", encoding),
+        None => child.env_remove("EXZIP_NAME_ENCODING"),
+    };
+    let status = child
+        .status()
+        .with_context(|| format!("Failed to run {}", command_line))?;
+    if !status.success() {
+        bail!("{} exited with {}", command_line, status);
+    }
+    Ok(())
+}
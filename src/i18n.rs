@@ -0,0 +1,150 @@
+use std::fmt::{self, Write as _};
+use std::sync::OnceLock;
+
+use crate::output;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Language {
+    English,
+    Japanese,
+}
+
+// The language of LC_ALL, LC_MESSAGES or LANG, whichever is set first.
+fn language() -> Language {
+    static LANGUAGE: OnceLock<Language> = OnceLock::new();
+    *LANGUAGE.get_or_init(|| {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        if locale.starts_with("ja") {
+            Language::Japanese
+        } else {
+            Language::English
+        }
+    })
+}
+
+// The translation of a message, which is its format string. None keeps the
+// English text: in English, for messages without a translation, and with
+// --output=json, whose output is for programs.
+pub fn translate(message: &str) -> Option<&'static str> {
+    if language() == Language::English || output::is_json() {
+        return None;
+    }
+    let translated = match message {
+        // Prompts
+        "Replace?" => "置き換えますか?",
+        "Extract anyway?" => "このまま展開しますか?",
+        "Remove?" => "削除しますか?",
+        "Answer y, n, always or never" => "y、n、always、never のいずれかで答えてください",
+        "Error:" => "エラー:",
+
+        // Warnings
+        "Already exists: {}" => "既に存在します: {}",
+        "Extracting to {}" => "{} に展開します",
+        "Backup {} -> {}" => "{} を {} に退避しました",
+        "CRC mismatch {}" => "CRC が一致しません {}",
+        "Damaged {}: {}" => "破損しています {}: {}",
+        "Damaged entry #{}: {}" => "破損したエントリ #{}: {}",
+        "Unreadable local header {}: {}" => "ローカルヘッダを読めません {}: {}",
+        "{} entries failed the CRC check:" => "{} 個のエントリが CRC チェックに失敗しました:",
+        "Recovery report: {} entries recovered, {} damaged" => {
+            "復旧結果: {} 個のエントリを復旧、{} 個が破損"
+        }
+        "Warning: Rewrote {} -> {}" => "警告: {} を {} に書き換えました",
+        "Warning: Ignoring {} bytes after the end of the archive" => {
+            "警告: アーカイブの末尾の後にある {} バイトを無視します"
+        }
+        "Warning: The archive comment is truncated" => {
+            "警告: アーカイブのコメントが途中で切れています"
+        }
+        "Warning: Failed to read the central directory ({}); scanning local headers" => {
+            "警告: セントラルディレクトリを読めませんでした ({})。ローカルヘッダを走査します"
+        }
+        "Warning: Not permitted to change ownership; owners are not restored" => {
+            "警告: 所有者を変更する権限がないため、所有者は復元されません"
+        }
+        "Warning: Extended attributes are not supported here" => {
+            "警告: ここでは拡張属性を使えません"
+        }
+        "Names differing only in case on a case-insensitive filesystem:" => {
+            "大文字と小文字を区別しないファイルシステムで、大文字小文字だけが異なる名前:"
+        }
+        "Not enough free space: {} needed, {} available" => {
+            "空き容量が足りません: {} 必要ですが、空きは {} です"
+        }
+        "Stale staging directory: {}" => "放置された作業ディレクトリ: {}",
+        "Use --clean-stale to remove them" => "削除するには --clean-stale を指定してください",
+        "Failed to send a notification: {}" => "通知を送れませんでした: {}",
+        "Bad filename {}" => "ファイル名が不正です {}",
+        "Not found {}" => "見つかりません {}",
+        "Not a file {}" => "ファイルではありません {}",
+        "Not a directory {}" => "ディレクトリではありません {}",
+        "Removed a link leading outside: {}" => "外を指すリンクを削除しました: {}",
+        "Removed a special file: {}" => "特殊ファイルを削除しました: {}",
+        "Watching {}" => "{} を監視しています",
+        "Listening on {}" => "{} で待ち受けています",
+        "Downloading {}" => "{} をダウンロードしています",
+        "Mounted {} on {}" => "{} を {} にマウントしました",
+        "Failed to unmount {}: {}" => "{} をアンマウントできませんでした: {}",
+        "Found {} archives in {}" => "{1} に {0} 個のアーカイブが見つかりました",
+
+        // Summaries
+        "Extracted {} entries ({}) to {}" => "{2} に {0} 個のエントリ ({1}) を展開しました",
+        "{} entries OK" => "{} 個のエントリに問題はありません",
+        "{} of {} entries FAILED" => "{1} 個中 {0} 個のエントリが失敗しました",
+        "{} of {} archives extracted" => "{1} 個中 {0} 個のアーカイブを展開しました",
+        "{} of {} archives failed:" => "{1} 個中 {0} 個のアーカイブが失敗しました:",
+
+        // Desktop notifications
+        "Extracted {}" => "{} を展開しました",
+        "Failed to extract {}" => "{} を展開できませんでした",
+        _ => return None,
+    };
+    Some(translated)
+}
+
+// Format a translated message. Translations only use "{}" and "{N}", so
+// that the arguments can come in a different order.
+pub fn fill(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let index = match &rest[start + 1..start + end] {
+            "" => {
+                next += 1;
+                next - 1
+            }
+            index => index.parse().unwrap_or(usize::MAX),
+        };
+        if let Some(arg) = args.get(index) {
+            let _ = write!(filled, "{}", arg);
+        }
+        rest = &rest[start + end + 1..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+// format!() with the message translated to the user's language, e.g.
+// tr!("Already exists: {}", path.display()).
+macro_rules! tr {
+    ($message:literal $(, $arg:expr)* $(,)?) => {
+        match $crate::i18n::translate($message) {
+            Some(template) => $crate::i18n::fill(
+                template,
+                &[$(&$arg as &dyn ::std::fmt::Display),*],
+            ),
+            None => format!($message $(, $arg)*),
+        }
+    };
+}
+pub(crate) use tr;
@@ -0,0 +1,148 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_IGNORED_NAMES: [&str; 2] = ["Thumbs.db", ".DS_Store"];
+const MACOSX_DIR: &str = "__MACOSX";
+const USER_IGNORE_FILE: &str = ".exzipignore";
+
+pub struct IgnoreRules {
+    ignore_defaults: bool,
+    ignore_macosx: bool,
+    patterns: Vec<String>,
+}
+
+impl IgnoreRules {
+    pub fn new(no_ignore: bool, keep_macosx: bool, patterns: &[String]) -> Self {
+        Self {
+            ignore_defaults: !no_ignore,
+            ignore_macosx: !no_ignore && !keep_macosx,
+            patterns: patterns.to_vec(),
+        }
+    }
+
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        if self.ignore_macosx && path.iter().any(|name| name == MACOSX_DIR) {
+            return true;
+        }
+        if self.ignore_defaults {
+            if let Some(filename) = path.file_name() {
+                if DEFAULT_IGNORED_NAMES.iter().any(|name| filename == *name) {
+                    return true;
+                }
+            }
+        }
+        // The last pattern matching decides, as `!` negates.
+        self.patterns
+            .iter()
+            .rev()
+            .find_map(|pattern| {
+                let (negated, pattern) = match pattern.strip_prefix('!') {
+                    Some(pattern) => (true, pattern),
+                    None => (false, pattern.strip_prefix('\\').unwrap_or(pattern)),
+                };
+                matches_pattern(pattern, path).then_some(!negated)
+            })
+            .unwrap_or(false)
+    }
+}
+
+pub fn user_ignore_file() -> Option<PathBuf> {
+    Some(PathBuf::from(env::var_os("HOME")?).join(USER_IGNORE_FILE))
+}
+
+// The patterns of a gitignore-style file: a glob per line, `#` starting a
+// comment, `!` negating and a trailing `/` ignored. A leading `\` escapes
+// `#` and `!`.
+pub fn read_ignore_file(path: &Path) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.trim_end_matches('/').to_string())
+        .collect())
+}
+
+// A pattern with a slash matches the whole path from the archive root; one
+// without matches any component.
+pub fn matches_pattern(pattern: &str, path: &Path) -> bool {
+    if pattern.contains('/') {
+        glob_match(pattern.trim_start_matches('/'), &path.to_string_lossy())
+    } else {
+        path.iter()
+            .any(|name| glob_match(pattern, &name.to_string_lossy()))
+    }
+}
+
+// Shell-style wildcard matching: `*` and `?` do not match `/`, `**` does,
+// and `[...]` is a character class (`[!...]` negates).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_chars(&pattern, &text)
+}
+
+fn glob_match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => {
+            let (rest, cross_slash) = if pattern.get(1) == Some(&'*') {
+                (&pattern[2..], true)
+            } else {
+                (&pattern[1..], false)
+            };
+            for i in 0..=text.len() {
+                if glob_match_chars(rest, &text[i..]) {
+                    return true;
+                }
+                if i < text.len() && text[i] == '/' && !cross_slash {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => {
+            matches!(text.first(), Some(&c) if c != '/')
+                && glob_match_chars(&pattern[1..], &text[1..])
+        }
+        Some('[') => {
+            let Some(&c) = text.first() else {
+                return false;
+            };
+            match match_class(&pattern[1..], c) {
+                Some((true, rest)) => glob_match_chars(rest, &text[1..]),
+                Some((false, _)) => false,
+                // Unterminated class: treat `[` literally
+                None => c == '[' && glob_match_chars(&pattern[1..], &text[1..]),
+            }
+        }
+        Some(&p) => text.first() == Some(&p) && glob_match_chars(&pattern[1..], &text[1..]),
+    }
+}
+
+// Returns whether `c` matches the class and the pattern after `]`.
+fn match_class(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negate, mut pos) = match pattern.first() {
+        Some('!') | Some('^') => (true, 1),
+        _ => (false, 0),
+    };
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let &start = pattern.get(pos)?;
+        if start == ']' && !first {
+            return Some((matched != negate, &pattern[pos + 1..]));
+        }
+        first = false;
+        if pattern.get(pos + 1) == Some(&'-') && pattern.get(pos + 2).is_some_and(|&end| end != ']')
+        {
+            let end = pattern[pos + 2];
+            matched |= start <= c && c <= end;
+            pos += 3;
+        } else {
+            matched |= start == c;
+            pos += 1;
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::cli::Args;
+use crate::control_chars::display_path;
+use crate::encoding::get_encoding;
+use crate::ignore::IgnoreRules;
+use crate::limits::format_size;
+use crate::name_index::{decode_names, read_raw_names};
+use crate::output::{self, outln, Event, Reporter};
+use crate::{detect_filename_encoding, get_inner_root, open_archive};
+
+// Print what extraction would see of an archive: the name encoding, the
+// entries and their sizes, the root directory that is stripped, and the
+// comment.
+pub fn archive_info(zipfile: &Path, args: &Args) -> Result<()> {
+    let mut archive = open_archive(zipfile, &Reporter::TERMINAL, !args.no_mmap)?;
+    let raw_names = read_raw_names(&mut archive)?;
+    let encoding = match args.oenc.as_deref() {
+        Some(encoding_name) => get_encoding(encoding_name).unwrap(),
+        None => detect_filename_encoding(&raw_names),
+    };
+    let names = decode_names(&raw_names, encoding);
+    let ignore_rules = IgnoreRules::new(args.no_ignore, args.keep_macosx, &args.ignore_pattern);
+    let inner_root = get_inner_root(&names, &ignore_rules)?;
+
+    let dirs = raw_names.iter().filter(|raw_name| raw_name.is_dir).count();
+    let size = raw_names
+        .iter()
+        .fold(0u64, |total, raw_name| total.saturating_add(raw_name.size));
+    let mut compressed_size = 0u64;
+    for i in 0..archive.len() {
+        compressed_size =
+            compressed_size.saturating_add(archive.by_index_raw(i)?.compressed_size());
+    }
+    let comment = encoding.decode_lossy(archive.comment());
+
+    if output::is_json() {
+        Event::new("info")
+            .str("archive", &zipfile.to_string_lossy())
+            .num("files", (raw_names.len() - dirs) as u64)
+            .num("dirs", dirs as u64)
+            .num("size", size)
+            .num("compressed_size", compressed_size)
+            .str("encoding", encoding.name())
+            .str("inner_root", &inner_root.to_string_lossy())
+            .str("comment", &comment)
+            .emit();
+        return Ok(());
+    }
+    outln!("{}", zipfile.display());
+    outln!(
+        "  Entries    {} ({} files, {} directories)",
+        raw_names.len(),
+        raw_names.len() - dirs,
+        dirs
+    );
+    outln!(
+        "  Size       {} ({} compressed)",
+        format_size(size),
+        format_size(compressed_size)
+    );
+    outln!("  Encoding   {}", encoding.name());
+    if inner_root.as_os_str().is_empty() {
+        outln!("  Root       (none)");
+    } else {
+        outln!("  Root       {}", display_path(&inner_root));
+    }
+    for (i, line) in comment.lines().enumerate() {
+        outln!(
+            "  {}  {}",
+            if i == 0 { "Comment  " } else { "         " },
+            line
+        );
+    }
+    Ok(())
+}
@@ -1,15 +1,176 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 
-static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+use crate::checksum::{Algorithm, Hasher};
+use crate::output::Reporter;
+use crate::progress;
+use crate::throttle::throttle;
+use crate::CopyOutcome;
+
+/// Stops an extraction from another thread: the extraction fails with
+/// "Interrupted" at the next chunk of file data or entry. Clones share the
+/// same state.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the extractions given this token or one of its clones.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether [`cancel`](Self::cancel) has been called.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// The token of the command, cancelled by Ctrl-C and a closed stdout.
+static INTERRUPTED: OnceLock<CancellationToken> = OnceLock::new();
+
+pub fn process_token() -> &'static CancellationToken {
+    INTERRUPTED.get_or_init(CancellationToken::new)
+}
 
 pub fn register_ctrlc() {
-    ctrlc::set_handler(move || {
-        INTERRUPTED.store(true, Ordering::SeqCst);
-    })
-    .expect("Error setting Ctrl-C handler");
+    ctrlc::set_handler(interrupt).expect("Error setting Ctrl-C handler");
+}
+
+// Stop the extraction as if Ctrl-C was pressed.
+pub fn interrupt() {
+    process_token().cancel();
 }
 
 #[inline]
 pub fn interrupted() -> bool {
-    INTERRUPTED.load(Ordering::SeqCst)
+    process_token().is_cancelled()
+}
+
+// An error reading the data of an entry, as from a truncated or garbled one,
+// with the bytes read before it. Only these are damage to get past with
+// --best-effort; going over a limit or being cancelled is not.
+#[derive(Debug)]
+pub struct ReadError {
+    pub read: u64,
+    error: io::Error,
+}
+
+impl ReadError {
+    pub fn find(err: &anyhow::Error) -> Option<&ReadError> {
+        err.chain().find_map(|cause| {
+            cause
+                .downcast_ref::<io::Error>()?
+                .get_ref()?
+                .downcast_ref::<ReadError>()
+        })
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+}
+
+// Checks for cancellation and the size limit on every read, and computes the
+// CRC of the data passing through, so that plain io::copy can do the copying.
+pub struct InterruptibleReader<R> {
+    inner: R,
+    limit: u64,
+    read: u64,
+    hasher: crc32fast::Hasher,
+    digest: Option<Hasher>,
+    cancel: CancellationToken,
+    reporter: Reporter,
+}
+
+impl<R: Read> InterruptibleReader<R> {
+    pub fn new(inner: R, limit: u64, cancel: &CancellationToken) -> Self {
+        InterruptibleReader {
+            inner,
+            limit,
+            read: 0,
+            hasher: crc32fast::Hasher::new(),
+            digest: None,
+            cancel: cancel.clone(),
+            reporter: Reporter::default(),
+        }
+    }
+
+    // Tell the observer of the extraction about the data read.
+    pub fn with_reporter(mut self, reporter: &Reporter) -> Self {
+        self.reporter = reporter.clone();
+        self
+    }
+
+    // Also compute a digest of the data for --manifest.
+    pub fn with_digest(mut self, algorithm: Option<Algorithm>) -> Self {
+        self.digest = algorithm.map(Hasher::new);
+        self
+    }
+
+    pub fn finish(self) -> CopyOutcome {
+        CopyOutcome {
+            written: self.read,
+            crc32: self.hasher.finalize(),
+            digest: self.digest.map(Hasher::finalize),
+        }
+    }
+}
+
+impl<R: Read> Read for InterruptibleReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Not ErrorKind::Interrupted, which io::copy would retry.
+        if self.cancel.is_cancelled() {
+            return Err(io::Error::other("Interrupted"));
+        }
+        let length = match self.inner.read(buf) {
+            Ok(length) => length,
+            // The zip crate reports a CRC mismatch in place of EOF. We
+            // compare the CRC by ourselves, so treat it as the end.
+            Err(err)
+                if err.kind() == io::ErrorKind::Other && err.to_string() == "Invalid checksum" =>
+            {
+                0
+            }
+            Err(err) => {
+                let kind = err.kind();
+                let error = ReadError {
+                    read: self.read,
+                    error: err,
+                };
+                return Err(io::Error::new(kind, error));
+            }
+        };
+        if self.read + length as u64 > self.limit {
+            return Err(io::Error::other(format!(
+                "Exceeded the extraction size limit of {} bytes",
+                self.limit
+            )));
+        }
+        self.hasher.update(&buf[..length]);
+        if let Some(digest) = &mut self.digest {
+            digest.update(&buf[..length]);
+        }
+        self.read += length as u64;
+        // Everything read here is about to be written.
+        throttle(length, &self.cancel);
+        progress::advance(length);
+        self.reporter.bytes_written(length);
+        Ok(length)
+    }
 }
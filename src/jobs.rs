@@ -0,0 +1,113 @@
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use anyhow::{Context as _, Result};
+
+use crate::cli::EXIT_ERROR;
+use crate::output::{self, error};
+
+// Copy the output of a child line by line, so that lines of concurrent
+// children never interleave, with the archive name in front of each line.
+fn forward_lines<R: Read, W: Write>(reader: R, prefix: &str, writer: fn() -> W) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        let mut writer = writer();
+        let _ = writer.write_all(prefix.as_bytes());
+        let _ = writer.write_all(&line);
+    }
+}
+
+// Extract one archive in a child process started with the same arguments.
+// With `replace`, the parent has asked about the existing target already.
+// `list` is the list of all the archives, which the child reads from
+// its stdin. Returns the exit status of the child.
+fn run_child(index: usize, zipfile: &Path, replace: bool, list: &[u8]) -> Result<i32> {
+    let mut args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    args.push("--job-index".into());
+    args.push(index.to_string().into());
+    if replace {
+        args.push("--on-conflict=replace".into());
+    }
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start the extraction of {}", zipfile.display()))?;
+
+    let prefix = format!("[{}] ", zipfile.display());
+    // Events name their archive already and must stay valid JSON.
+    let stdout_prefix = if output::is_json() { "" } else { &prefix };
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    thread::scope(|scope| {
+        // The child may exit without reading it all.
+        scope.spawn(move || {
+            let _ = stdin.write_all(list);
+        });
+        scope.spawn(|| forward_lines(stdout, stdout_prefix, || io::stdout().lock()));
+        scope.spawn(|| forward_lines(stderr, &prefix, || io::stderr().lock()));
+    });
+    // Killed by a signal counts as a failure.
+    Ok(child.wait()?.code().unwrap_or(EXIT_ERROR))
+}
+
+// Extract the given archives with up to `jobs` child processes at a time.
+// Unless `keep_going`, no more archives are started after one fails.
+// Returns the archives which failed, with their exit statuses.
+pub fn run_jobs<'a>(
+    zipfiles: &[(usize, &'a Path)],
+    jobs: usize,
+    replace: bool,
+    keep_going: bool,
+    list: &[u8],
+) -> Vec<(&'a Path, i32)> {
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let failed = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(zipfiles.len()) {
+            scope.spawn(|| loop {
+                if stop.load(Ordering::SeqCst) {
+                    return;
+                }
+                let Some(&(index, zipfile)) = zipfiles.get(next.fetch_add(1, Ordering::SeqCst))
+                else {
+                    return;
+                };
+                let status = run_child(index, zipfile, replace, list).unwrap_or_else(|err| {
+                    error!("{:?}", err);
+                    EXIT_ERROR
+                });
+                if status != 0 {
+                    failed.lock().unwrap().push((index, zipfile, status));
+                    if !keep_going {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                }
+            });
+        }
+    });
+    let mut failed = failed.into_inner().unwrap();
+    failed.sort();
+    failed
+        .into_iter()
+        .map(|(_, zipfile, status)| (zipfile, status))
+        .collect()
+}
@@ -0,0 +1,271 @@
+use std::fmt::Write as _;
+
+// A quoted JSON string.
+pub fn string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// A JSON array of values which are already JSON.
+pub fn array(values: impl IntoIterator<Item = String>) -> String {
+    let values: Vec<String> = values.into_iter().collect();
+    format!("[{}]", values.join(","))
+}
+
+// Builds a JSON object on one line, with the fields in the order added.
+#[derive(Default)]
+pub struct Object(String);
+
+impl Object {
+    pub fn new() -> Self {
+        Object::default()
+    }
+
+    // `value` must already be JSON.
+    pub fn raw(mut self, key: &str, value: &str) -> Self {
+        self.0.push(if self.0.is_empty() { '{' } else { ',' });
+        self.0.push_str(&string(key));
+        self.0.push(':');
+        self.0.push_str(value);
+        self
+    }
+
+    pub fn str(self, key: &str, value: &str) -> Self {
+        self.raw(key, &string(value))
+    }
+
+    pub fn num(self, key: &str, value: u64) -> Self {
+        self.raw(key, &value.to_string())
+    }
+
+    pub fn bool(self, key: &str, value: bool) -> Self {
+        self.raw(key, if value { "true" } else { "false" })
+    }
+
+    pub fn finish(mut self) -> String {
+        if self.0.is_empty() {
+            self.0.push('{');
+        }
+        self.0.push('}');
+        self.0
+    }
+}
+
+// A parsed JSON value. Objects keep their fields in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    // Whole numbers that are not negative.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(value) if *value >= 0.0 && value.fract() == 0.0 => Some(*value as u64),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Null => "null".to_string(),
+            Value::Bool(value) => value.to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::String(value) => string(value),
+            Value::Array(values) => array(values.iter().map(Value::to_json)),
+            Value::Object(fields) => fields
+                .iter()
+                .fold(Object::new(), |object, (key, value)| {
+                    object.raw(key, &value.to_json())
+                })
+                .finish(),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .text
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, literal: &str) -> Option<()> {
+        self.text[self.pos..]
+            .starts_with(literal.as_bytes())
+            .then(|| self.pos += literal.len())
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        let value = match self.text.get(self.pos)? {
+            b'n' => self.eat("null").map(|()| Value::Null)?,
+            b't' => self.eat("true").map(|()| Value::Bool(true))?,
+            b'f' => self.eat("false").map(|()| Value::Bool(false))?,
+            b'"' => Value::String(self.string()?),
+            b'[' => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.eat("]").is_none() {
+                    loop {
+                        values.push(self.value()?);
+                        self.skip_whitespace();
+                        if self.eat("]").is_some() {
+                            break;
+                        }
+                        self.eat(",")?;
+                    }
+                }
+                Value::Array(values)
+            }
+            b'{' => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.eat("}").is_none() {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.skip_whitespace();
+                        self.eat(":")?;
+                        fields.push((key, self.value()?));
+                        self.skip_whitespace();
+                        if self.eat("}").is_some() {
+                            break;
+                        }
+                        self.eat(",")?;
+                    }
+                }
+                Value::Object(fields)
+            }
+            _ => self.number()?,
+        };
+        self.skip_whitespace();
+        Some(value)
+    }
+
+    fn number(&mut self) -> Option<Value> {
+        let start = self.pos;
+        while self
+            .text
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_digit() || b"+-.eE".contains(c))
+        {
+            self.pos += 1;
+        }
+        let number = std::str::from_utf8(&self.text[start..self.pos]).ok()?;
+        number.parse().ok().map(Value::Number)
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = std::str::from_utf8(self.text.get(self.pos..self.pos + 4)?).ok()?;
+        self.pos += 4;
+        u32::from_str_radix(digits, 16).ok()
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.eat("\"")?;
+        let mut bytes = Vec::new();
+        loop {
+            let c = *self.text.get(self.pos)?;
+            self.pos += 1;
+            match c {
+                b'"' => return String::from_utf8(bytes).ok(),
+                b'\\' => {
+                    let escape = *self.text.get(self.pos)?;
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // A surrogate pair for characters outside the BMP.
+                            if (0xd800..0xdc00).contains(&code) {
+                                self.eat("\\u")?;
+                                let low = self
+                                    .hex4()?
+                                    .checked_sub(0xdc00)
+                                    .filter(|low| *low < 0x400)?;
+                                code = 0x10000 + ((code - 0xd800) << 10) + low;
+                            }
+                            char::from_u32(code)?
+                        }
+                        _ => return None,
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                c => bytes.push(c),
+            }
+        }
+    }
+}
+
+// Parse a whole JSON text, or None if it is not valid.
+pub fn parse(text: &str) -> Option<Value> {
+    let mut parser = Parser {
+        text: text.as_bytes(),
+        pos: 0,
+    };
+    let value = parser.value()?;
+    (parser.pos == parser.text.len()).then_some(value)
+}
@@ -0,0 +1,2477 @@
+//! Extraction of zip archives into a directory of their own, as the `exzip`
+//! command does it: entry names without the UTF-8 flag are decoded from
+//! Shift_JIS and other legacy encodings, names are sanitized, the common root
+//! directory is stripped, junk such as `__MACOSX` is skipped, and everything
+//! is written to a staging directory which is renamed into place at the end.
+//!
+//! [`Extractor`] extracts archives with [`ExtractOptions`];
+//! [`detect_encoding`] and [`sanitize_path`] are the pieces it uses to name
+//! the extracted files. Nothing is printed or asked: an [`ExtractObserver`]
+//! is told what happens during an extraction, and a [`CancellationToken`]
+//! stops one from another thread. [`Extractor::extract_async`] runs an
+//! extraction as a future.
+
+mod apple_double;
+mod backend;
+mod bench;
+mod beneath;
+mod cat;
+mod checksum;
+// The exzip command, which the binary runs.
+#[doc(hidden)]
+pub mod cli;
+mod completions;
+mod config;
+mod control_chars;
+mod dedupe;
+mod encoding;
+mod eocd;
+mod extra_field;
+mod extractor;
+mod fadvise;
+mod ffi;
+mod file_list;
+mod hook;
+mod i18n;
+mod ignore;
+mod info;
+mod interrupt;
+mod jobs;
+mod json;
+mod libdeflate;
+mod limits;
+mod list;
+mod long_names;
+mod man;
+mod manifest;
+mod md5;
+mod mmap;
+mod mount;
+mod name_index;
+mod name_map;
+mod name_policy;
+mod notify;
+#[cfg(feature = "object-storage")]
+mod object_storage;
+mod observer;
+mod output;
+mod parallel;
+mod pipeline;
+mod portable;
+mod progress;
+mod recover;
+mod remote;
+mod report;
+mod sandbox;
+mod scan;
+mod serve;
+mod sparse;
+mod stale;
+mod task;
+mod tempfile_utils;
+mod throttle;
+mod trash;
+mod tree;
+mod unchanged;
+mod uring;
+mod verify;
+mod watch;
+mod xattr;
+mod zero_copy;
+mod zip_ext;
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read as _, Seek as _, Write as _};
+use std::os::unix::fs::PermissionsExt as _;
+use std::os::unix::io::AsRawFd as _;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Instant, SystemTime};
+
+use anyhow::{bail, Context as _, Result};
+use cap_std::ambient_authority;
+use cap_std::fs::{Dir, Permissions};
+use nix::errno::Errno;
+use nix::unistd::{fchownat, FchownatFlags, Gid, Uid};
+use rustix::fs::{fallocate, mknodat, syncfs, FallocateFlags, FileType, Mode};
+use tempfile::TempDir;
+use zip::read::ZipFile;
+use zip::result::ZipResult;
+use zip::ZipArchive;
+
+use crate::apple_double::{apple_double_target, parse_apple_double};
+use crate::checksum::{reader_digest, Algorithm};
+use crate::control_chars::display_path;
+use crate::dedupe::Dedupe;
+use crate::eocd::{find_archive_end, ArchiveEnd, Bounded};
+use crate::extra_field::{
+    find_extra_field, parse_os2_extended_attributes, OS2_EXTENDED_ATTRIBUTES,
+};
+use crate::i18n::tr;
+use crate::ignore::IgnoreRules;
+use crate::interrupt::{InterruptibleReader, ReadError};
+use crate::limits::{format_size, Limits};
+use crate::long_names::{has_long_component, is_path_too_long, truncate_path};
+use crate::manifest::Manifest;
+use crate::mmap::ArchiveReader;
+use crate::name_index::{decode_names, read_raw_names, IndexedName, RawName};
+use crate::name_map::{NameMap, NameMapEntry};
+use crate::name_policy::{check_traversal, NamePolicy, Placement};
+use crate::output::{diag, notice, say, status, summary, Event, Reporter, Style};
+use crate::parallel::{CopyJob, CopyPool};
+use crate::pipeline::pipelined_copy;
+use crate::recover::{salvage, RecoveryReport};
+use crate::remote::{is_download, is_url, url_file_name, RemoteFile};
+use crate::report::{ArchiveReport, Report};
+use crate::sparse::SparseWriter;
+use crate::stale::{StagingLock, STAGING_PREFIX};
+use crate::tempfile_utils::{tempdir_with_prefix_in, TempDirExt};
+use crate::unchanged::link_unchanged;
+use crate::uring::UringWriter;
+use crate::xattr::{get_xattr, set_xattrs, QUARANTINE};
+use crate::zero_copy::copy_stored;
+use crate::zip_ext::{read_local_extra_field, DosTimezone, SpecialKind, ZipFileExt};
+
+pub use crate::encoding::{get_encoding, ZipEncoding};
+pub use crate::extractor::{detect_encoding, ExtractOptions, Extractor};
+pub use crate::interrupt::CancellationToken;
+pub use crate::observer::ExtractObserver;
+pub use crate::task::ExtractTask;
+
+// Extra free space required on top of the declared size: 5% plus 16 MiB
+// for filesystem overhead such as directory blocks.
+const SPACE_MARGIN_RATIO: f64 = 0.05;
+const SPACE_MARGIN_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum AppleMetadata {
+    /// Discard __MACOSX entirely
+    Skip,
+    /// Apply Finder info, resource forks and attributes as xattrs
+    Xattr,
+    /// Keep the ._ files next to the files they describe
+    Appledouble,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum DuplicatePolicy {
+    /// The last entry wins
+    Last,
+    /// The first entry wins
+    First,
+    /// Abort the extraction
+    Error,
+    /// Extract later entries as "name (1).ext"
+    Rename,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum CaseCollisionPolicy {
+    /// Abort before extracting anything
+    Error,
+    /// Extract later entries as "name (1).ext"
+    Rename,
+    /// Let later entries overwrite earlier ones
+    Overwrite,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum ControlCharPolicy {
+    /// Replace them with \xNN or \u{NNNN}
+    Escape,
+    /// Remove them
+    Strip,
+    /// Abort the extraction
+    Reject,
+    /// Extract the names as they are
+    Keep,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum Inflater {
+    /// The built-in streaming decoder (miniz_oxide)
+    Miniz,
+    /// libdeflate, loaded at run time, for entries up to 64 MiB
+    Libdeflate,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum Sparse {
+    /// For entries of 1 MiB or more compressed at least 8:1, such as disk images
+    Auto,
+    Always,
+    Never,
+}
+
+impl Sparse {
+    fn applies_to(self, size: u64, compressed_size: u64) -> bool {
+        match self {
+            Sparse::Auto => size >= 1024 * 1024 && size / 8 >= compressed_size,
+            Sparse::Always => true,
+            Sparse::Never => false,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum ManifestAlgorithm {
+    /// SHA256SUMS, for sha256sum -c
+    Sha256,
+    /// MD5SUMS, for md5sum -c
+    Md5,
+}
+
+impl ManifestAlgorithm {
+    fn algorithm(self) -> Algorithm {
+        match self {
+            ManifestAlgorithm::Sha256 => Algorithm::Sha256,
+            ManifestAlgorithm::Md5 => Algorithm::Md5,
+        }
+    }
+}
+
+/// What to do when the target directory of an archive already exists.
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConflictPolicy {
+    /// Ask whether to replace it
+    Prompt,
+    /// Replace it
+    Replace,
+    /// Leave it and skip the archive
+    Skip,
+    /// Extract to "name (1)" instead
+    Number,
+    /// Extract into it, replacing files that exist in both
+    Merge,
+    /// Rename it to "name.bak" and extract in its place
+    Backup,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum IoBackend {
+    /// Plain system calls
+    Std,
+    /// Batch the creation and writing of small files with io_uring (experimental)
+    Uring,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum SpecialFilePolicy {
+    /// Report and skip them
+    Skip,
+    /// Abort the extraction
+    Error,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum PathTraversalPolicy {
+    /// Abort the extraction
+    Strict,
+    /// Extract the sanitized path and report the rewrite
+    Warn,
+    /// Extract the sanitized path silently
+    Sanitize,
+}
+
+// Asks a yes or no question.
+type Confirm = Box<dyn Fn(&str) -> Result<bool> + Send + Sync>;
+
+// How to extract, as the command line or an ExtractOptions gives it. The
+// fields are those of the options of the same names.
+struct Options {
+    oenc: Option<String>,
+    verbose: bool,
+    no_mmap: bool,
+    progress: bool,
+    preserve_permissions: bool,
+    allow_symlinks: bool,
+    preserve_owner: bool,
+    // With --utc applied.
+    mtime_timezone: DosTimezone,
+    no_mtime: bool,
+    xattrs: bool,
+    apple_metadata: AppleMetadata,
+    no_ignore: bool,
+    keep_macosx: bool,
+    // With the ignore files read in.
+    ignore_pattern: Vec<String>,
+    no_quarantine: bool,
+    manifest: Option<ManifestAlgorithm>,
+    max_output_size: Option<u64>,
+    max_compression_ratio: Option<f64>,
+    max_entries: Option<usize>,
+    no_space_check: bool,
+    keep_corrupt: bool,
+    best_effort: bool,
+    on_duplicate: DuplicatePolicy,
+    on_case_collision: CaseCollisionPolicy,
+    portable_names: bool,
+    control_chars: ControlCharPolicy,
+    truncate_long_names: bool,
+    path_traversal: PathTraversalPolicy,
+    special_files: SpecialFilePolicy,
+    recreate_fifos: bool,
+    fsync: bool,
+    skip_unchanged: bool,
+    skip_empty_dirs: bool,
+    dedupe: bool,
+    threads: usize,
+    buffer_size: usize,
+    pipeline_buffer: Option<u64>,
+    fadvise: bool,
+    inflate: Inflater,
+    sparse: Sparse,
+    io_backend: IoBackend,
+    // With --force applied.
+    on_conflict: ConflictPolicy,
+    destination: Option<PathBuf>,
+    as_name: Option<PathBuf>,
+    // Cancelled by Ctrl-C for the command, by the caller for the library.
+    cancel: CancellationToken,
+    // The terminal for the command, the observer if any for the library.
+    reporter: Reporter,
+    // Asks whether to go on without enough free space. The library has no
+    // one to ask and gives up.
+    confirm: Option<Confirm>,
+}
+
+// The defaults of the command, silent and not interrupted by Ctrl-C.
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            oenc: None,
+            verbose: false,
+            no_mmap: false,
+            progress: false,
+            preserve_permissions: false,
+            allow_symlinks: false,
+            preserve_owner: false,
+            mtime_timezone: DosTimezone::Local,
+            no_mtime: false,
+            xattrs: false,
+            apple_metadata: AppleMetadata::Skip,
+            no_ignore: false,
+            keep_macosx: false,
+            ignore_pattern: Vec::new(),
+            no_quarantine: false,
+            manifest: None,
+            max_output_size: None,
+            max_compression_ratio: None,
+            max_entries: None,
+            no_space_check: false,
+            keep_corrupt: false,
+            best_effort: false,
+            on_duplicate: DuplicatePolicy::Last,
+            on_case_collision: CaseCollisionPolicy::Error,
+            portable_names: false,
+            control_chars: ControlCharPolicy::Escape,
+            truncate_long_names: false,
+            path_traversal: PathTraversalPolicy::Sanitize,
+            special_files: SpecialFilePolicy::Skip,
+            recreate_fifos: false,
+            fsync: false,
+            skip_unchanged: false,
+            skip_empty_dirs: false,
+            dedupe: false,
+            threads: 1,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            pipeline_buffer: None,
+            fadvise: false,
+            inflate: Inflater::Miniz,
+            sparse: Sparse::Auto,
+            io_backend: IoBackend::Std,
+            on_conflict: ConflictPolicy::Prompt,
+            destination: None,
+            as_name: None,
+            cancel: CancellationToken::new(),
+            reporter: Reporter::default(),
+            confirm: None,
+        }
+    }
+}
+
+struct CopyOutcome {
+    written: u64,
+    crc32: u32,
+    // For --manifest.
+    digest: Option<Vec<u8>>,
+}
+
+const DEFAULT_BUFFER_SIZE: usize = 128 * 1024;
+
+#[derive(Clone)]
+struct CopyOptions {
+    buffer_size: usize,
+    pipeline_buffer: Option<u64>,
+    fadvise: bool,
+    inflate: Inflater,
+    sparse: Sparse,
+    digest: Option<Algorithm>,
+    cancel: CancellationToken,
+    reporter: Reporter,
+}
+
+fn interruptable_copy<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    limit: u64,
+    buffer_size: usize,
+    cancel: &CancellationToken,
+) -> Result<CopyOutcome>
+where
+    R: io::Read + ?Sized,
+    W: io::Write + ?Sized,
+{
+    copy_through(
+        InterruptibleReader::new(reader, limit, cancel),
+        writer,
+        buffer_size,
+    )
+}
+
+fn copy_through<R, W>(
+    mut reader: InterruptibleReader<R>,
+    writer: &mut W,
+    buffer_size: usize,
+) -> Result<CopyOutcome>
+where
+    R: io::Read,
+    W: io::Write + ?Sized,
+{
+    // Each chunk is written as soon as it is read, so that a slow source
+    // like a pipe or a remote archive is not held back to fill the buffer.
+    let mut buffer = vec![0; buffer_size];
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        writer.write_all(&buffer[..n])?;
+    }
+    writer.flush()?;
+    Ok(reader.finish())
+}
+
+// Reserve the declared size up front to reduce fragmentation and to run
+// out of space before writing rather than in the middle of a large entry.
+// The file size is left alone in case the entry turns out shorter.
+fn preallocate(outfile: &File, size: u64) -> Result<()> {
+    if size == 0 {
+        return Ok(());
+    }
+    match fallocate(outfile, FallocateFlags::KEEP_SIZE, 0, size) {
+        Ok(()) => Ok(()),
+        Err(rustix::io::Errno::NOSPC) => bail!("Not enough free space for {} bytes", size),
+        // Not supported by the filesystem; preallocation is only a hint.
+        Err(_) => Ok(()),
+    }
+}
+
+// Decompress the data of a file entry into `writer`.
+fn write_entry<W>(
+    file: &mut ZipFile,
+    archive_file: &File,
+    writer: &mut W,
+    limit: u64,
+    options: &CopyOptions,
+) -> Result<CopyOutcome>
+where
+    W: io::Write + Send + ?Sized,
+{
+    if options.inflate == Inflater::Libdeflate {
+        let outcome = libdeflate::inflate_entry(
+            file,
+            archive_file,
+            writer,
+            limit,
+            options.digest,
+            &options.cancel,
+            &options.reporter,
+        )?;
+        if let Some(outcome) = outcome {
+            return Ok(outcome);
+        }
+    }
+    let reader = InterruptibleReader::new(file, limit, &options.cancel)
+        .with_digest(options.digest)
+        .with_reporter(&options.reporter);
+    match options.pipeline_buffer {
+        Some(pipeline_buffer) => {
+            pipelined_copy(reader, writer, options.buffer_size, pipeline_buffer)
+        }
+        None => copy_through(reader, writer, options.buffer_size),
+    }
+}
+
+fn write_sparse(
+    file: &mut ZipFile,
+    archive_file: &File,
+    outfile: &File,
+    limit: u64,
+    options: &CopyOptions,
+) -> Result<CopyOutcome> {
+    let mut writer = SparseWriter::new(outfile)?;
+    let outcome = write_entry(file, archive_file, &mut writer, limit, options)?;
+    writer.finish()?;
+    Ok(outcome)
+}
+
+// Copy the data of a file entry, without going through user space for
+// stored entries when possible.
+fn copy_entry(
+    file: &mut ZipFile,
+    archive_file: &File,
+    outfile: &mut File,
+    limit: u64,
+    options: &CopyOptions,
+) -> Result<CopyOutcome> {
+    let sparse = options
+        .sparse
+        .applies_to(file.size(), file.compressed_size());
+    // Reserving the space would fill the holes.
+    let reserved = if sparse { 0 } else { file.size().min(limit) };
+    preallocate(outfile, reserved)?;
+    if options.fadvise {
+        fadvise::will_read(archive_file, file.data_start(), file.compressed_size());
+    }
+    let result = if sparse {
+        write_sparse(file, archive_file, outfile, limit, options)
+    } else {
+        match copy_stored(
+            file,
+            archive_file,
+            outfile,
+            limit,
+            options.digest,
+            &options.cancel,
+            &options.reporter,
+        ) {
+            Ok(Some(outcome)) => Ok(outcome),
+            Ok(None) => write_entry(file, archive_file, outfile, limit, options),
+            Err(err) => Err(err),
+        }
+    };
+    // Truncating to the current size releases the blocks reserved past the
+    // end by an entry declaring more than it contains.
+    let length = outfile.metadata()?.len();
+    if length < reserved {
+        outfile.set_len(length)?;
+    }
+    if options.fadvise {
+        fadvise::done_reading(archive_file, file.data_start(), file.compressed_size());
+        fadvise::done_writing(outfile);
+    }
+    result
+}
+
+/// Make an entry name safe to join to the target directory: `..` is resolved
+/// within the name, and the root, drive prefixes and `.` are dropped. `None`
+/// for a name that climbs above the top or contains NUL.
+pub fn sanitize_path(path: &Path) -> Option<PathBuf> {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        use std::path::Component;
+        match component {
+            Component::Normal(os_str) => {
+                // Reject a component which contains NUL character
+                #[cfg(windows)]
+                compile_error!("wide character is not supported");
+                #[cfg(unix)]
+                {
+                    use std::os::unix::ffi::OsStrExt;
+                    if os_str.as_bytes().contains(&0u8) {
+                        return None;
+                    }
+                }
+                result.push(os_str);
+            }
+            Component::ParentDir => {
+                if result == Path::new("") {
+                    return None;
+                }
+                result.pop();
+            }
+            // Remove Prefix(C:), RootDir(/), CurDir(.)
+            _ => {}
+        }
+    }
+    Some(result)
+}
+
+// Whether sanitize_path() drops or resolves something meaningful.
+fn is_traversal(path: &Path) -> bool {
+    use std::path::Component;
+    path.components().any(|component| {
+        matches!(
+            component,
+            Component::Prefix(_) | Component::RootDir | Component::ParentDir
+        )
+    })
+}
+
+// Resolve a symlink target relative to the directory containing the link.
+// Returns None if the target is absolute or escapes the root.
+fn resolve_symlink_target(link_path: &Path, target: &Path) -> Option<PathBuf> {
+    let mut result = link_path.parent()?.to_path_buf();
+    for component in target.components() {
+        use std::path::Component;
+        match component {
+            Component::Normal(os_str) => result.push(os_str),
+            Component::ParentDir => {
+                if result == Path::new("") {
+                    return None;
+                }
+                result.pop();
+            }
+            Component::CurDir => {}
+            Component::Prefix(_) | Component::RootDir => return None,
+        }
+    }
+    Some(result)
+}
+
+// "name.ext" -> "name (1).ext", "name (2).ext", ... until `exists` is false.
+fn unique_path(path: &Path, exists: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|ext| ext.to_string_lossy());
+    (1..)
+        .map(|n| {
+            let name = match &extension {
+                Some(ext) => format!("{} ({}).{}", stem, n, ext),
+                None => format!("{} ({})", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !exists(candidate))
+        .unwrap()
+}
+
+// What unzip() needs to know about the archive and how to extract it.
+#[derive(Clone, Copy)]
+struct UnzipContext<'a> {
+    zipfile: &'a Path,
+    encoding: ZipEncoding,
+    names: &'a [IndexedName],
+    inner_root: &'a Path,
+    ignore_rules: &'a IgnoreRules,
+    options: &'a Options,
+    case_insensitive: bool,
+    // The quarantine attribute of the archive, propagated to every entry.
+    quarantine: Option<&'a [u8]>,
+    // The directory being replaced, with --skip-unchanged.
+    previous: Option<&'a Dir>,
+    // The directories with files in them, with --skip-empty-dirs.
+    nonempty_dirs: Option<&'a HashSet<PathBuf>>,
+}
+
+// A file entry whose data is still being written by a worker thread.
+struct PendingFile {
+    path: PathBuf,
+    unstripped_path: PathBuf,
+    crc32: u32,
+    times: Option<(Option<SystemTime>, Option<SystemTime>)>,
+    name_map_index: Option<usize>,
+}
+
+// Account for the data copied for a file entry and return whether it is intact.
+fn check_copy(
+    result: Result<CopyOutcome>,
+    expected_crc32: u32,
+    unstripped_path: &Path,
+    options: &Options,
+    written_total: &mut u64,
+    crc_mismatches: &mut Vec<PathBuf>,
+    report: &mut RecoveryReport,
+) -> Result<bool> {
+    match result {
+        Ok(outcome) => {
+            *written_total += outcome.written;
+            if outcome.crc32 != expected_crc32 {
+                notice!(
+                    options.reporter,
+                    "CRC mismatch {}",
+                    display_path(unstripped_path)
+                );
+                crc_mismatches.push(unstripped_path.to_path_buf());
+                return Ok(false);
+            }
+            Ok(true)
+        }
+        // Read errors from a truncated or garbled entry; the output
+        // keeps whatever was decompressed before the error, which counts
+        // towards the limits.
+        Err(err) if options.best_effort && ReadError::find(&err).is_some() => {
+            *written_total += ReadError::find(&err).unwrap().read;
+            notice!(
+                options.reporter,
+                "Damaged {}: {}",
+                display_path(unstripped_path),
+                err
+            );
+            report
+                .damaged
+                .push((unstripped_path.to_path_buf(), err.to_string()));
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// The digest for --manifest, taken before check_copy consumes the outcome.
+fn take_digest(result: &mut Result<CopyOutcome>) -> Option<Vec<u8>> {
+    result
+        .as_mut()
+        .ok()
+        .and_then(|outcome| outcome.digest.take())
+}
+
+fn corrupt_path_of(path: &Path) -> PathBuf {
+    let mut corrupt_name = path.file_name().unwrap().to_os_string();
+    corrupt_name.push(".corrupt");
+    path.with_file_name(corrupt_name)
+}
+
+fn rename_corrupt(dst_root: &Dir, path: &Path) -> Result<PathBuf> {
+    let corrupt_path = corrupt_path_of(path);
+    dst_root.rename(path, dst_root, &corrupt_path)?;
+    Ok(corrupt_path)
+}
+
+// Directories known to exist in the staging directory, so that each one is
+// created once instead of being checked again for every file in it. They
+// are created on first use rather than up front, since renaming options
+// may change the names of directories too.
+#[derive(Default)]
+struct CreatedDirs(HashSet<PathBuf>);
+
+impl CreatedDirs {
+    fn create_dir_all(&mut self, dst_root: &Dir, path: &Path) -> io::Result<()> {
+        if self.0.contains(path) {
+            return Ok(());
+        }
+        dst_root.create_dir_all(path)?;
+        for ancestor in path.ancestors() {
+            if !self.0.insert(ancestor.to_path_buf()) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn limits_from_options(options: &Options) -> Limits {
+    Limits {
+        max_output_size: options.max_output_size,
+        max_compression_ratio: options.max_compression_ratio,
+        max_entries: options.max_entries,
+    }
+}
+
+fn skip_event(reporter: &Reporter, name: &Path, reason: &str) {
+    reporter.entry_skipped(name, reason);
+    reporter.emit(
+        Event::new("skip")
+            .str("name", &name.to_string_lossy())
+            .str("reason", reason),
+    );
+}
+
+fn entry_done_event(reporter: &Reporter, path: &Path, bytes: u64, ok: bool) {
+    reporter.emit(
+        Event::new("entry-done")
+            .str("path", &path.to_string_lossy())
+            .num("bytes", bytes)
+            .bool("ok", ok),
+    );
+}
+
+// What zipinfo would tell about an entry, for --verbose.
+fn entry_details(
+    file: &ZipFile,
+    is_utf8: bool,
+    encoding: ZipEncoding,
+    timezone: DosTimezone,
+) -> String {
+    let mut details = format!(
+        "{}, {} -> {}",
+        format!("{:?}", file.compression()).to_lowercase(),
+        format_size(file.compressed_size()),
+        format_size(file.size())
+    );
+    if file.compressed_size() > 0 {
+        details.push_str(&format!(
+            " (ratio {:.1})",
+            file.size() as f64 / file.compressed_size() as f64
+        ));
+    }
+    if let Some(modified) = file.entry_times(timezone).modified {
+        let modified: chrono::DateTime<chrono::Local> = modified.into();
+        details.push_str(&format!(", {}", modified.format("%Y-%m-%d %H:%M:%S")));
+    }
+    if is_utf8 {
+        details.push_str(", UTF-8 flag");
+    } else {
+        details.push_str(&format!(", decoded as {}", encoding.name()));
+    }
+    details
+}
+
+// Returns the number of entries extracted intact and the bytes written.
+fn unzip<R>(
+    archive: &mut ZipArchive<R>,
+    dst_root: &Dir,
+    context: UnzipContext,
+    mut name_map: Option<&mut Vec<NameMapEntry>>,
+    mut local_header_reader: Option<&mut BufReader<File>>,
+    mut manifest: Option<&mut Manifest>,
+) -> Result<(usize, u64)>
+where
+    R: io::Read + io::Seek,
+{
+    let UnzipContext {
+        zipfile,
+        encoding,
+        names,
+        inner_root,
+        ignore_rules,
+        options,
+        case_insensitive,
+        quarantine,
+        previous,
+        nonempty_dirs,
+    } = context;
+    let limits = limits_from_options(options);
+    limits.check_entries(archive.len())?;
+    let mut written_total = 0u64;
+    let mut crc_mismatches = Vec::new();
+    let mut report = RecoveryReport::default();
+    let keep_corrupt = options.keep_corrupt || options.best_effort;
+    let mut names_taken = NamePolicy::new(options, case_insensitive);
+    // The path each file was extracted to, by its name in the archive, for
+    // hard links to find their targets after renaming.
+    let mut final_paths = HashMap::new();
+    // The symlinks created, folded on case-insensitive filesystems. Their
+    // targets are checked from where the link is, so nothing may be
+    // extracted through them.
+    let mut symlinks = HashSet::new();
+    let link_key = |path: &Path| {
+        if case_insensitive {
+            PathBuf::from(fold_case(path))
+        } else {
+            path.to_path_buf()
+        }
+    };
+    let mut created_dirs = CreatedDirs::default();
+    let quarantine_attributes = quarantine.map(|value| vec![(QUARANTINE.to_vec(), value.to_vec())]);
+
+    // Directory permissions are applied after all entries are written
+    // because a read-only directory would reject its children.
+    let mut dir_permissions = Vec::new();
+    // Directory times are applied last since writing children updates them.
+    let mut dir_times = Vec::new();
+    let mut apple_doubles = Vec::new();
+    let mut chown_permitted = true;
+    // File data is written by worker threads when --threads is above one.
+    let threads = match options.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        threads => threads,
+    };
+    let copy_options = CopyOptions {
+        buffer_size: options.buffer_size,
+        pipeline_buffer: options.pipeline_buffer,
+        fadvise: options.fadvise,
+        inflate: options.inflate,
+        sparse: options.sparse,
+        digest: manifest.as_ref().map(|manifest| manifest.algorithm),
+        cancel: options.cancel.clone(),
+        reporter: options.reporter.clone(),
+    };
+    let archive_file = File::open(zipfile)?;
+    if options.fadvise {
+        fadvise::advise_archive(&archive_file);
+    }
+    let pool =
+        (threads > 1).then(|| CopyPool::new(zipfile, threads, !options.no_mmap, &copy_options));
+    let mut pending = Vec::new();
+    let mut dedupe = options.dedupe.then(Dedupe::default);
+    let mut uring = if options.io_backend == IoBackend::Uring && pool.is_none() {
+        Some(UringWriter::new()?)
+    } else {
+        None
+    };
+    for (i, name) in names.iter().enumerate() {
+        let mut file = match archive.by_index(i) {
+            Ok(file) => file,
+            Err(err) if options.best_effort => {
+                notice!(options.reporter, "Damaged entry #{}: {}", i, err);
+                report
+                    .damaged
+                    .push((PathBuf::from(format!("#{}", i)), err.to_string()));
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let mut intact = true;
+        let mut is_pending = false;
+        let decoded_name = &name.decoded;
+        check_traversal(options, decoded_name, name.sanitized.as_deref())?;
+        let mut name_map_entry = NameMapEntry {
+            raw_name: file.name_raw().to_vec(),
+            encoding: if name.is_utf8 {
+                "UTF-8"
+            } else {
+                encoding.name()
+            },
+            path: None,
+            name: decoded_name.clone(),
+            size: file.size(),
+            intact: None,
+        };
+        let Some(unstripped_path) = name.path()?.map(Path::to_path_buf) else {
+            skip_event(&options.reporter, decoded_name, "traversal");
+            if let Some(name_map) = name_map.as_deref_mut() {
+                name_map.push(name_map_entry);
+            }
+            continue;
+        };
+        name_map_entry.name = unstripped_path.clone();
+
+        if options.apple_metadata != AppleMetadata::Skip && file.is_file() {
+            let target = apple_double_target(&unstripped_path)
+                .and_then(|target| Some(target.strip_prefix(inner_root).ok()?.to_path_buf()));
+            if let Some(target) = target {
+                status!(
+                    options.reporter,
+                    Style::Plain,
+                    "Extract",
+                    "{}",
+                    display_path(&unstripped_path)
+                );
+                let mut data = Vec::new();
+                let limit = limits.entry_limit(written_total, file.compressed_size());
+                let outcome = interruptable_copy(
+                    &mut file,
+                    &mut data,
+                    limit,
+                    copy_options.buffer_size,
+                    &options.cancel,
+                )?;
+                written_total += outcome.written;
+                let intact = outcome.crc32 == file.crc32();
+                if intact {
+                    apple_doubles.push((target, data));
+                } else {
+                    crc_mismatches.push(unstripped_path.clone());
+                }
+                name_map_entry.intact = Some(intact);
+                if let Some(name_map) = name_map.as_deref_mut() {
+                    name_map.push(name_map_entry);
+                }
+                continue;
+            }
+        }
+
+        let corrupt_path;
+        let placed;
+        let mut path = match unstripped_path.strip_prefix(inner_root) {
+            Ok(path) if path == Path::new("") => Path::new("."),
+            Ok(path) => path,
+            _ => {
+                status!(
+                    options.reporter,
+                    Style::Skip,
+                    "Skip",
+                    "{}",
+                    display_path(&unstripped_path)
+                );
+                skip_event(&options.reporter, &unstripped_path, "ignored");
+                if !ignore_rules.is_ignored(&unstripped_path) {
+                    bail!("Unexpected strip_prefix: {:?}", inner_root);
+                }
+                if let Some(name_map) = name_map.as_deref_mut() {
+                    name_map.push(name_map_entry);
+                }
+                continue;
+            }
+        };
+
+        if ignore_rules.is_ignored(&unstripped_path) {
+            status!(
+                options.reporter,
+                Style::Skip,
+                "Skip",
+                "{}",
+                display_path(&unstripped_path)
+            );
+            skip_event(&options.reporter, &unstripped_path, "ignored");
+            if let Some(name_map) = name_map.as_deref_mut() {
+                name_map.push(name_map_entry);
+            }
+            continue;
+        }
+
+        if file.is_dir()
+            && path != Path::new(".")
+            && nonempty_dirs.is_some_and(|dirs| !dirs.contains(path))
+        {
+            status!(
+                options.reporter,
+                Style::Skip,
+                "Skip",
+                "{} (empty)",
+                display_path(&unstripped_path)
+            );
+            skip_event(&options.reporter, &unstripped_path, "empty");
+            if let Some(name_map) = name_map.as_deref_mut() {
+                name_map.push(name_map_entry);
+            }
+            continue;
+        }
+
+        let special_kind = file.special_kind();
+        let is_fifo = options.recreate_fifos && special_kind == Some(SpecialKind::Fifo);
+        if let Some(kind) = special_kind.filter(|_| !is_fifo) {
+            match options.special_files {
+                SpecialFilePolicy::Skip => {
+                    status!(
+                        options.reporter,
+                        Style::Skip,
+                        "Skip",
+                        "{} ({})",
+                        display_path(&unstripped_path),
+                        kind
+                    );
+                    skip_event(&options.reporter, &unstripped_path, &kind.to_string());
+                    if let Some(name_map) = name_map.as_deref_mut() {
+                        name_map.push(name_map_entry);
+                    }
+                    continue;
+                }
+                SpecialFilePolicy::Error => {
+                    bail!(
+                        "Unsupported {} entry {}",
+                        kind,
+                        display_path(&unstripped_path)
+                    );
+                }
+            }
+        }
+
+        let rewritten = names_taken.rewrite(&unstripped_path, path)?;
+        path = &rewritten;
+
+        if !file.is_dir() {
+            placed = match names_taken.place(&unstripped_path, path.to_path_buf())? {
+                Placement::New(path) => path,
+                Placement::Overwrite(path) => {
+                    // The earlier entry may not be written yet.
+                    if let Some(uring) = &mut uring {
+                        uring.flush(dst_root)?;
+                    }
+                    // Truncating a file linked by --skip-unchanged would
+                    // change the directory being replaced.
+                    if previous.is_some() {
+                        let _ = dst_root.remove_file(&path);
+                    }
+                    path
+                }
+                Placement::Skip => {
+                    if let Some(name_map) = name_map.as_deref_mut() {
+                        name_map.push(name_map_entry);
+                    }
+                    continue;
+                }
+            };
+            path = &placed;
+        }
+
+        if let Some(link) = path
+            .ancestors()
+            .skip(1)
+            .find(|ancestor| symlinks.contains(&link_key(ancestor)))
+        {
+            bail!(
+                "{} is inside the symlink {}",
+                display_path(&unstripped_path),
+                link.display()
+            );
+        }
+
+        if !file.is_dir() {
+            final_paths.insert(unstripped_path.clone(), path.to_path_buf());
+        }
+
+        let is_symlink = options.allow_symlinks && file.is_symlink();
+        // Files written through io_uring only exist after the batch is
+        // flushed, so not those needing more than their times set.
+        let needs_path_ops = options.preserve_owner
+            || options.preserve_permissions
+            || local_header_reader.is_some()
+            || quarantine_attributes.is_some();
+        let mut is_queued = false;
+
+        if options.verbose {
+            status!(
+                options.reporter,
+                Style::Plain,
+                "Extract",
+                "{}  {}",
+                display_path(&unstripped_path),
+                entry_details(&file, name.is_utf8, encoding, options.mtime_timezone)
+            );
+        } else {
+            status!(
+                options.reporter,
+                Style::Plain,
+                "Extract",
+                "{}",
+                display_path(&unstripped_path)
+            );
+        }
+        progress::start_entry(&display_path(&unstripped_path), file.size());
+        options
+            .reporter
+            .entry_started(&unstripped_path, file.size());
+        options.reporter.emit(
+            Event::new("entry-start")
+                .str("name", &unstripped_path.to_string_lossy())
+                .str("path", &path.to_string_lossy())
+                .num("size", file.size()),
+        );
+        let written_before = written_total;
+        if file.is_dir() {
+            created_dirs.create_dir_all(dst_root, path)?;
+        } else if is_symlink {
+            let mut target = String::new();
+            file.read_to_string(&mut target)
+                .context("Symlink target is not valid UTF-8")?;
+            if target.contains('\0') || resolve_symlink_target(path, Path::new(&target)).is_none() {
+                bail!(
+                    "Symlink {} points outside of the extracted directory: {}",
+                    display_path(&unstripped_path),
+                    target
+                );
+            }
+            created_dirs.create_dir_all(dst_root, path.parent().unwrap())?;
+            dst_root.symlink(&target, path)?;
+            symlinks.insert(link_key(path));
+        } else if let Some(link_target) = file.hardlink_target(encoding) {
+            // Hard links refer to an entry extracted earlier in the archive.
+            let link_target = sanitize_path(&link_target)
+                .and_then(|target| final_paths.get(&target))
+                .cloned()
+                .with_context(|| {
+                    format!(
+                        "Hard link {} to an entry not extracted: {}",
+                        display_path(&unstripped_path),
+                        display_path(&link_target)
+                    )
+                })?;
+            if let Some(uring) = &mut uring {
+                uring.flush(dst_root)?;
+            }
+            created_dirs.create_dir_all(dst_root, path.parent().unwrap())?;
+            dst_root.hard_link(&link_target, dst_root, path)?;
+            if let Some(manifest) = manifest.as_deref_mut() {
+                manifest.link(&link_target, path);
+            }
+        } else if is_fifo {
+            created_dirs.create_dir_all(dst_root, path.parent().unwrap())?;
+            mknodat(
+                dst_root,
+                path,
+                FileType::Fifo,
+                Mode::from_bits_truncate(0o644),
+                0,
+            )?;
+        } else if file.is_file() {
+            created_dirs.create_dir_all(dst_root, path.parent().unwrap())?;
+            let queue_to = uring.as_mut().filter(|_| {
+                file.size() <= uring::MAX_FILE_SIZE
+                    && !needs_path_ops
+                    && !options
+                        .sparse
+                        .applies_to(file.size(), file.compressed_size())
+            });
+            let unchanged = match previous {
+                Some(previous) => {
+                    link_unchanged(previous, dst_root, path, file.size(), file.crc32())?
+                }
+                None => false,
+            };
+            if unchanged {
+                status!(
+                    options.reporter,
+                    Style::Plain,
+                    "Unchanged",
+                    "{}",
+                    display_path(&unstripped_path)
+                );
+                if let Some(manifest) = manifest.as_deref_mut() {
+                    let digest = reader_digest(
+                        &mut dst_root.open(path)?,
+                        manifest.algorithm,
+                        &options.cancel,
+                    )?;
+                    manifest.insert(path, Some(digest));
+                }
+                if let Some(dedupe) = &mut dedupe {
+                    dedupe.add(path, file.crc32());
+                }
+            } else if let Some(uring) = queue_to {
+                let mut data = Vec::with_capacity(file.size() as usize);
+                let limit = limits.entry_limit(written_total, file.compressed_size());
+                let reader = InterruptibleReader::new(&mut file, limit, &options.cancel)
+                    .with_digest(copy_options.digest)
+                    .with_reporter(&options.reporter);
+                let mut result = copy_through(reader, &mut data, copy_options.buffer_size);
+                let digest = take_digest(&mut result);
+                intact = check_copy(
+                    result,
+                    file.crc32(),
+                    &unstripped_path,
+                    options,
+                    &mut written_total,
+                    &mut crc_mismatches,
+                    &mut report,
+                )?;
+                if intact {
+                    uring.queue(dst_root, path, data)?;
+                    is_queued = true;
+                    if let Some(manifest) = manifest.as_deref_mut() {
+                        manifest.insert(path, digest);
+                    }
+                    if let Some(dedupe) = &mut dedupe {
+                        dedupe.add(path, file.crc32());
+                    }
+                } else if keep_corrupt {
+                    corrupt_path = corrupt_path_of(path);
+                    beneath::create_file(dst_root, &corrupt_path)?.write_all(&data)?;
+                    path = &corrupt_path;
+                } else {
+                    // Never written; the extraction fails at the end.
+                    entry_done_event(
+                        &options.reporter,
+                        path,
+                        written_total - written_before,
+                        false,
+                    );
+                    if let Some(name_map) = name_map.as_deref_mut() {
+                        name_map_entry.intact = Some(false);
+                        name_map.push(name_map_entry);
+                    }
+                    continue;
+                }
+            } else if let Some(pool) = &pool {
+                let outfile = beneath::create_file(dst_root, path)?;
+                let limit =
+                    limits.entry_limit(written_total + pool.written(), file.compressed_size());
+                pool.submit(CopyJob {
+                    id: pending.len(),
+                    index: i,
+                    outfile,
+                    limit,
+                });
+                pending.push(PendingFile {
+                    path: path.to_path_buf(),
+                    unstripped_path: unstripped_path.clone(),
+                    crc32: file.crc32(),
+                    times: None,
+                    name_map_index: None,
+                });
+                is_pending = true;
+            } else {
+                let mut tmpfile = beneath::create_tmpfile(dst_root, path)?;
+                let mut named_file;
+                let outfile = match &mut tmpfile {
+                    Some(tmpfile) => &mut tmpfile.file,
+                    None => {
+                        named_file = beneath::create_file(dst_root, path)?;
+                        &mut named_file
+                    }
+                };
+                let limit = limits.entry_limit(written_total, file.compressed_size());
+                let mut result =
+                    copy_entry(&mut file, &archive_file, outfile, limit, &copy_options);
+                let digest = take_digest(&mut result);
+                intact = check_copy(
+                    result,
+                    file.crc32(),
+                    &unstripped_path,
+                    options,
+                    &mut written_total,
+                    &mut crc_mismatches,
+                    &mut report,
+                )?;
+                if !intact && keep_corrupt {
+                    corrupt_path = corrupt_path_of(path);
+                    match &tmpfile {
+                        Some(tmpfile) => tmpfile.link(&corrupt_path)?,
+                        None => dst_root.rename(path, dst_root, &corrupt_path)?,
+                    }
+                    path = &corrupt_path;
+                } else if let Some(tmpfile) = &tmpfile {
+                    if !intact {
+                        // Never given a name; the extraction fails at the end.
+                        entry_done_event(
+                            &options.reporter,
+                            path,
+                            written_total - written_before,
+                            false,
+                        );
+                        if let Some(name_map) = name_map.as_deref_mut() {
+                            name_map_entry.intact = Some(false);
+                            name_map.push(name_map_entry);
+                        }
+                        continue;
+                    }
+                    tmpfile.link(path)?;
+                }
+                if intact {
+                    if let Some(manifest) = manifest.as_deref_mut() {
+                        manifest.insert(path, digest);
+                    }
+                    if let Some(dedupe) = &mut dedupe {
+                        dedupe.add(path, file.crc32());
+                    }
+                }
+            }
+        }
+
+        // Set last modified time
+        let times = file.entry_times(options.mtime_timezone);
+        if !options.no_mtime && (times.accessed.is_some() || times.modified.is_some()) {
+            if file.is_dir() {
+                dir_times.push((path.to_path_buf(), times.accessed, times.modified));
+            } else if is_pending {
+                // Writing the data would update the times.
+                pending.last_mut().unwrap().times = Some((times.accessed, times.modified));
+            } else if is_queued {
+                uring
+                    .as_mut()
+                    .unwrap()
+                    .set_times(times.accessed, times.modified);
+            } else {
+                beneath::set_times(dst_root, path, times.accessed, times.modified)?;
+            }
+        }
+
+        if options.preserve_owner && chown_permitted {
+            if let Some((uid, gid)) = file.unix_owner() {
+                match fchownat(
+                    Some(dst_root.as_raw_fd()),
+                    path,
+                    Some(Uid::from_raw(uid)),
+                    Some(Gid::from_raw(gid)),
+                    FchownatFlags::NoFollowSymlink,
+                ) {
+                    Ok(()) => {}
+                    Err(Errno::EPERM) => {
+                        diag!(
+                            options.reporter,
+                            "Warning: Not permitted to change ownership; owners are not restored"
+                        );
+                        chown_permitted = false;
+                    }
+                    Err(err) => return Err(err).context("Failed to change ownership"),
+                }
+            }
+        }
+
+        // Opening a FIFO to set attributes would block until a writer appears.
+        if let Some(reader) = local_header_reader
+            .as_deref_mut()
+            .filter(|_| !is_symlink && !is_fifo)
+        {
+            let local_extra = match read_local_extra_field(reader, file.header_start()) {
+                Ok(local_extra) => local_extra,
+                Err(err) if options.best_effort => {
+                    notice!(
+                        options.reporter,
+                        "Unreadable local header {}: {}",
+                        display_path(&unstripped_path),
+                        err
+                    );
+                    Vec::new()
+                }
+                Err(err) => return Err(err.into()),
+            };
+            if let Some(attributes) = find_extra_field(&local_extra, OS2_EXTENDED_ATTRIBUTES)
+                .and_then(parse_os2_extended_attributes)
+            {
+                match set_xattrs(dst_root, path, file.is_dir(), &attributes) {
+                    Ok(()) => {}
+                    Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+                        diag!(
+                            options.reporter,
+                            "Warning: Extended attributes are not supported here"
+                        );
+                        local_header_reader = None;
+                    }
+                    Err(err) => return Err(err).context("Failed to set extended attributes"),
+                }
+            }
+        }
+
+        if let Some(attributes) = quarantine_attributes
+            .as_deref()
+            .filter(|_| !is_symlink && !is_fifo)
+        {
+            set_xattrs(dst_root, path, file.is_dir(), attributes)
+                .context("Failed to set the quarantine attribute")?;
+        }
+
+        if options.preserve_permissions && !is_symlink {
+            if let Some(mode) = file.unix_mode() {
+                let permissions = Permissions::from_mode(mode & 0o777);
+                if file.is_dir() {
+                    dir_permissions.push((path.to_path_buf(), permissions));
+                } else if file.is_file() {
+                    dst_root.set_permissions(path, permissions)?;
+                }
+            }
+        }
+
+        // Without --allow-symlinks, a symlink entry is written as a regular
+        // file containing its target path.
+
+        if let Some(name_map) = name_map.as_deref_mut() {
+            if is_pending {
+                pending.last_mut().unwrap().name_map_index = Some(name_map.len());
+            }
+            name_map_entry.path = Some(path.to_path_buf());
+            name_map_entry.intact = (file.is_file() && !is_pending).then_some(intact);
+            name_map.push(name_map_entry);
+        }
+        if !is_pending {
+            entry_done_event(
+                &options.reporter,
+                path,
+                written_total - written_before,
+                intact,
+            );
+            if intact {
+                report.recovered += 1;
+            }
+        }
+
+        if options.cancel.is_cancelled() {
+            bail!("Interrupted");
+        }
+    }
+
+    if let Some(uring) = &mut uring {
+        uring.flush(dst_root)?;
+    }
+
+    if let Some(pool) = pool {
+        let mut results = pool.finish();
+        results.sort_by_key(|(id, _)| *id);
+        for (id, mut result) in results {
+            let entry = &pending[id];
+            let written_before = written_total;
+            let digest = take_digest(&mut result);
+            let intact = check_copy(
+                result,
+                entry.crc32,
+                &entry.unstripped_path,
+                options,
+                &mut written_total,
+                &mut crc_mismatches,
+                &mut report,
+            )?;
+            let mut path = entry.path.clone();
+            if !intact && keep_corrupt {
+                path = rename_corrupt(dst_root, &path)?;
+            }
+            if intact {
+                if let Some(manifest) = manifest.as_deref_mut() {
+                    manifest.insert(&path, digest);
+                }
+                if let Some(dedupe) = &mut dedupe {
+                    dedupe.add(&path, entry.crc32);
+                }
+            }
+            if let (Some(name_map), Some(index)) = (name_map.as_deref_mut(), entry.name_map_index) {
+                name_map[index].path = Some(path.clone());
+                name_map[index].intact = Some(intact);
+            }
+            if let Some((atime, mtime)) = entry.times {
+                beneath::set_times(dst_root, &path, atime, mtime)?;
+            }
+            entry_done_event(
+                &options.reporter,
+                &path,
+                written_total - written_before,
+                intact,
+            );
+            if intact {
+                report.recovered += 1;
+            }
+        }
+    }
+
+    if options.best_effort {
+        for path in crc_mismatches.drain(..) {
+            report.damaged.push((path, "CRC mismatch".to_string()));
+        }
+        report.print(&options.reporter);
+    }
+    if !crc_mismatches.is_empty() {
+        notice!(
+            options.reporter,
+            "{} entries failed the CRC check:",
+            crc_mismatches.len()
+        );
+        for path in &crc_mismatches {
+            notice!(options.reporter, "  {}", display_path(path));
+        }
+        if !keep_corrupt {
+            bail!("CRC mismatch");
+        }
+    }
+
+    if let Some(dedupe) = dedupe {
+        let (linked, freed) = dedupe.link(dst_root, &options.cancel, &options.reporter)?;
+        if linked > 0 {
+            say!(
+                options.reporter,
+                "Linked {} duplicate files, saving {}",
+                linked,
+                format_size(freed)
+            );
+        }
+    }
+
+    // The files described by AppleDouble entries may come later in the
+    // archive, so the metadata is applied after all entries are written.
+    for (target, data) in apple_doubles {
+        match dst_root.symlink_metadata(&target) {
+            Ok(metadata) if !metadata.is_symlink() => {}
+            _ => {
+                say!(
+                    options.reporter,
+                    "Skip metadata for missing {}",
+                    target.display()
+                );
+                continue;
+            }
+        }
+        match options.apple_metadata {
+            AppleMetadata::Skip => unreachable!(),
+            AppleMetadata::Xattr => {
+                let attributes = parse_apple_double(&data).with_context(|| {
+                    format!("Malformed AppleDouble file for {}", target.display())
+                })?;
+                let is_dir = dst_root.symlink_metadata(&target)?.is_dir();
+                set_xattrs(dst_root, &target, is_dir, &attributes)
+                    .with_context(|| format!("Failed to set metadata on {}", target.display()))?;
+            }
+            AppleMetadata::Appledouble => {
+                let mut name = OsString::from("._");
+                name.push(target.file_name().unwrap());
+                dst_root.write(target.with_file_name(name), &data)?;
+            }
+        }
+    }
+
+    // Deepest first so that a read-only parent does not block its children.
+    dir_permissions.sort_by_key(|(path, _)| std::cmp::Reverse(path.components().count()));
+    for (path, permissions) in dir_permissions {
+        dst_root.set_permissions(path, permissions)?;
+    }
+    dir_times.sort_by_key(|(path, _, _)| std::cmp::Reverse(path.components().count()));
+    for (path, atime, mtime) in dir_times {
+        beneath::set_times(dst_root, &path, atime, mtime)?;
+    }
+    Ok((report.recovered, written_total))
+}
+
+fn get_inner_root(names: &[IndexedName], ignore_rules: &IgnoreRules) -> Result<PathBuf> {
+    if names.is_empty() {
+        return Ok(PathBuf::new());
+    }
+
+    let mut root: Option<PathBuf> = None;
+    for name in names {
+        let Some(path) = name.path()? else {
+            continue;
+        };
+        let mut path = path.to_path_buf();
+        if ignore_rules.is_ignored(&path) {
+            continue;
+        }
+        if !name.is_dir {
+            path.pop();
+        }
+        if let Some(root) = &root {
+            if !path.starts_with(root) {
+                return Ok(PathBuf::new());
+            }
+        } else if let Some(name) = path.iter().next() {
+            // The first found directory
+            root = Some(PathBuf::from(name));
+        } else {
+            // There is a file in root
+            return Ok(PathBuf::new());
+        }
+    }
+    Ok(root.unwrap_or_default())
+}
+
+fn detect_filename_encoding(raw_names: &[RawName]) -> ZipEncoding {
+    for candidate_encoding in &[encoding_rs::UTF_8, encoding_rs::SHIFT_JIS] {
+        let mismatch = raw_names.iter().any(|raw_name| {
+            !raw_name.is_utf8 && {
+                let (_cow, _encoding, malformed) = candidate_encoding.decode(&raw_name.bytes);
+                malformed
+            }
+        });
+        if !mismatch {
+            return ZipEncoding::EncodingRs(candidate_encoding);
+        }
+    }
+    ZipEncoding::Cp437
+}
+
+fn declared_size(raw_names: &[RawName]) -> u64 {
+    raw_names
+        .iter()
+        .fold(0u64, |total, raw_name| total.saturating_add(raw_name.size))
+}
+
+// Returns false if there is no one to ask, or they declined to continue
+// without enough space.
+fn check_free_space(raw_names: &[RawName], staging_dir: &Path, options: &Options) -> Result<bool> {
+    let required = declared_size(raw_names);
+    let margin = ((required as f64 * SPACE_MARGIN_RATIO) as u64).saturating_add(SPACE_MARGIN_BYTES);
+    let stat = rustix::fs::statvfs(staging_dir)?;
+    let available = stat.f_bavail.saturating_mul(stat.f_frsize);
+    if required.saturating_add(margin) <= available {
+        return Ok(true);
+    }
+    notice!(
+        options.reporter,
+        "Not enough free space: {} needed, {} available",
+        format_size(required),
+        format_size(available)
+    );
+    match &options.confirm {
+        Some(confirm) => confirm(&tr!("Extract anyway?")),
+        None => Ok(false),
+    }
+}
+
+fn fold_case(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
+fn is_case_insensitive(dir: &Dir) -> io::Result<bool> {
+    const PROBE: &str = "exzip-case-probe";
+    dir.create(PROBE)?;
+    let result = dir.try_exists(PROBE.to_uppercase());
+    dir.remove_file(PROBE)?;
+    result
+}
+
+// The paths of the entries to be extracted, relative to the target, and
+// whether each is a directory.
+fn extracted_paths(
+    names: &[IndexedName],
+    inner_root: &Path,
+    ignore_rules: &IgnoreRules,
+) -> Result<Vec<(PathBuf, bool)>> {
+    let mut paths = Vec::new();
+    for name in names {
+        let Some(path) = name.path()? else {
+            continue;
+        };
+        if ignore_rules.is_ignored(path) {
+            continue;
+        }
+        if let Ok(stripped) = path.strip_prefix(inner_root) {
+            paths.push((stripped.to_path_buf(), name.is_dir));
+        }
+    }
+    Ok(paths)
+}
+
+// The directories that files are extracted into, directly or below.
+fn nonempty_dirs(paths: &[(PathBuf, bool)]) -> HashSet<PathBuf> {
+    let mut dirs = HashSet::new();
+    for (path, _) in paths.iter().filter(|(_, is_dir)| !is_dir) {
+        for dir in path.ancestors().skip(1) {
+            if !dirs.insert(dir.to_path_buf()) {
+                break;
+            }
+        }
+    }
+    dirs
+}
+
+// Pairs of file entries whose extracted paths differ only in case.
+fn find_case_collisions(paths: &[(PathBuf, bool)]) -> Vec<(PathBuf, PathBuf)> {
+    let mut seen: HashMap<String, &PathBuf> = HashMap::new();
+    let mut collisions = Vec::new();
+    for (path, _) in paths.iter().filter(|(_, is_dir)| !is_dir) {
+        match seen.get(&fold_case(path)) {
+            Some(&other) if other != path => collisions.push((other.clone(), path.clone())),
+            Some(_) => {}
+            None => {
+                seen.insert(fold_case(path), path);
+            }
+        }
+    }
+    collisions
+}
+
+// Fail early with a clear message instead of ENAMETOOLONG halfway.
+fn check_path_lengths(paths: &[(PathBuf, bool)], target_path: &Path, truncate: bool) -> Result<()> {
+    let base_len = std::path::absolute(target_path)?.as_os_str().len();
+    for (path, _) in paths {
+        let path = if truncate {
+            truncate_path(path)
+        } else if has_long_component(path) {
+            bail!(
+                "Name too long (over {} bytes): {} (use --truncate-long-names)",
+                long_names::NAME_MAX,
+                display_path(path)
+            );
+        } else {
+            path.clone()
+        };
+        if is_path_too_long(base_len, &path) {
+            bail!(
+                "Path too long (over {} bytes): {}",
+                long_names::PATH_MAX,
+                display_path(&path)
+            );
+        }
+    }
+    Ok(())
+}
+
+type Archive = ZipArchive<Bounded<ArchiveReader>>;
+
+// Open an archive, ignoring any data appended after it. Warnings about the
+// archive go to `reporter`.
+fn open_archive(zipfile: &Path, reporter: &Reporter, use_mmap: bool) -> ZipResult<Archive> {
+    let mut reader = if is_url(zipfile) {
+        ArchiveReader::Remote(RemoteFile::open(zipfile)?)
+    } else {
+        ArchiveReader::open(File::open(zipfile)?, use_mmap)?
+    };
+    let file_len = reader.seek(io::SeekFrom::End(0))?;
+    let archive_end = match find_archive_end(&mut reader)? {
+        Some(archive_end) => {
+            if archive_end.end < file_len {
+                notice!(
+                    reporter,
+                    "Warning: Ignoring {} bytes after the end of the archive",
+                    file_len - archive_end.end
+                );
+            }
+            if archive_end.comment_length_fix.is_some() {
+                notice!(reporter, "Warning: The archive comment is truncated");
+            }
+            archive_end
+        }
+        None => ArchiveEnd {
+            end: file_len,
+            comment_length_fix: None,
+        },
+    };
+    ZipArchive::new(Bounded::new(reader, archive_end)?)
+}
+
+// The directory an archive is extracted to, before any renaming by the
+// conflict policy.
+fn target_path(zipfile: &Path, options: &Options) -> PathBuf {
+    if let Some(name) = &options.as_name {
+        return match &options.destination {
+            Some(dir) => dir.join(name),
+            None if is_url(zipfile) || is_download(zipfile) => name.clone(),
+            None => zipfile.parent().unwrap_or(Path::new("")).join(name),
+        };
+    }
+    match &options.destination {
+        Some(dir) if is_url(zipfile) => dir.join(url_file_name(zipfile).with_extension("")),
+        Some(dir) => dir.join(zipfile.file_stem().unwrap()),
+        // What is downloaded goes to the current directory.
+        None if is_url(zipfile) => url_file_name(zipfile).with_extension(""),
+        None if is_download(zipfile) => PathBuf::from(zipfile.file_stem().unwrap()),
+        None => zipfile.with_extension(""),
+    }
+}
+
+// Staging directories are made next to the target, to be renamed into place.
+fn staging_parent(target_path: &Path) -> &Path {
+    match target_path.parent().unwrap() {
+        parent if parent == Path::new("") => Path::new("."),
+        parent => parent,
+    }
+}
+
+fn create_staging_dir(staging_parent: &Path) -> Result<(TempDir, StagingLock)> {
+    let temp_dir_obj = tempdir_with_prefix_in(staging_parent, STAGING_PREFIX)?;
+    let lock = StagingLock::acquire(temp_dir_obj.path())?;
+    Ok((temp_dir_obj, lock))
+}
+
+// "name.bak", or "name (1).bak" and so on if that exists too.
+fn backup_path(target_path: &Path) -> PathBuf {
+    let mut name = target_path.file_name().unwrap().to_os_string();
+    name.push(".bak");
+    let backup = target_path.with_file_name(name);
+    if backup.symlink_metadata().is_err() {
+        return backup;
+    }
+    unique_path(&backup, |candidate| candidate.symlink_metadata().is_ok())
+}
+
+// Move the extracted tree into an existing directory (--on-conflict=merge).
+// Directories in both are merged; anything else in the way is replaced.
+// The existing directories keep their own permissions and times.
+fn merge_dirs(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        let is_dir = entry.file_type()?.is_dir();
+        match fs::symlink_metadata(&target) {
+            Ok(metadata) if metadata.is_dir() && is_dir => {
+                merge_dirs(&entry.path(), &target)?;
+                continue;
+            }
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&target)?,
+            // A file can be renamed over a file, but not a directory.
+            Ok(_) if is_dir => fs::remove_file(&target)?,
+            _ => {}
+        }
+        fs::rename(entry.path(), &target)?;
+    }
+    Ok(())
+}
+
+fn move_into_place(temp_dir_obj: TempDir, target_path: &Path, options: &Options) -> Result<()> {
+    // syncfs covers every file and directory written under the staging
+    // directory, including ones whose permissions no longer allow opening.
+    if options.fsync {
+        let staging = File::open(temp_dir_obj.path())?;
+        syncfs(&staging).context("Failed to sync the extracted files")?;
+    }
+
+    let merge = options.on_conflict == ConflictPolicy::Merge && target_path.is_dir();
+    let line = format!(
+        "{} {} -> {}",
+        if merge { "merge" } else { "rename" },
+        temp_dir_obj.relative_path_from("./").display(),
+        target_path.display()
+    );
+    say!(
+        options.reporter,
+        "{}",
+        output::paint_stdout(&line, Style::Done)
+    );
+
+    if merge {
+        merge_dirs(temp_dir_obj.path(), target_path)
+            .with_context(|| format!("Failed to merge into {}", target_path.display()))?;
+    } else {
+        if target_path.exists() {
+            if options.on_conflict == ConflictPolicy::Backup {
+                let backup = backup_path(target_path);
+                fs::rename(target_path, &backup).with_context(|| {
+                    format!("Failed to rename the old directory to {}", backup.display())
+                })?;
+                notice!(
+                    options.reporter,
+                    "Backup {} -> {}",
+                    target_path.display(),
+                    backup.display()
+                );
+            } else {
+                fs::remove_dir_all(target_path).with_context(|| {
+                    format!(
+                        "Failed to remove the old directory {}",
+                        target_path.display()
+                    )
+                })?;
+            }
+        }
+        fs::rename(temp_dir_obj.path(), target_path).with_context(|| {
+            format!("Failed to move the directory to {}", target_path.display())
+        })?;
+    }
+
+    if options.fsync {
+        File::open(staging_parent(target_path))?
+            .sync_all()
+            .context("Failed to sync the parent directory")?;
+    }
+    Ok(())
+}
+
+/// What an extraction produced.
+pub struct Extracted {
+    /// The directory the archive was extracted to.
+    pub target_path: PathBuf,
+    /// The number of entries extracted.
+    pub entries: usize,
+    /// The encoding the entry names were decoded with; unknown when a damaged
+    /// archive is salvaged without one given.
+    pub encoding: Option<&'static str>,
+}
+
+// Extract an archive without a usable central directory.
+fn salvage_into(zipfile: &Path, target_path: &Path, options: &Options) -> Result<Extracted> {
+    let mut reader = BufReader::new(File::open(zipfile)?);
+    let (temp_dir_obj, _lock) = create_staging_dir(staging_parent(target_path))?;
+    let temp_dir = Dir::open_ambient_dir(temp_dir_obj.path(), ambient_authority())?;
+    let encoding = options
+        .oenc
+        .as_deref()
+        .map(|name| get_encoding(name).unwrap());
+    let ignore_rules = IgnoreRules::new(
+        options.no_ignore,
+        options.keep_macosx,
+        &options.ignore_pattern,
+    );
+    let mut names_taken = NamePolicy::new(options, is_case_insensitive(&temp_dir)?);
+    let report = salvage(
+        &mut reader,
+        &temp_dir,
+        encoding,
+        &ignore_rules,
+        &mut names_taken,
+        options,
+    )?;
+    report.print(&options.reporter);
+    if report.recovered == 0 {
+        bail!("Nothing could be recovered");
+    }
+    move_into_place(temp_dir_obj, target_path, options)?;
+    Ok(Extracted {
+        target_path: target_path.to_path_buf(),
+        entries: report.recovered,
+        encoding: encoding.map(|encoding| encoding.name()),
+    })
+}
+
+fn extract_into(
+    zipfile: &Path,
+    target_path: &Path,
+    options: &Options,
+    name_map: Option<&mut NameMap>,
+    report: Option<&mut Report>,
+) -> Result<Extracted> {
+    let start = Instant::now();
+    let mut archive = match open_archive(zipfile, &options.reporter, !options.no_mmap) {
+        Ok(archive) => archive,
+        Err(err) if options.best_effort => {
+            notice!(
+                options.reporter,
+                "Warning: Failed to read the central directory ({}); scanning local headers",
+                err
+            );
+            return salvage_into(zipfile, target_path, options);
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let raw_names = read_raw_names(&mut archive)?;
+    let staging_parent = staging_parent(target_path);
+    if !options.no_space_check && !check_free_space(&raw_names, staging_parent, options)? {
+        bail!("Not enough free space");
+    }
+
+    let (temp_dir_obj, _lock) = create_staging_dir(staging_parent)?;
+    let temp_dir = Dir::open_ambient_dir(temp_dir_obj.path(), ambient_authority())?;
+
+    let mut local_header_reader = if options.xattrs {
+        Some(BufReader::new(File::open(zipfile)?))
+    } else {
+        None
+    };
+
+    let encoding = if let Some(encoding_name) = &options.oenc {
+        get_encoding(encoding_name).unwrap()
+    } else {
+        detect_filename_encoding(&raw_names)
+    };
+    let names = decode_names(&raw_names, encoding);
+
+    let quarantine = if options.no_quarantine {
+        None
+    } else {
+        get_xattr(zipfile, QUARANTINE)?
+    };
+
+    let ignore_rules = IgnoreRules::new(
+        options.no_ignore,
+        options.keep_macosx,
+        &options.ignore_pattern,
+    );
+    let inner_root =
+        get_inner_root(&names, &ignore_rules).context("Failed to determine inner root")?;
+
+    let paths = extracted_paths(&names, &inner_root, &ignore_rules)?;
+    check_path_lengths(&paths, target_path, options.truncate_long_names)?;
+    let nonempty_dirs = options.skip_empty_dirs.then(|| nonempty_dirs(&paths));
+
+    let case_insensitive = is_case_insensitive(&temp_dir)?;
+    if case_insensitive && options.on_case_collision == CaseCollisionPolicy::Error {
+        let collisions = find_case_collisions(&paths);
+        if !collisions.is_empty() {
+            notice!(
+                options.reporter,
+                "Names differing only in case on a case-insensitive filesystem:"
+            );
+            for (first, second) in &collisions {
+                notice!(
+                    options.reporter,
+                    "  {} <-> {}",
+                    first.display(),
+                    second.display()
+                );
+            }
+            bail!("Case collision (use --on-case-collision to resolve)");
+        }
+    }
+
+    let previous = if options.skip_unchanged && target_path.is_dir() {
+        Some(Dir::open_ambient_dir(target_path, ambient_authority())?)
+    } else {
+        None
+    };
+
+    let mut name_map_entries = Vec::new();
+    let context = UnzipContext {
+        zipfile,
+        encoding,
+        names: &names,
+        inner_root: &inner_root,
+        ignore_rules: &ignore_rules,
+        options,
+        case_insensitive,
+        quarantine: quarantine.as_deref(),
+        previous: previous.as_ref(),
+        nonempty_dirs: nonempty_dirs.as_ref(),
+    };
+    if options.progress {
+        progress::start(declared_size(&raw_names));
+    }
+    let mut manifest = options
+        .manifest
+        .map(|algorithm| Manifest::new(algorithm.algorithm()));
+    let scanned = Instant::now();
+    let result = unzip(
+        &mut archive,
+        &temp_dir,
+        context,
+        (name_map.is_some() || report.is_some()).then_some(&mut name_map_entries),
+        local_header_reader.as_mut(),
+        manifest.as_mut(),
+    );
+    progress::finish();
+    let (entries, written) = result?;
+    if let Some(manifest) = manifest {
+        let name = manifest.file_name();
+        manifest
+            .write(&temp_dir)
+            .with_context(|| format!("Failed to write {}", name))?;
+    }
+
+    let extracted = Instant::now();
+    move_into_place(temp_dir_obj, target_path, options)?;
+    let renamed = Instant::now();
+    options.reporter.emit(
+        Event::new("done")
+            .str("archive", &zipfile.to_string_lossy())
+            .str("target", &target_path.to_string_lossy())
+            .num("entries", entries as u64)
+            .num("bytes", written)
+            .bool("ok", true),
+    );
+    summary!(
+        options.reporter,
+        "Extracted {} entries ({}) to {}",
+        entries,
+        format_size(written),
+        target_path.display()
+    );
+
+    if let Some(name_map) = name_map {
+        name_map
+            .write_entries(target_path, &name_map_entries)
+            .context("Failed to write the name map")?;
+    }
+
+    if let Some(report) = report {
+        let timings = [
+            ("scan", scanned - start),
+            ("extract", extracted - scanned),
+            ("rename", renamed - extracted),
+            ("total", renamed - start),
+        ];
+        report
+            .add(&ArchiveReport {
+                zipfile,
+                target_path,
+                encoding: encoding.name(),
+                inner_root: &inner_root,
+                entries: &name_map_entries,
+                timings: &timings,
+            })
+            .context("Failed to write the report")?;
+    }
+
+    Ok(Extracted {
+        target_path: target_path.to_path_buf(),
+        entries,
+        encoding: Some(encoding.name()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use zip::write::{FileOptions, ZipWriter};
+
+    use super::*;
+
+    // An archive in `dir` with what `build` writes to it.
+    fn zip_file(dir: &Path, build: impl FnOnce(&mut ZipWriter<File>) -> ZipResult<()>) -> PathBuf {
+        let zipfile = dir.join("test.zip");
+        let mut writer = ZipWriter::new(File::create(&zipfile).unwrap());
+        build(&mut writer).unwrap();
+        writer.finish().unwrap();
+        zipfile
+    }
+
+    fn extract_zip(zipfile: &Path, options: ExtractOptions) -> Result<Extracted> {
+        let options = options.destination(zipfile.parent().unwrap());
+        Ok(Extractor::new(options).extract(zipfile)?.unwrap())
+    }
+
+    // A copy of one of test_assets in `dir`, where it is extracted.
+    fn fixture(dir: &Path, name: &str) -> PathBuf {
+        let zipfile = dir.join(name);
+        let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test_assets")
+            .join(name);
+        fs::copy(source, &zipfile).unwrap();
+        zipfile
+    }
+
+    // What is in `dir` besides the archives, with the staging directories.
+    fn extracted_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| !name.ends_with(".zip"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn best_effort() -> ExtractOptions {
+        let mut options = ExtractOptions::default();
+        options.options.best_effort = true;
+        options
+    }
+
+    // Data that does not compress much, so the output limits are reached
+    // while inflating.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 1u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sanitize_path_drops_roots_and_resolves_parents() {
+        let sanitize = |path: &str| sanitize_path(Path::new(path));
+        assert_eq!(sanitize("a/./b"), Some(PathBuf::from("a/b")));
+        assert_eq!(sanitize("/etc/passwd"), Some(PathBuf::from("etc/passwd")));
+        assert_eq!(sanitize("a/../b"), Some(PathBuf::from("b")));
+        assert_eq!(sanitize("a/../../b"), None);
+        assert_eq!(sanitize("../evil.txt"), None);
+        assert_eq!(sanitize("a\0b"), None);
+    }
+
+    #[test]
+    fn is_traversal_of_roots_and_parents_only() {
+        assert!(is_traversal(Path::new("/abs.txt")));
+        assert!(is_traversal(Path::new("a/../b")));
+        assert!(!is_traversal(Path::new("a/./b")));
+        assert!(!is_traversal(Path::new("a..b/c")));
+    }
+
+    #[test]
+    fn resolve_symlink_target_stays_inside() {
+        let resolve =
+            |link: &str, target: &str| resolve_symlink_target(Path::new(link), Path::new(target));
+        assert_eq!(resolve("d/link", "file"), Some(PathBuf::from("d/file")));
+        assert_eq!(resolve("d/e/link", "../f"), Some(PathBuf::from("d/f")));
+        assert_eq!(resolve("link", "./file"), Some(PathBuf::from("file")));
+        assert_eq!(resolve("d/link", "../../file"), None);
+        assert_eq!(resolve("d/link", "/etc/passwd"), None);
+    }
+
+    #[test]
+    fn symlinks_stay_inside() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zipfile = zip_file(temp_dir.path(), |writer| {
+            writer.add_symlink("d/link", "../e", FileOptions::default())?;
+            writer.start_file("e", FileOptions::default())?;
+            writer.add_symlink("outside", "../e", FileOptions::default())
+        });
+        let err = extract_zip(&zipfile, ExtractOptions::default().allow_symlinks(true));
+        assert!(format!("{:#}", err.err().unwrap()).contains("points outside"));
+        let extracted = extract_zip(&zipfile, ExtractOptions::default()).unwrap();
+        // Without --allow-symlinks, they are files holding the target.
+        let link = extracted.target_path.join("d/link");
+        assert_eq!(fs::read_to_string(link).unwrap(), "../e");
+    }
+
+    // The second link is inside the directory lexically, but the first one
+    // puts it in the root, from where it points outside.
+    #[test]
+    fn nothing_is_extracted_through_a_symlink() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zipfile = zip_file(temp_dir.path(), |writer| {
+            writer.add_symlink("d/link", "..", FileOptions::default())?;
+            writer.add_symlink("d/link/escape", "..", FileOptions::default())?;
+            writer.start_file("top.txt", FileOptions::default())
+        });
+        let err = extract_zip(&zipfile, ExtractOptions::default().allow_symlinks(true));
+        assert!(format!("{:#}", err.err().unwrap()).contains("inside the symlink d/link"));
+        assert!(!temp_dir.path().join("test").exists());
+        assert!(!temp_dir.path().join("escape").exists());
+    }
+
+    // The first byte of the deflated data of the first entry becomes a
+    // block of the reserved type, which no decoder reads past.
+    fn garble_first_entry(zipfile: &Path) {
+        let mut data = fs::read(zipfile).unwrap();
+        let name_len = u16::from_le_bytes([data[26], data[27]]) as usize;
+        let extra_len = u16::from_le_bytes([data[28], data[29]]) as usize;
+        data[30 + name_len + extra_len] = 0x07;
+        fs::write(zipfile, data).unwrap();
+    }
+
+    #[test]
+    fn best_effort_keeps_damaged_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zipfile = zip_file(temp_dir.path(), |writer| {
+            writer.start_file("damaged.txt", FileOptions::default())?;
+            writer.write_all(&noise(4096))?;
+            writer.start_file("intact.txt", FileOptions::default())?;
+            writer.write_all(b"intact").map_err(Into::into)
+        });
+        garble_first_entry(&zipfile);
+        assert!(extract_zip(&zipfile, ExtractOptions::default()).is_err());
+        let extracted = extract_zip(&zipfile, best_effort()).unwrap();
+        let intact = extracted.target_path.join("intact.txt");
+        assert_eq!(fs::read_to_string(intact).unwrap(), "intact");
+    }
+
+    #[test]
+    fn best_effort_keeps_the_output_limit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zipfile = zip_file(temp_dir.path(), |writer| {
+            for name in ["a", "b"] {
+                writer.start_file(name, FileOptions::default())?;
+                writer.write_all(&noise(64 * 1024))?;
+            }
+            Ok(())
+        });
+        let err = extract_zip(&zipfile, best_effort().max_output_size(100 * 1024));
+        assert!(format!("{:#}", err.err().unwrap()).contains("size limit"));
+        assert!(!temp_dir.path().join("test").exists());
+    }
+
+    // Cut the archive before the central directory, leaving only what
+    // salvaging can read.
+    fn cut_central_directory(zipfile: &Path) {
+        let mut data = fs::read(zipfile).unwrap();
+        let start = data.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        data.truncate(start);
+        fs::write(zipfile, data).unwrap();
+    }
+
+    #[test]
+    fn salvage_keeps_the_limits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zipfile = zip_file(temp_dir.path(), |writer| {
+            for name in ["a", "b"] {
+                writer.start_file(name, FileOptions::default())?;
+                writer.write_all(&noise(64 * 1024))?;
+            }
+            Ok(())
+        });
+        cut_central_directory(&zipfile);
+        assert!(extract_zip(&zipfile, ExtractOptions::default()).is_err());
+        let err = extract_zip(&zipfile, best_effort().max_entries(1));
+        assert!(format!("{:#}", err.err().unwrap()).contains("--max-entries 1"));
+        let err = extract_zip(&zipfile, best_effort().max_output_size(100 * 1024));
+        assert!(format!("{:#}", err.err().unwrap()).contains("size limit"));
+        let extracted = extract_zip(&zipfile, best_effort()).unwrap();
+        assert_eq!(extracted.entries, 2);
+    }
+
+    #[test]
+    fn salvage_applies_the_name_policies() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zipfile = zip_file(temp_dir.path(), |writer| {
+            for (name, data) in [("a", "first"), ("a", "last"), ("b\u{7}", "bell")] {
+                writer.start_file(name, FileOptions::default())?;
+                writer.write_all(data.as_bytes())?;
+            }
+            writer.start_file("../evil", FileOptions::default())
+        });
+        cut_central_directory(&zipfile);
+        let mut options = best_effort();
+        options.options.path_traversal = PathTraversalPolicy::Strict;
+        let err = extract_zip(&zipfile, options);
+        assert!(format!("{:#}", err.err().unwrap()).contains("Path traversal"));
+        let mut options = best_effort();
+        options.options.on_duplicate = DuplicatePolicy::Error;
+        let err = extract_zip(&zipfile, options);
+        assert!(format!("{:#}", err.err().unwrap()).contains("Duplicate entry a"));
+
+        let mut options = best_effort();
+        options.options.on_duplicate = DuplicatePolicy::First;
+        let extracted = extract_zip(&zipfile, options).unwrap();
+        let target_path = &extracted.target_path;
+        assert_eq!(fs::read_to_string(target_path.join("a")).unwrap(), "first");
+        assert_eq!(
+            fs::read_to_string(target_path.join("b\\x07")).unwrap(),
+            "bell"
+        );
+        assert!(!temp_dir.path().join("evil").exists());
+    }
+
+    #[test]
+    fn max_entries_counts_every_entry() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zipfile = fixture(temp_dir.path(), "two_directory.zip");
+        let err = extract_zip(&zipfile, ExtractOptions::default().max_entries(2));
+        assert!(format!("{:#}", err.err().unwrap()).contains("3 entries"));
+        assert!(extracted_names(temp_dir.path()).is_empty());
+        let extracted = extract_zip(&zipfile, ExtractOptions::default().max_entries(3)).unwrap();
+        assert_eq!(extracted.entries, 3);
+    }
+
+    #[test]
+    fn output_limits_stop_and_clean_up() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zipfile = zip_file(temp_dir.path(), |writer| {
+            writer.start_file("zeros", FileOptions::default())?;
+            writer.write_all(&vec![0; 4 * 1024 * 1024])?;
+            writer.start_file("noise", FileOptions::default())?;
+            writer.write_all(&noise(64 * 1024)).map_err(Into::into)
+        });
+        let mut options = ExtractOptions::default();
+        options.options.max_compression_ratio = Some(100.0);
+        let err = extract_zip(&zipfile, options);
+        assert!(format!("{:#}", err.err().unwrap()).contains("size limit"));
+        assert!(extracted_names(temp_dir.path()).is_empty());
+
+        // The total counts, not each entry on its own.
+        let total = 4 * 1024 * 1024 + 64 * 1024;
+        let err = extract_zip(
+            &zipfile,
+            ExtractOptions::default().max_output_size(total - 1),
+        );
+        assert!(format!("{:#}", err.err().unwrap()).contains("size limit"));
+        assert!(extracted_names(temp_dir.path()).is_empty());
+        extract_zip(&zipfile, ExtractOptions::default().max_output_size(total)).unwrap();
+        assert_eq!(extracted_names(temp_dir.path()), ["test"]);
+    }
+
+    // The warnings and skipped entries of an extraction.
+    #[derive(Default)]
+    struct Recorder(std::sync::Mutex<Vec<String>>);
+
+    impl ExtractObserver for Recorder {
+        fn entry_skipped(&self, name: &Path, reason: &str) {
+            let line = format!("skip {} ({})", name.display(), reason);
+            self.0.lock().unwrap().push(line);
+        }
+
+        fn warning(&self, message: &str) {
+            self.0.lock().unwrap().push(message.to_owned());
+        }
+    }
+
+    fn extract_recorded(zipfile: &Path, policy: PathTraversalPolicy) -> Result<Vec<String>> {
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let mut options = ExtractOptions::default().observer(recorder.clone());
+        options.options.path_traversal = policy;
+        extract_zip(zipfile, options)?;
+        let lines = recorder.0.lock().unwrap().clone();
+        Ok(lines)
+    }
+
+    #[test]
+    fn path_traversal_policies() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zipfile = zip_file(temp_dir.path(), |writer| {
+            for name in ["/abs.txt", "d/../e.txt", "../evil.txt"] {
+                writer.start_file(name, FileOptions::default())?;
+            }
+            Ok(())
+        });
+        let err = extract_recorded(&zipfile, PathTraversalPolicy::Strict);
+        assert!(
+            format!("{:#}", err.err().unwrap()).contains("Path traversal in entry name /abs.txt")
+        );
+        assert!(extracted_names(temp_dir.path()).is_empty());
+
+        let lines = extract_recorded(&zipfile, PathTraversalPolicy::Warn).unwrap();
+        let warnings: Vec<_> = lines
+            .iter()
+            .filter(|line| line.starts_with("Warning"))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "Warning: Rewrote /abs.txt -> abs.txt",
+                "Warning: Rewrote d/../e.txt -> e.txt",
+                "Warning: Skipped ../evil.txt, which is outside of the target directory",
+            ]
+        );
+        let target_path = temp_dir.path().join("test");
+        assert!(target_path.join("abs.txt").is_file());
+        assert!(target_path.join("e.txt").is_file());
+        assert!(!temp_dir.path().join("evil.txt").exists());
+        fs::remove_dir_all(target_path).unwrap();
+
+        let lines = extract_recorded(&zipfile, PathTraversalPolicy::Sanitize).unwrap();
+        assert_eq!(lines, ["skip ../evil.txt (traversal)"]);
+    }
+
+    // Names which only look like traversal are left alone.
+    #[test]
+    fn strict_path_traversal_accepts_plain_names() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for name in ["one_directory.zip", "two_directory.zip", "one_file.zip"] {
+            let zipfile = fixture(temp_dir.path(), name);
+            extract_recorded(&zipfile, PathTraversalPolicy::Strict).unwrap();
+        }
+        assert_eq!(
+            extracted_names(temp_dir.path()),
+            ["one_directory", "one_file", "two_directory"]
+        );
+        assert!(temp_dir.path().join("one_directory/b").is_file());
+        assert!(temp_dir.path().join("two_directory/a/x").is_file());
+    }
+}
@@ -0,0 +1,163 @@
+use std::cell::RefCell;
+use std::ffi::{c_int, c_void, CStr};
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::FileExt as _;
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use zip::read::ZipFile;
+use zip::CompressionMethod;
+
+use crate::checksum::{Algorithm, Hasher};
+use crate::interrupt::CancellationToken;
+use crate::output::Reporter;
+use crate::progress;
+use crate::throttle::throttle;
+use crate::CopyOutcome;
+
+// Entries are inflated in one call from and into memory, so larger ones are
+// streamed as usual.
+const MAX_ENTRY_SIZE: u64 = 64 * 1024 * 1024;
+
+const LIBDEFLATE_SUCCESS: c_int = 0;
+
+type AllocDecompressor = unsafe extern "C" fn() -> *mut c_void;
+type FreeDecompressor = unsafe extern "C" fn(*mut c_void);
+type DeflateDecompress = unsafe extern "C" fn(
+    *mut c_void,
+    *const c_void,
+    usize,
+    *mut c_void,
+    usize,
+    *mut usize,
+) -> c_int;
+
+// libdeflate, loaded at run time so that the build does not depend on it.
+struct Library {
+    alloc_decompressor: AllocDecompressor,
+    free_decompressor: FreeDecompressor,
+    deflate_decompress: DeflateDecompress,
+}
+
+fn load() -> Option<Library> {
+    let names: [&CStr; 2] = [c"libdeflate.so.0", c"libdeflate.so"];
+    let handle = names.iter().find_map(|name| {
+        let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        (!handle.is_null()).then_some(handle)
+    })?;
+    let symbol = |name: &CStr| {
+        let symbol = unsafe { libc::dlsym(handle, name.as_ptr()) };
+        (!symbol.is_null()).then_some(symbol)
+    };
+    unsafe {
+        Some(Library {
+            alloc_decompressor: std::mem::transmute::<*mut c_void, AllocDecompressor>(symbol(
+                c"libdeflate_alloc_decompressor",
+            )?),
+            free_decompressor: std::mem::transmute::<*mut c_void, FreeDecompressor>(symbol(
+                c"libdeflate_free_decompressor",
+            )?),
+            deflate_decompress: std::mem::transmute::<*mut c_void, DeflateDecompress>(symbol(
+                c"libdeflate_deflate_decompress",
+            )?),
+        })
+    }
+}
+
+fn library() -> Option<&'static Library> {
+    static LIBRARY: OnceLock<Option<Library>> = OnceLock::new();
+    LIBRARY.get_or_init(load).as_ref()
+}
+
+pub fn is_available() -> bool {
+    library().is_some()
+}
+
+struct Decompressor(*mut c_void);
+
+impl Drop for Decompressor {
+    fn drop(&mut self) {
+        if let Some(library) = library() {
+            unsafe { (library.free_decompressor)(self.0) };
+        }
+    }
+}
+
+thread_local! {
+    static DECOMPRESSOR: RefCell<Option<Decompressor>> = const { RefCell::new(None) };
+}
+
+// Inflate a whole deflated entry with libdeflate (--inflate=libdeflate).
+// Returns None to fall back to the streaming decoder: for other methods,
+// large entries, and data libdeflate rejects, whose error the streaming
+// decoder then reports as usual.
+pub fn inflate_entry<W: Write + ?Sized>(
+    file: &ZipFile,
+    archive_file: &File,
+    outfile: &mut W,
+    limit: u64,
+    digest: Option<Algorithm>,
+    cancel: &CancellationToken,
+    reporter: &Reporter,
+) -> Result<Option<CopyOutcome>> {
+    let Some(library) = library() else {
+        return Ok(None);
+    };
+    if file.compression() != CompressionMethod::Deflated || file.size() > MAX_ENTRY_SIZE {
+        return Ok(None);
+    }
+    let start = file.data_start();
+    if start + file.compressed_size() > archive_file.metadata()?.len() {
+        return Ok(None);
+    }
+    if file.size() > limit {
+        bail!("Exceeded the extraction size limit of {} bytes", limit);
+    }
+
+    let mut input = vec![0u8; file.compressed_size() as usize];
+    archive_file.read_exact_at(&mut input, start)?;
+    let mut output = vec![0u8; file.size() as usize];
+    let mut length = 0usize;
+    let result = DECOMPRESSOR.with_borrow_mut(|decompressor| {
+        if decompressor.is_none() {
+            let pointer = unsafe { (library.alloc_decompressor)() };
+            if pointer.is_null() {
+                return None;
+            }
+            *decompressor = Some(Decompressor(pointer));
+        }
+        let decompressor = decompressor.as_ref().unwrap();
+        Some(unsafe {
+            (library.deflate_decompress)(
+                decompressor.0,
+                input.as_ptr().cast(),
+                input.len(),
+                output.as_mut_ptr().cast(),
+                output.len(),
+                &mut length,
+            )
+        })
+    });
+    if result != Some(LIBDEFLATE_SUCCESS) {
+        return Ok(None);
+    }
+
+    let output = &output[..length];
+    throttle(output.len(), cancel);
+    progress::advance(output.len());
+    reporter.bytes_written(output.len());
+    outfile.write_all(output)?;
+    if cancel.is_cancelled() {
+        bail!("Interrupted");
+    }
+    Ok(Some(CopyOutcome {
+        written: output.len() as u64,
+        crc32: crc32fast::hash(output),
+        digest: digest.map(|algorithm| {
+            let mut hasher = Hasher::new(algorithm);
+            hasher.update(output);
+            hasher.finalize()
+        }),
+    }))
+}
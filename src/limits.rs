@@ -0,0 +1,88 @@
+use anyhow::{bail, Result};
+
+// The compression ratio limit only applies once an entry has produced this
+// much output, so that small, highly compressible files are not rejected.
+const RATIO_CHECK_THRESHOLD: u64 = 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    pub max_output_size: Option<u64>,
+    pub max_compression_ratio: Option<f64>,
+    pub max_entries: Option<usize>,
+}
+
+impl Limits {
+    pub fn check_entries(&self, entries: usize) -> Result<()> {
+        if let Some(max_entries) = self.max_entries {
+            if entries > max_entries {
+                bail!(
+                    "The archive has {} entries, more than --max-entries {}",
+                    entries,
+                    max_entries
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // The number of bytes the next entry may produce.
+    pub fn entry_limit(&self, written_total: u64, compressed_size: u64) -> u64 {
+        let remaining = match self.max_output_size {
+            Some(max_output_size) => max_output_size.saturating_sub(written_total),
+            None => u64::MAX,
+        };
+        let by_ratio = match self.max_compression_ratio {
+            Some(ratio) => {
+                let allowed = (compressed_size.max(1) as f64 * ratio) as u64;
+                allowed.max(RATIO_CHECK_THRESHOLD)
+            }
+            None => u64::MAX,
+        };
+        remaining.min(by_ratio)
+    }
+}
+
+// Parse a byte size such as "4096", "500M" or "10GiB" (binary units).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, unit) = s.split_at(digits_end);
+    let value: u64 = digits.parse().map_err(|_| format!("invalid size: {}", s))?;
+    let shift = match unit
+        .to_ascii_lowercase()
+        .trim_end_matches("ib")
+        .trim_end_matches('b')
+    {
+        "" => 0,
+        "k" => 10,
+        "m" => 20,
+        "g" => 30,
+        "t" => 40,
+        _ => return Err(format!("invalid size unit: {}", unit)),
+    };
+    value
+        .checked_mul(1u64 << shift)
+        .ok_or_else(|| format!("size too large: {}", s))
+}
+
+pub fn parse_buffer_size(s: &str) -> Result<usize, String> {
+    match parse_size(s)? {
+        0 => Err("buffer size must not be zero".to_string()),
+        size => usize::try_from(size).map_err(|_| format!("size too large: {}", s)),
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", size)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
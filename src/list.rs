@@ -0,0 +1,163 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+
+use crate::cli::{Args, ListFormat, ListSort};
+use crate::control_chars::display_path;
+use crate::encoding::get_encoding;
+use crate::ignore::matches_pattern;
+use crate::json;
+use crate::limits::format_size;
+use crate::name_index::{decode_names, read_raw_names};
+use crate::output::{outln, Reporter};
+use crate::zip_ext::ZipFileExt;
+use crate::{detect_filename_encoding, open_archive};
+
+struct Entry {
+    name: PathBuf,
+    is_dir: bool,
+    size: u64,
+    compressed_size: u64,
+    method: String,
+    modified: Option<DateTime<Local>>,
+}
+
+// List the entries of an archive with their names decoded as extraction
+// would, limited to those matching any of `patterns` if given.
+pub fn list_archive(
+    zipfile: &Path,
+    patterns: &[String],
+    format: ListFormat,
+    sort: ListSort,
+    reverse: bool,
+    args: &Args,
+) -> Result<()> {
+    let mut archive = open_archive(zipfile, &Reporter::TERMINAL, !args.no_mmap)?;
+    let raw_names = read_raw_names(&mut archive)?;
+    let encoding = match args.oenc.as_deref() {
+        Some(encoding_name) => get_encoding(encoding_name).unwrap(),
+        None => detect_filename_encoding(&raw_names),
+    };
+    let names = decode_names(&raw_names, encoding);
+
+    let mut entries = Vec::new();
+    for (i, name) in names.into_iter().enumerate() {
+        if !patterns.is_empty()
+            && !patterns
+                .iter()
+                .any(|pattern| matches_pattern(pattern, &name.decoded))
+        {
+            continue;
+        }
+        let file = archive.by_index_raw(i)?;
+        entries.push(Entry {
+            name: name.decoded,
+            is_dir: name.is_dir,
+            size: file.size(),
+            compressed_size: file.compressed_size(),
+            method: format!("{:?}", file.compression()).to_lowercase(),
+            modified: file
+                .entry_times(args.mtime_timezone)
+                .modified
+                .map(DateTime::from),
+        });
+    }
+
+    match sort {
+        ListSort::Archive => {}
+        ListSort::Name => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+        ListSort::Size => entries.sort_by_key(|entry| entry.size),
+        ListSort::Time => entries.sort_by_key(|entry| entry.modified),
+    }
+    if reverse {
+        entries.reverse();
+    }
+
+    match format {
+        ListFormat::Table => print_table(&entries),
+        ListFormat::Json => print_json(&entries),
+        ListFormat::Csv => print_csv(&entries),
+    }
+    Ok(())
+}
+
+fn print_table(entries: &[Entry]) {
+    outln!(
+        "{:>10}  {:>10}  {:<9} {:<16}  Name",
+        "Size",
+        "Compressed",
+        "Method",
+        "Modified"
+    );
+    for entry in entries {
+        let modified = entry
+            .modified
+            .map(|modified| modified.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        outln!(
+            "{:>10}  {:>10}  {:<9} {:<16}  {}",
+            format_size(entry.size),
+            format_size(entry.compressed_size),
+            entry.method,
+            modified,
+            display_path(&entry.name)
+        );
+    }
+    let size = entries.iter().map(|entry| entry.size).sum();
+    let compressed_size = entries.iter().map(|entry| entry.compressed_size).sum();
+    outln!(
+        "{:>10}  {:>10}  {:<9} {:<16}  {} entries",
+        format_size(size),
+        format_size(compressed_size),
+        "",
+        "",
+        entries.len()
+    );
+}
+
+fn print_json(entries: &[Entry]) {
+    outln!("[");
+    for (i, entry) in entries.iter().enumerate() {
+        let mut object = json::Object::new()
+            .str("name", &entry.name.to_string_lossy())
+            .bool("dir", entry.is_dir)
+            .num("size", entry.size)
+            .num("compressed_size", entry.compressed_size)
+            .str("method", &entry.method);
+        if let Some(modified) = entry.modified {
+            object = object.str("modified", &modified.to_rfc3339());
+        }
+        let separator = if i + 1 < entries.len() { "," } else { "" };
+        outln!("{}{}", object.finish(), separator);
+    }
+    outln!("]");
+}
+
+// Quoted only when needed, as in RFC 4180.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn print_csv(entries: &[Entry]) {
+    outln!("name,dir,size,compressed_size,method,modified");
+    for entry in entries {
+        let modified = entry
+            .modified
+            .map(|modified| modified.to_rfc3339())
+            .unwrap_or_default();
+        outln!(
+            "{},{},{},{},{},{}",
+            csv_field(&entry.name.to_string_lossy()),
+            entry.is_dir,
+            entry.size,
+            entry.compressed_size,
+            entry.method,
+            modified
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+// Linux limits in bytes. PATH_MAX includes the terminating NUL.
+pub const NAME_MAX: usize = 255;
+pub const PATH_MAX: usize = 4096;
+
+pub fn has_long_component(path: &Path) -> bool {
+    path.iter().any(|name| name.len() > NAME_MAX)
+}
+
+// Whether `path` joined to a base directory of `base_len` bytes exceeds PATH_MAX.
+pub fn is_path_too_long(base_len: usize, path: &Path) -> bool {
+    base_len + 1 + path.as_os_str().len() + 1 > PATH_MAX
+}
+
+// Shorten components longer than NAME_MAX to "<prefix>~<crc32>.<ext>".
+// The hash of the original name keeps the result deterministic and unique.
+pub fn truncate_path(path: &Path) -> PathBuf {
+    path.iter().map(truncate_name).collect()
+}
+
+fn truncate_name(name: &OsStr) -> PathBuf {
+    if name.len() <= NAME_MAX {
+        return PathBuf::from(name);
+    }
+    let name_str = name.to_string_lossy();
+    let (stem, extension) = match name_str.rfind('.') {
+        // Keep reasonable extensions only
+        Some(pos) if pos > 0 && name_str.len() - pos <= 16 => name_str.split_at(pos),
+        _ => (name_str.as_ref(), ""),
+    };
+    let suffix = format!("~{:08x}{}", crc32fast::hash(name.as_bytes()), extension);
+    let mut stem_len = NAME_MAX - suffix.len();
+    while !stem.is_char_boundary(stem_len) {
+        stem_len -= 1;
+    }
+    PathBuf::from(format!("{}{}", &stem[..stem_len], suffix))
+}
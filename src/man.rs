@@ -0,0 +1,188 @@
+use clap::{Arg, Command};
+
+use crate::cli::EXIT_STATUS_HELP;
+use crate::output::outln;
+
+// Escape text for roff: backslashes and hyphens, and a leading dot or quote
+// which would start a request.
+fn roff(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with(['.', '\'']) {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+fn usage(command: &mut Command) -> Vec<String> {
+    command
+        .render_usage()
+        .to_string()
+        .trim_start_matches("Usage:")
+        .lines()
+        .map(|line| roff(line.trim()))
+        .collect()
+}
+
+fn option(page: &mut Vec<String>, arg: &Arg) {
+    let mut names = Vec::new();
+    if let Some(short) = arg.get_short() {
+        names.push(format!("\\fB\\-{}\\fR", short));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(format!("\\fB\\-\\-{}\\fR", roff(long)));
+    }
+    if names.is_empty() {
+        names.push(format!(
+            "\\fI{}\\fR",
+            roff(&arg.get_id().as_str().to_uppercase())
+        ));
+    } else if arg.get_action().takes_values() {
+        let value = arg
+            .get_value_names()
+            .and_then(|names| names.first())
+            .map_or_else(
+                || arg.get_id().as_str().to_uppercase(),
+                |name| name.to_string(),
+            );
+        let last = names.pop().unwrap();
+        names.push(format!("{} \\fI{}\\fR", last, roff(&value)));
+    }
+    page.push(".TP".to_string());
+    page.push(names.join(", "));
+
+    let mut help = arg
+        .get_help()
+        .map(|help| roff(&help.to_string()))
+        .unwrap_or_default();
+    let possible_values: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| match value.get_help() {
+            Some(value_help) => format!("{} ({})", value.get_name(), value_help),
+            None => value.get_name().to_string(),
+        })
+        .collect();
+    if arg.get_action().takes_values() && !possible_values.is_empty() {
+        help.push_str(&format!(
+            "\n.br\nValues: {}.",
+            roff(&possible_values.join(", "))
+        ));
+    }
+    let defaults: Vec<String> = arg
+        .get_default_values()
+        .iter()
+        .map(|value| value.to_string_lossy().into_owned())
+        .collect();
+    if arg.get_action().takes_values() && !defaults.is_empty() && !arg.is_positional() {
+        help.push_str(&format!("\n.br\nDefault: {}.", roff(&defaults.join(", "))));
+    }
+    if let Some(env) = arg.get_env() {
+        help.push_str(&format!(
+            "\n.br\nEnvironment: \\fB{}\\fR.",
+            roff(&env.to_string_lossy())
+        ));
+    }
+    page.push(help);
+}
+
+fn options(page: &mut Vec<String>, command: &Command, include_global: bool) {
+    for arg in command.get_arguments() {
+        if arg.is_hide_set()
+            || arg.get_id() == "help"
+            || arg.get_id() == "version"
+            || (arg.is_global_set() && !include_global)
+            // The synopsis names them well enough.
+            || (arg.is_positional() && arg.get_help().is_none())
+        {
+            continue;
+        }
+        option(page, arg);
+    }
+}
+
+// Print a man page in roff, from the same definitions as --help.
+pub fn print_man_page(mut command: Command) {
+    command.build();
+    let name = command.get_name().to_string();
+    let version = command.get_version().unwrap_or_default().to_string();
+    let about = command
+        .get_about()
+        .map(|about| about.to_string())
+        .unwrap_or_default();
+
+    let mut page = vec![
+        format!(
+            ".TH {} 1 \"\" \"{} {}\" \"User Commands\"",
+            name.to_uppercase(),
+            name,
+            version
+        ),
+        ".SH NAME".to_string(),
+        format!("{} \\- {}", name, roff(&about)),
+        ".SH SYNOPSIS".to_string(),
+    ];
+    page.push(usage(&mut command).join("\n.br\n"));
+    page.push(".SH OPTIONS".to_string());
+    options(&mut page, &command, true);
+
+    page.push(".SH COMMANDS".to_string());
+    let mut subcommands: Vec<Command> = command
+        .get_subcommands()
+        .filter(|subcommand| subcommand.get_name() != "help")
+        .cloned()
+        .collect();
+    for subcommand in &mut subcommands {
+        page.push(format!(".SS \"{}\"", usage(subcommand).join(" ")));
+        if let Some(about) = subcommand.get_about() {
+            page.push(roff(&about.to_string()));
+        }
+        if subcommand.get_name() == "extract" {
+            page.push(format!(
+                "Takes the options of \\fB{}\\fR without a subcommand.",
+                name
+            ));
+            continue;
+        }
+        options(&mut page, subcommand, false);
+    }
+
+    page.push(".SH EXIT STATUS".to_string());
+    for line in EXIT_STATUS_HELP.lines().skip(1) {
+        if let Some((status, meaning)) = line.trim().split_once(' ') {
+            page.push(".TP".to_string());
+            page.push(status.to_string());
+            page.push(roff(meaning.trim()));
+        }
+    }
+
+    page.push(".SH ENVIRONMENT".to_string());
+    for (variable, meaning) in [
+        (
+            "NO_COLOR",
+            "Disables colors with \\-\\-color=auto when set and not empty.",
+        ),
+        (
+            "LC_ALL, LC_MESSAGES, LANG",
+            "Messages are in Japanese for a ja locale and in English otherwise.",
+        ),
+    ] {
+        page.push(".TP".to_string());
+        page.push(format!("\\fB{}\\fR", variable));
+        page.push(meaning.to_string());
+    }
+    page.push(".SH FILES".to_string());
+    page.push(".TP".to_string());
+    page.push("\\fI$XDG_CONFIG_HOME/exzip/config.toml\\fR".to_string());
+    page.push(
+        "Defaults for options: \\fBencoding\\fR, \\fBignore_patterns\\fR, \\fBon_conflict\\fR, \\fBthreads\\fR and \\fBprogress\\fR. \\fI~/.config\\fR is used if XDG_CONFIG_HOME is not set; options on the command line take precedence, and \\fB\\-\\-no\\-config\\fR ignores the file."
+            .to_string(),
+    );
+    page.push(
+        ".br\nArchives of other formats are passed to external commands set up as \\fBbackend.\\fR\\fINAME\\fR\\fB.command\\fR (an array in which {archive} and {dir} are replaced) and \\fBbackend.\\fR\\fINAME\\fR\\fB.extensions\\fR."
+            .to_string(),
+    );
+
+    outln!("{}", page.join("\n"));
+}
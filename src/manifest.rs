@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cap_std::fs::Dir;
+
+use crate::checksum::{to_hex, Algorithm};
+
+// The digests of the extracted files, written to the target directory in
+// the format of sha256sum and md5sum so that `sha256sum -c` checks them.
+pub struct Manifest {
+    pub algorithm: Algorithm,
+    digests: BTreeMap<PathBuf, Vec<u8>>,
+    // Hard links, resolved when written since their targets may still be
+    // in the hands of worker threads.
+    links: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Manifest {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Manifest {
+            algorithm,
+            digests: BTreeMap::new(),
+            links: Vec::new(),
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self.algorithm {
+            Algorithm::Md5 => "MD5SUMS",
+            Algorithm::Sha256 => "SHA256SUMS",
+        }
+    }
+
+    // A later entry of the same path replaces the earlier one.
+    pub fn insert(&mut self, path: &Path, digest: Option<Vec<u8>>) {
+        match digest {
+            Some(digest) => self.digests.insert(path.to_path_buf(), digest),
+            None => self.digests.remove(path),
+        };
+    }
+
+    // `path` is a hard link to `target`, extracted earlier.
+    pub fn link(&mut self, target: &Path, path: &Path) {
+        self.links.push((target.to_path_buf(), path.to_path_buf()));
+    }
+
+    pub fn write(mut self, dst_root: &Dir) -> io::Result<()> {
+        for (target, path) in std::mem::take(&mut self.links) {
+            let digest = self.digests.get(&target).cloned();
+            self.insert(&path, digest);
+        }
+        let name = self.file_name();
+        // An entry of the same name is overwritten by the manifest.
+        self.digests.remove(Path::new(name));
+        let mut content = Vec::new();
+        for (path, digest) in &self.digests {
+            let path = path.as_os_str().as_encoded_bytes();
+            // Like the coreutils tools, a leading backslash marks a name
+            // with a backslash or newline escaped.
+            let escaped = path.contains(&b'\\') || path.contains(&b'\n');
+            if escaped {
+                content.push(b'\\');
+            }
+            content.extend_from_slice(to_hex(digest).as_bytes());
+            content.extend_from_slice(b"  ");
+            for &b in path {
+                match b {
+                    b'\\' if escaped => content.extend_from_slice(b"\\\\"),
+                    b'\n' => content.extend_from_slice(b"\\n"),
+                    _ => content.push(b),
+                }
+            }
+            content.push(b'\n');
+        }
+        dst_root.write(name, content)
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::ptr::{self, NonNull};
+
+use rustix::mm::{mmap, munmap, MapFlags, ProtFlags};
+
+use crate::remote::RemoteFile;
+
+// Larger archives are read through a buffer on 32-bit targets, where the
+// address space is scarce.
+const MAX_MAPPED_SIZE_32BIT: u64 = 256 * 1024 * 1024;
+
+// A read-only private mapping of a whole file. Like every mmap reader, a
+// process truncating the file meanwhile would make reads fault.
+pub struct Mmap {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// The mapping is read-only, so it can be shared and moved freely.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    pub fn map(file: &File) -> io::Result<Option<Self>> {
+        let len = file.metadata()?.len();
+        if len == 0 || (cfg!(target_pointer_width = "32") && len > MAX_MAPPED_SIZE_32BIT) {
+            return Ok(None);
+        }
+        let len = usize::try_from(len).map_err(|_| io::Error::from(io::ErrorKind::OutOfMemory))?;
+        let ptr = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                ProtFlags::READ,
+                MapFlags::PRIVATE,
+                file,
+                0,
+            )?
+        };
+        Ok(NonNull::new(ptr.cast()).map(|ptr| Mmap { ptr, len }))
+    }
+}
+
+impl AsRef<[u8]> for Mmap {
+    fn as_ref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = munmap(self.ptr.as_ptr().cast(), self.len);
+        }
+    }
+}
+
+// Reads an archive from a mapping when possible, avoiding a seek and read
+// system call per entry, or through a buffer otherwise. Archives given as
+// URLs with --remote-seek are read with range requests.
+pub enum ArchiveReader {
+    Mapped(Cursor<Mmap>),
+    Buffered(BufReader<File>),
+    Remote(RemoteFile),
+}
+
+impl ArchiveReader {
+    pub fn open(file: File, use_mmap: bool) -> io::Result<Self> {
+        if use_mmap {
+            // Fall back to reading when the file cannot be mapped.
+            if let Ok(Some(mapping)) = Mmap::map(&file) {
+                return Ok(ArchiveReader::Mapped(Cursor::new(mapping)));
+            }
+        }
+        Ok(ArchiveReader::Buffered(BufReader::new(file)))
+    }
+}
+
+impl Read for ArchiveReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ArchiveReader::Mapped(cursor) => cursor.read(buf),
+            ArchiveReader::Buffered(reader) => reader.read(buf),
+            ArchiveReader::Remote(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl Seek for ArchiveReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            ArchiveReader::Mapped(cursor) => cursor.seek(pos),
+            ArchiveReader::Buffered(reader) => reader.seek(pos),
+            ArchiveReader::Remote(reader) => reader.seek(pos),
+        }
+    }
+}
@@ -0,0 +1,723 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write as _};
+use std::mem;
+use std::os::fd::{AsRawFd as _, FromRawFd as _, OwnedFd};
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::fs::{FileExt as _, OpenOptionsExt as _};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context as _, Result};
+use flate2::read::DeflateDecoder;
+use zip::CompressionMethod;
+
+use crate::cli::Args;
+use crate::control_chars::display_path;
+use crate::encoding::get_encoding;
+use crate::ignore::IgnoreRules;
+use crate::interrupt::interrupted;
+use crate::name_index::{decode_names, read_raw_names};
+use crate::output::{notice, say, Reporter};
+use crate::zip_ext::{DosTimezone, ZipFileExt as _};
+use crate::{detect_filename_encoding, get_inner_root, open_archive, Archive};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// Nothing changes under a read-only mount, so the kernel may cache for long.
+const VALID_SECONDS: u64 = 3600;
+const MAX_READ: usize = 128 * 1024;
+
+const ROOT_ID: u64 = 1;
+
+// Opcodes and structures of the FUSE kernel protocol, version 7.
+const FUSE_LOOKUP: u32 = 1;
+const FUSE_FORGET: u32 = 2;
+const FUSE_GETATTR: u32 = 3;
+const FUSE_OPEN: u32 = 14;
+const FUSE_READ: u32 = 15;
+const FUSE_STATFS: u32 = 17;
+const FUSE_RELEASE: u32 = 18;
+const FUSE_FLUSH: u32 = 25;
+const FUSE_INIT: u32 = 26;
+const FUSE_OPENDIR: u32 = 27;
+const FUSE_READDIR: u32 = 28;
+const FUSE_RELEASEDIR: u32 = 29;
+const FUSE_ACCESS: u32 = 34;
+const FUSE_INTERRUPT: u32 = 36;
+const FUSE_DESTROY: u32 = 38;
+const FUSE_BATCH_FORGET: u32 = 42;
+
+const IN_HEADER_SIZE: usize = 40;
+const FOPEN_KEEP_CACHE: u32 = 1 << 1;
+const PROTOCOL_MINOR: u32 = 31;
+
+enum Content {
+    Dir(BTreeMap<OsString, u64>),
+    File {
+        index: usize,
+        size: u64,
+        data_start: u64,
+        compressed_size: u64,
+        compression: CompressionMethod,
+    },
+}
+
+struct Node {
+    parent: u64,
+    content: Content,
+    mode: u32,
+    mtime: Option<SystemTime>,
+}
+
+// The tree extraction would create: names decoded and sanitized, the
+// common root stripped, and junk, links and special files left out.
+fn build_tree(archive: &mut Archive, args: &Args) -> Result<Vec<Node>> {
+    let raw_names = read_raw_names(archive)?;
+    let encoding = match args.oenc.as_deref() {
+        Some(encoding_name) => get_encoding(encoding_name).unwrap(),
+        None => detect_filename_encoding(&raw_names),
+    };
+    let names = decode_names(&raw_names, encoding);
+    let ignore_rules = IgnoreRules::new(args.no_ignore, args.keep_macosx, &args.ignore_pattern);
+    let inner_root = get_inner_root(&names, &ignore_rules)?;
+
+    // Inode numbers are indices plus one, so the root is 1.
+    let mut nodes = vec![Node {
+        parent: ROOT_ID,
+        content: Content::Dir(BTreeMap::new()),
+        mode: 0o555,
+        mtime: None,
+    }];
+    for (i, name) in names.iter().enumerate() {
+        let Some(path) = name.path()? else {
+            continue;
+        };
+        if ignore_rules.is_ignored(path) {
+            continue;
+        }
+        let path = path.strip_prefix(&inner_root).unwrap_or(path);
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let file = archive.by_index_raw(i)?;
+        if file.is_symlink() || file.special_kind().is_some() {
+            say!("Skip {}", display_path(path));
+            continue;
+        }
+        let mode = file
+            .unix_mode()
+            .map_or(if name.is_dir { 0o555 } else { 0o444 }, |mode| mode & 0o555);
+        let mtime = file.entry_times(DosTimezone::Local).modified;
+
+        let mut parent = ROOT_ID;
+        let mut components = path.iter().peekable();
+        while let Some(component) = components.next() {
+            let is_last = components.peek().is_none();
+            let Content::Dir(children) = &nodes[parent as usize - 1].content else {
+                // A file of the same name is in the way.
+                break;
+            };
+            if let Some(&child) = children.get(component) {
+                if is_last {
+                    // A duplicate; the first one is kept.
+                    if let Content::Dir(_) = nodes[child as usize - 1].content {
+                        nodes[child as usize - 1].mode = mode;
+                        nodes[child as usize - 1].mtime = mtime;
+                    }
+                }
+                parent = child;
+                continue;
+            }
+            let content = if is_last && !name.is_dir {
+                Content::File {
+                    index: i,
+                    size: file.size(),
+                    data_start: file.data_start(),
+                    compressed_size: file.compressed_size(),
+                    compression: file.compression(),
+                }
+            } else {
+                Content::Dir(BTreeMap::new())
+            };
+            let (mode, mtime) = if is_last {
+                (mode, mtime)
+            } else {
+                (0o555, None)
+            };
+            nodes.push(Node {
+                parent,
+                content,
+                mode,
+                mtime,
+            });
+            let child = nodes.len() as u64;
+            if let Content::Dir(children) = &mut nodes[parent as usize - 1].content {
+                children.insert(component.to_os_string(), child);
+            }
+            parent = child;
+        }
+    }
+    Ok(nodes)
+}
+
+// Reads the archive file at a position of its own, so that open files do
+// not disturb each other.
+struct ArchiveSlice {
+    file: Arc<File>,
+    pos: u64,
+    end: u64,
+}
+
+impl Read for ArchiveSlice {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min((self.end - self.pos) as usize);
+        let len = self.file.read_at(&mut buf[..len], self.pos)?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+// An open file. Deflated data can only be read from the start, so reading
+// backwards starts over; sequential reads continue where the last ended.
+enum Handle {
+    Stored {
+        start: u64,
+        size: u64,
+    },
+    Deflated {
+        decoder: Box<DeflateDecoder<ArchiveSlice>>,
+        pos: u64,
+        size: u64,
+        data_start: u64,
+        compressed_size: u64,
+    },
+    // Other methods are decompressed whole when opened.
+    Buffered(Vec<u8>),
+}
+
+struct Filesystem {
+    nodes: Vec<Node>,
+    archive: Archive,
+    archive_file: Arc<File>,
+    handles: HashMap<u64, Handle>,
+    next_handle: u64,
+    uid: u32,
+    gid: u32,
+}
+
+fn deflate_decoder(
+    file: &Arc<File>,
+    data_start: u64,
+    compressed_size: u64,
+) -> Box<DeflateDecoder<ArchiveSlice>> {
+    Box::new(DeflateDecoder::new(ArchiveSlice {
+        file: Arc::clone(file),
+        pos: data_start,
+        end: data_start + compressed_size,
+    }))
+}
+
+// Builds a reply in the layout of the kernel's structures.
+#[derive(Default)]
+struct Reply(Vec<u8>);
+
+impl Reply {
+    fn u16(mut self, value: u16) -> Self {
+        self.0.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    fn u32(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    fn u64(mut self, value: u64) -> Self {
+        self.0.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    fn zeros(mut self, len: usize) -> Self {
+        self.0.resize(self.0.len() + len, 0);
+        self
+    }
+}
+
+fn field_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn field_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_ne_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+impl Filesystem {
+    fn node(&self, id: u64) -> Option<&Node> {
+        id.checked_sub(1)
+            .and_then(|index| self.nodes.get(index as usize))
+    }
+
+    // struct fuse_attr
+    fn attr(&self, reply: Reply, id: u64) -> Reply {
+        let node = self.node(id).unwrap();
+        let (size, kind, nlink) = match node.content {
+            Content::Dir(_) => (0, libc::S_IFDIR, 2),
+            Content::File { size, .. } => (size, libc::S_IFREG, 1),
+        };
+        let mtime = node
+            .mtime
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        reply
+            .u64(id)
+            .u64(size)
+            .u64(size.div_ceil(512))
+            .u64(mtime.as_secs())
+            .u64(mtime.as_secs())
+            .u64(mtime.as_secs())
+            .u32(mtime.subsec_nanos())
+            .u32(mtime.subsec_nanos())
+            .u32(mtime.subsec_nanos())
+            .u32(kind | node.mode)
+            .u32(nlink)
+            .u32(self.uid)
+            .u32(self.gid)
+            .u32(0)
+            .u32(4096)
+            .u32(0)
+    }
+
+    // struct fuse_entry_out
+    fn entry(&self, id: u64) -> Reply {
+        let reply = Reply::default()
+            .u64(id)
+            .u64(0)
+            .u64(VALID_SECONDS)
+            .u64(VALID_SECONDS)
+            .u32(0)
+            .u32(0);
+        self.attr(reply, id)
+    }
+
+    fn lookup(&self, parent: u64, name: &OsStr) -> Result<Reply, i32> {
+        match self.node(parent).map(|node| &node.content) {
+            Some(Content::Dir(children)) => children
+                .get(name)
+                .map(|&child| self.entry(child))
+                .ok_or(libc::ENOENT),
+            Some(Content::File { .. }) => Err(libc::ENOTDIR),
+            None => Err(libc::ENOENT),
+        }
+    }
+
+    fn open(&mut self, id: u64, flags: u32) -> Result<Reply, i32> {
+        if flags & libc::O_ACCMODE as u32 != libc::O_RDONLY as u32 {
+            return Err(libc::EROFS);
+        }
+        let Some(Content::File {
+            index,
+            size,
+            data_start,
+            compressed_size,
+            compression,
+        }) = self.node(id).map(|node| &node.content)
+        else {
+            return Err(libc::EISDIR);
+        };
+        let handle = match compression {
+            CompressionMethod::Stored => Handle::Stored {
+                start: *data_start,
+                size: *size,
+            },
+            CompressionMethod::Deflated => Handle::Deflated {
+                decoder: deflate_decoder(&self.archive_file, *data_start, *compressed_size),
+                pos: 0,
+                size: *size,
+                data_start: *data_start,
+                compressed_size: *compressed_size,
+            },
+            _ => {
+                let mut data = Vec::new();
+                let index = *index;
+                self.archive
+                    .by_index(index)
+                    .map_err(|_| libc::EIO)?
+                    .read_to_end(&mut data)
+                    .map_err(|_| libc::EIO)?;
+                Handle::Buffered(data)
+            }
+        };
+        self.next_handle += 1;
+        self.handles.insert(self.next_handle, handle);
+        Ok(Reply::default()
+            .u64(self.next_handle)
+            .u32(FOPEN_KEEP_CACHE)
+            .u32(0))
+    }
+
+    fn read(&mut self, handle: u64, offset: u64, len: usize) -> Result<Reply, i32> {
+        let archive_file = Arc::clone(&self.archive_file);
+        let handle = self.handles.get_mut(&handle).ok_or(libc::EBADF)?;
+        let mut data = Vec::new();
+        match handle {
+            Handle::Stored { start, size } => {
+                let len = len.min(size.saturating_sub(offset) as usize);
+                data.resize(len, 0);
+                archive_file
+                    .read_exact_at(&mut data, *start + offset)
+                    .map_err(|_| libc::EIO)?;
+            }
+            Handle::Deflated {
+                decoder,
+                pos,
+                size,
+                data_start,
+                compressed_size,
+            } => {
+                if offset < *pos {
+                    *decoder = deflate_decoder(&archive_file, *data_start, *compressed_size);
+                    *pos = 0;
+                }
+                let skipped = io::copy(&mut decoder.by_ref().take(offset - *pos), &mut io::sink())
+                    .map_err(|_| libc::EIO)?;
+                *pos += skipped;
+                let len = len.min(size.saturating_sub(*pos) as usize);
+                decoder
+                    .by_ref()
+                    .take(len as u64)
+                    .read_to_end(&mut data)
+                    .map_err(|_| libc::EIO)?;
+                *pos += data.len() as u64;
+            }
+            Handle::Buffered(buffer) => {
+                let start = (offset as usize).min(buffer.len());
+                let end = (start + len).min(buffer.len());
+                data.extend_from_slice(&buffer[start..end]);
+            }
+        }
+        Ok(Reply(data))
+    }
+
+    fn readdir(&self, id: u64, offset: u64, len: usize) -> Result<Reply, i32> {
+        let Some(node) = self.node(id) else {
+            return Err(libc::ENOENT);
+        };
+        let Content::Dir(children) = &node.content else {
+            return Err(libc::ENOTDIR);
+        };
+        let entries = [(OsStr::new("."), id), (OsStr::new(".."), node.parent)]
+            .into_iter()
+            .chain(
+                children
+                    .iter()
+                    .map(|(name, &child)| (name.as_os_str(), child)),
+            );
+        let mut reply = Reply::default();
+        for (i, (name, child)) in entries.enumerate().skip(offset as usize) {
+            let kind = match self.node(child).unwrap().content {
+                Content::Dir(_) => libc::DT_DIR,
+                Content::File { .. } => libc::DT_REG,
+            };
+            // struct fuse_dirent, padded to 8 bytes.
+            let entry_len = (24 + name.len()).next_multiple_of(8);
+            if reply.0.len() + entry_len > len {
+                break;
+            }
+            reply = reply
+                .u64(child)
+                .u64(i as u64 + 1)
+                .u32(name.len() as u32)
+                .u32(kind.into());
+            reply.0.extend_from_slice(name.as_bytes());
+            reply = reply.zeros(entry_len - 24 - name.len());
+        }
+        Ok(reply)
+    }
+
+    fn statfs(&self) -> Reply {
+        let blocks: u64 = self
+            .nodes
+            .iter()
+            .map(|node| match node.content {
+                Content::File { size, .. } => size.div_ceil(4096),
+                Content::Dir(_) => 0,
+            })
+            .sum();
+        // struct fuse_kstatfs
+        Reply::default()
+            .u64(blocks)
+            .u64(0)
+            .u64(0)
+            .u64(self.nodes.len() as u64)
+            .u64(0)
+            .u32(4096)
+            .u32(255)
+            .u32(4096)
+            .u32(0)
+            .zeros(24)
+    }
+
+    // Handle one request; None for those that get no reply.
+    fn handle(&mut self, opcode: u32, id: u64, body: &[u8]) -> Option<Result<Reply, i32>> {
+        Some(match opcode {
+            FUSE_INIT => {
+                let (major, max_readahead) = (field_u32(body, 0), field_u32(body, 8));
+                if major < 7 {
+                    Err(libc::EPROTO)
+                } else {
+                    // struct fuse_init_out; a newer kernel settles for 7.x.
+                    Ok(Reply::default()
+                        .u32(7)
+                        .u32(PROTOCOL_MINOR)
+                        .u32(max_readahead)
+                        .u32(0)
+                        .u16(16)
+                        .u16(12)
+                        .u32(MAX_READ as u32)
+                        .u32(1)
+                        .zeros(36))
+                }
+            }
+            FUSE_LOOKUP => {
+                let name = body.split(|&b| b == 0).next().unwrap_or_default();
+                self.lookup(id, OsStr::from_bytes(name))
+            }
+            FUSE_GETATTR => match self.node(id) {
+                // struct fuse_attr_out
+                Some(_) => Ok(self.attr(Reply::default().u64(VALID_SECONDS).u32(0).u32(0), id)),
+                None => Err(libc::ENOENT),
+            },
+            FUSE_OPEN => self.open(id, field_u32(body, 0)),
+            FUSE_READ => self.read(
+                field_u64(body, 0),
+                field_u64(body, 8),
+                (field_u32(body, 16) as usize).min(MAX_READ),
+            ),
+            FUSE_RELEASE => {
+                self.handles.remove(&field_u64(body, 0));
+                Ok(Reply::default())
+            }
+            FUSE_OPENDIR => match self.node(id).map(|node| &node.content) {
+                Some(Content::Dir(_)) => Ok(Reply::default().u64(0).u32(FOPEN_KEEP_CACHE).u32(0)),
+                _ => Err(libc::ENOTDIR),
+            },
+            FUSE_READDIR => self.readdir(
+                id,
+                field_u64(body, 8),
+                (field_u32(body, 16) as usize).min(MAX_READ),
+            ),
+            FUSE_RELEASEDIR | FUSE_FLUSH | FUSE_DESTROY => Ok(Reply::default()),
+            FUSE_ACCESS if field_u32(body, 0) & libc::W_OK as u32 != 0 => Err(libc::EROFS),
+            FUSE_ACCESS => Ok(Reply::default()),
+            FUSE_STATFS => Ok(self.statfs()),
+            FUSE_FORGET | FUSE_BATCH_FORGET | FUSE_INTERRUPT => return None,
+            _ => Err(libc::ENOSYS),
+        })
+    }
+}
+
+// Mount with mount(2), which needs root, or else through fusermount, which
+// hands over the /dev/fuse descriptor on a socket. Returns the descriptor
+// and whether fusermount mounted it.
+fn mount_fuse(mountpoint: &Path) -> Result<(File, bool)> {
+    let device = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_CLOEXEC)
+        .open("/dev/fuse")
+        .context("Failed to open /dev/fuse")?;
+    let target = CString::new(mountpoint.as_os_str().as_bytes())?;
+    let options = CString::new(format!(
+        "fd={},rootmode=40000,user_id={},group_id={}",
+        device.as_raw_fd(),
+        unsafe { libc::getuid() },
+        unsafe { libc::getgid() }
+    ))?;
+    let mounted = unsafe {
+        libc::mount(
+            c"exzip".as_ptr(),
+            target.as_ptr(),
+            c"fuse.exzip".as_ptr(),
+            libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV,
+            options.as_ptr().cast(),
+        )
+    };
+    if mounted == 0 {
+        return Ok((device, false));
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() != Some(libc::EPERM) {
+        return Err(err).with_context(|| format!("Failed to mount {}", mountpoint.display()));
+    }
+
+    let (ours, theirs) = UnixStream::pair()?;
+    // fusermount finds its end of the socket in _FUSE_COMMFD.
+    unsafe { libc::fcntl(theirs.as_raw_fd(), libc::F_SETFD, 0) };
+    let status = Command::new(fusermount())
+        .args(["-o", "ro,nosuid,nodev,fsname=exzip,subtype=exzip", "--"])
+        .arg(mountpoint)
+        .env("_FUSE_COMMFD", theirs.as_raw_fd().to_string())
+        .status()
+        .context("Failed to run fusermount")?;
+    drop(theirs);
+    if !status.success() {
+        bail!("fusermount exited with {}", status);
+    }
+    Ok((File::from(receive_fd(&ours)?), true))
+}
+
+fn fusermount() -> &'static str {
+    let found = |name: &str| {
+        std::env::var_os("PATH")
+            .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(name).is_file()))
+    };
+    if found("fusermount3") {
+        "fusermount3"
+    } else {
+        "fusermount"
+    }
+}
+
+fn receive_fd(socket: &UnixStream) -> Result<OwnedFd> {
+    let mut byte = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr().cast(),
+        iov_len: byte.len(),
+    };
+    // Room for one SCM_RIGHTS message, aligned for cmsghdr.
+    let mut control = [0u64; 8];
+    let mut message: libc::msghdr = unsafe { mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr().cast();
+    message.msg_controllen = mem::size_of_val(&control) as _;
+    if unsafe { libc::recvmsg(socket.as_raw_fd(), &mut message, 0) } < 0 {
+        return Err(io::Error::last_os_error()).context("Failed to receive from fusermount");
+    }
+    let header = unsafe { libc::CMSG_FIRSTHDR(&message) };
+    if header.is_null()
+        || unsafe { (*header).cmsg_level } != libc::SOL_SOCKET
+        || unsafe { (*header).cmsg_type } != libc::SCM_RIGHTS
+    {
+        bail!("fusermount sent no file descriptor");
+    }
+    let fd = unsafe { (libc::CMSG_DATA(header) as *const libc::c_int).read_unaligned() };
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn unmount(mountpoint: &Path, via_fusermount: bool) {
+    // Lazily, so that a shell sitting in the mount does not keep it.
+    let result = if via_fusermount {
+        Command::new(fusermount())
+            .args(["-u", "-z", "--"])
+            .arg(mountpoint)
+            .status()
+            .map(|_| ())
+    } else {
+        CString::new(mountpoint.as_os_str().as_bytes())
+            .map_err(io::Error::from)
+            .and_then(
+                |target| match unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) } {
+                    0 => Ok(()),
+                    _ => Err(io::Error::last_os_error()),
+                },
+            )
+    };
+    if let Err(err) = result {
+        notice!("Failed to unmount {}: {}", mountpoint.display(), err);
+    }
+}
+
+// Mount `zipfile` read-only on `mountpoint` as it would be extracted, and
+// serve it until interrupted or unmounted. File data is decompressed as it
+// is read, without checking the CRC. Returns whether it was interrupted.
+pub fn mount(zipfile: &Path, mountpoint: &Path, args: &Args) -> Result<bool> {
+    let mut archive = open_archive(zipfile, &Reporter::TERMINAL, !args.no_mmap)?;
+    let nodes = build_tree(&mut archive, args)?;
+    let mut filesystem = Filesystem {
+        nodes,
+        archive,
+        archive_file: Arc::new(File::open(zipfile)?),
+        handles: HashMap::new(),
+        next_handle: 0,
+        uid: unsafe { libc::getuid() },
+        gid: unsafe { libc::getgid() },
+    };
+
+    let (mut device, via_fusermount) = mount_fuse(mountpoint)?;
+    notice!("Mounted {} on {}", zipfile.display(), mountpoint.display());
+
+    // Reading /dev/fuse blocks, so Ctrl-C is noticed here and unmounting
+    // ends the loop below.
+    let done = Arc::new(AtomicBool::new(false));
+    let watcher = {
+        let done = Arc::clone(&done);
+        let mountpoint = mountpoint.to_path_buf();
+        thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                if interrupted() {
+                    unmount(&mountpoint, via_fusermount);
+                    return;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        })
+    };
+
+    let mut buffer = vec![0u8; MAX_READ + 4096];
+    let result = loop {
+        let len = match device.read(&mut buffer) {
+            Ok(len) => len,
+            // The request was interrupted before it was read.
+            Err(err) if err.raw_os_error() == Some(libc::ENOENT) => continue,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            // Unmounted.
+            Err(err) if err.raw_os_error() == Some(libc::ENODEV) => break Ok(()),
+            Err(err) => break Err(err.into()),
+        };
+        if len < IN_HEADER_SIZE {
+            continue;
+        }
+        let request = &buffer[..len];
+        let (opcode, unique, id) = (
+            field_u32(request, 4),
+            field_u64(request, 8),
+            field_u64(request, 16),
+        );
+        let Some(result) = filesystem.handle(opcode, id, &request[IN_HEADER_SIZE..]) else {
+            continue;
+        };
+        // struct fuse_out_header
+        let (error, payload) = match result {
+            Ok(reply) => (0, reply.0),
+            Err(errno) => (-errno, Vec::new()),
+        };
+        let mut reply = Reply::default()
+            .u32((16 + payload.len()) as u32)
+            .u32(error as u32)
+            .u64(unique);
+        reply.0.extend_from_slice(&payload);
+        match device.write(&reply.0) {
+            // The request was interrupted meanwhile.
+            Err(err) if err.raw_os_error() == Some(libc::ENOENT) => {}
+            Err(err) => break Err(err.into()),
+            Ok(_) => {}
+        }
+        if opcode == FUSE_DESTROY {
+            break Ok(());
+        }
+    };
+    done.store(true, Ordering::SeqCst);
+    let _ = watcher.join();
+    let interrupted = interrupted();
+    if result.is_err() && !interrupted {
+        unmount(mountpoint, via_fusermount);
+    }
+    result.map(|()| interrupted)
+}
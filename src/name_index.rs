@@ -0,0 +1,83 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+use zip::result::ZipResult;
+use zip::ZipArchive;
+
+use crate::encoding::ZipEncoding;
+use crate::zip_ext::ZipFileExt;
+use crate::{is_traversal, sanitize_path};
+
+// What is needed of an entry before extracting, read from the archive once.
+// Each by_index_raw() reads the local header, which adds up on archives with
+// many entries.
+pub struct RawName {
+    pub bytes: Vec<u8>,
+    pub is_utf8: bool,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+pub fn read_raw_names<R>(archive: &mut ZipArchive<R>) -> ZipResult<Vec<RawName>>
+where
+    R: io::Read + io::Seek,
+{
+    (0..archive.len())
+        .map(|i| {
+            let file = archive.by_index_raw(i)?;
+            Ok(RawName {
+                bytes: file.name_raw().to_vec(),
+                is_utf8: file.is_utf8(),
+                is_dir: file.is_dir(),
+                size: file.size(),
+            })
+        })
+        .collect()
+}
+
+pub struct IndexedName {
+    pub decoded: PathBuf,
+    // None for a malformed name.
+    pub sanitized: Option<PathBuf>,
+    pub is_utf8: bool,
+    pub is_dir: bool,
+}
+
+impl IndexedName {
+    // The sanitized path, or None for a name that climbs above the top,
+    // which is left out of the extraction unless --path-traversal rejects it.
+    pub fn path(&self) -> Result<Option<&Path>> {
+        match &self.sanitized {
+            Some(path) => Ok(Some(path)),
+            None if is_traversal(&self.decoded) => Ok(None),
+            None => bail!("Malformed zip file"),
+        }
+    }
+}
+
+impl RawName {
+    fn decode(&self, encoding: ZipEncoding) -> PathBuf {
+        if self.is_utf8 {
+            return PathBuf::from(String::from_utf8_lossy(&self.bytes).as_ref());
+        }
+        PathBuf::from(encoding.decode_lossy(&self.bytes))
+    }
+}
+
+// Decode and sanitize every name, indexed like the archive.
+pub fn decode_names(raw_names: &[RawName], encoding: ZipEncoding) -> Vec<IndexedName> {
+    raw_names
+        .iter()
+        .map(|raw_name| {
+            let decoded = raw_name.decode(encoding);
+            IndexedName {
+                sanitized: sanitize_path(&decoded),
+                decoded,
+                is_utf8: raw_name.is_utf8,
+                is_dir: raw_name.is_dir,
+            }
+        })
+        .collect()
+}
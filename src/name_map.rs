@@ -0,0 +1,100 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::{Path, PathBuf};
+
+pub struct NameMapEntry {
+    pub raw_name: Vec<u8>,
+    pub encoding: &'static str,
+    pub path: Option<PathBuf>,
+    // The rest is for --report.
+    pub name: PathBuf,
+    pub size: u64,
+    // Whether the data passed the CRC check, for entries with data.
+    pub intact: Option<bool>,
+}
+
+// Tab separated lines of: percent-encoded raw name, encoding, percent-encoded
+// extracted path. Skipped entries have "-" as the extracted path.
+pub struct NameMap {
+    writer: BufWriter<File>,
+}
+
+impl NameMap {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(Self { writer })
+    }
+
+    pub fn write_entries(
+        &mut self,
+        target_path: &Path,
+        entries: &[NameMapEntry],
+    ) -> io::Result<()> {
+        for entry in entries {
+            let path = match &entry.path {
+                Some(path) if path == Path::new(".") => {
+                    percent_encode(target_path.as_os_str().as_bytes())
+                }
+                Some(path) => percent_encode(target_path.join(path).as_os_str().as_bytes()),
+                None => "-".to_owned(),
+            };
+            writeln!(
+                self.writer,
+                "{}\t{}\t{}",
+                percent_encode(&entry.raw_name),
+                entry.encoding,
+                path
+            )?;
+        }
+        self.writer.flush()
+    }
+}
+
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if b.is_ascii_graphic() && b != b'%' {
+            result.push(b as char);
+        } else {
+            write!(result, "%{:02X}", b).unwrap();
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+
+    #[test]
+    fn paths_are_percent_encoded_like_raw_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let map_path = dir.path().join("names.tsv");
+        let entry = |raw_name: &[u8], path: Option<&Path>| NameMapEntry {
+            raw_name: raw_name.to_vec(),
+            encoding: "Shift_JIS",
+            path: path.map(Path::to_path_buf),
+            name: PathBuf::new(),
+            size: 0,
+            intact: None,
+        };
+        let invalid = Path::new(OsStr::from_bytes(b"\x83e\tx"));
+        let entries = [
+            entry(b"\x83e\tx", Some(invalid)),
+            entry(b"a b", Some(Path::new("a b"))),
+            entry(b"../c", None),
+        ];
+        let mut map = NameMap::create(&map_path).unwrap();
+        map.write_entries(Path::new("/t"), &entries).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&map_path).unwrap(),
+            "%83e%09x\tShift_JIS\t/t/%83e%09x\n\
+             a%20b\tShift_JIS\t/t/a%20b\n\
+             ../c\tShift_JIS\t-\n"
+        );
+    }
+}
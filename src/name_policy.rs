@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+use crate::control_chars::{contains_suspicious, display_path, escape_path, strip_path};
+use crate::long_names::{has_long_component, truncate_path};
+use crate::output::{notice, status, Style};
+use crate::portable::portable_path;
+use crate::{
+    fold_case, is_traversal, skip_event, unique_path, CaseCollisionPolicy, ControlCharPolicy,
+    DuplicatePolicy, Options, PathTraversalPolicy,
+};
+
+// Checked on the name as it is, since sanitizing cannot keep a name which
+// climbs above the top.
+pub fn check_traversal(options: &Options, decoded: &Path, sanitized: Option<&Path>) -> Result<()> {
+    if !is_traversal(decoded) {
+        return Ok(());
+    }
+    match (options.path_traversal, sanitized) {
+        (PathTraversalPolicy::Strict, _) => {
+            bail!("Path traversal in entry name {}", display_path(decoded));
+        }
+        (PathTraversalPolicy::Warn, Some(sanitized)) => {
+            notice!(
+                options.reporter,
+                "Warning: Rewrote {} -> {}",
+                display_path(decoded),
+                display_path(sanitized)
+            );
+        }
+        (PathTraversalPolicy::Warn, None) => {
+            notice!(
+                options.reporter,
+                "Warning: Skipped {}, which is outside of the target directory",
+                display_path(decoded)
+            );
+        }
+        (PathTraversalPolicy::Sanitize, _) => {}
+    }
+    Ok(())
+}
+
+// Where a file entry goes after checking for duplicates.
+pub enum Placement {
+    New(PathBuf),
+    // Over an earlier entry of the same name, with --on-duplicate=last.
+    Overwrite(PathBuf),
+    Skip,
+}
+
+// The rewriting of entry names and the names taken so far, shared by
+// extracting and salvaging so that both apply the same policies.
+pub struct NamePolicy<'a> {
+    options: &'a Options,
+    case_insensitive: bool,
+    extracted_files: HashSet<PathBuf>,
+    folded_files: HashSet<String>,
+}
+
+impl<'a> NamePolicy<'a> {
+    pub fn new(options: &'a Options, case_insensitive: bool) -> Self {
+        NamePolicy {
+            options,
+            case_insensitive,
+            extracted_files: HashSet::new(),
+            folded_files: HashSet::new(),
+        }
+    }
+
+    // Apply --control-chars, --portable-names and --truncate-long-names.
+    pub fn rewrite(&self, unstripped_path: &Path, path: &Path) -> Result<PathBuf> {
+        let options = self.options;
+        let mut path = path.to_path_buf();
+        if options.control_chars != ControlCharPolicy::Keep && contains_suspicious(&path) {
+            path = match options.control_chars {
+                ControlCharPolicy::Escape => escape_path(&path),
+                ControlCharPolicy::Strip => strip_path(&path),
+                ControlCharPolicy::Reject => {
+                    bail!(
+                        "Control characters in entry name {}",
+                        display_path(unstripped_path)
+                    );
+                }
+                ControlCharPolicy::Keep => unreachable!(),
+            };
+            status!(
+                options.reporter,
+                Style::Plain,
+                "Rename",
+                "{} -> {}",
+                display_path(unstripped_path),
+                path.display()
+            );
+        }
+
+        if options.portable_names {
+            let portable = portable_path(&path);
+            if portable != path {
+                status!(
+                    options.reporter,
+                    Style::Plain,
+                    "Rename",
+                    "{} -> {}",
+                    display_path(unstripped_path),
+                    portable.display()
+                );
+                path = portable;
+            }
+        }
+
+        if options.truncate_long_names && has_long_component(&path) {
+            let truncated = truncate_path(&path);
+            status!(
+                options.reporter,
+                Style::Plain,
+                "Truncate",
+                "{} -> {}",
+                display_path(unstripped_path),
+                truncated.display()
+            );
+            path = truncated;
+        }
+        Ok(path)
+    }
+
+    // Apply --on-duplicate and --on-case-collision to a file entry.
+    pub fn place(&mut self, unstripped_path: &Path, path: PathBuf) -> Result<Placement> {
+        let options = self.options;
+        let mut overwrite = false;
+        let mut path = path;
+        if !self.extracted_files.insert(path.clone()) {
+            match options.on_duplicate {
+                DuplicatePolicy::Last => {
+                    status!(
+                        options.reporter,
+                        Style::Plain,
+                        "Duplicate",
+                        "{}, overwriting",
+                        display_path(unstripped_path)
+                    );
+                    overwrite = true;
+                }
+                DuplicatePolicy::First => {
+                    status!(
+                        options.reporter,
+                        Style::Skip,
+                        "Skip",
+                        "{} (duplicate)",
+                        display_path(unstripped_path)
+                    );
+                    skip_event(&options.reporter, unstripped_path, "duplicate");
+                    return Ok(Placement::Skip);
+                }
+                DuplicatePolicy::Error => {
+                    bail!("Duplicate entry {}", display_path(unstripped_path));
+                }
+                DuplicatePolicy::Rename => {
+                    let extracted_files = &self.extracted_files;
+                    path = unique_path(&path, |candidate| extracted_files.contains(candidate));
+                    self.extracted_files.insert(path.clone());
+                    status!(
+                        options.reporter,
+                        Style::Plain,
+                        "Duplicate",
+                        "{}, renamed to {}",
+                        display_path(unstripped_path),
+                        path.display()
+                    );
+                }
+            }
+        }
+
+        // An overwritten file keeps the name it has on disk.
+        if !overwrite
+            && self.case_insensitive
+            && options.on_case_collision == CaseCollisionPolicy::Rename
+            && !self.folded_files.insert(fold_case(&path))
+        {
+            let folded_files = &self.folded_files;
+            path = unique_path(&path, |candidate| {
+                folded_files.contains(&fold_case(candidate))
+            });
+            self.folded_files.insert(fold_case(&path));
+            status!(
+                options.reporter,
+                Style::Plain,
+                "Collision",
+                "{}, renamed to {}",
+                display_path(unstripped_path),
+                path.display()
+            );
+        }
+
+        Ok(if overwrite {
+            Placement::Overwrite(path)
+        } else {
+            Placement::New(path)
+        })
+    }
+}
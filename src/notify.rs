@@ -0,0 +1,43 @@
+use std::io;
+use std::process::{Command, Stdio};
+
+// A desktop notification (--notify), through notify-send on freedesktop
+// desktops and osascript on macOS.
+pub fn notify(title: &str, body: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            apple_script_string(body),
+            apple_script_string(title)
+        ));
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=exzip", "--", title, body]);
+        command
+    };
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("{}: {}", command.get_program().to_string_lossy(), err),
+            )
+        })?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {}",
+            command.get_program().to_string_lossy(),
+            status
+        )));
+    }
+    Ok(())
+}
+
+fn apple_script_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,139 @@
+// s3:// and gs:// URLs, read with plain HTTPS requests that curl signs.
+//
+// S3 credentials are taken from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY
+// or else the profile in ~/.aws/credentials, and the region from AWS_REGION
+// or AWS_DEFAULT_REGION. AWS_ENDPOINT_URL points to an S3-compatible service
+// instead, addressed by path. GCS takes an OAuth token from
+// GOOGLE_OAUTH_ACCESS_TOKEN. Without credentials, objects are requested
+// anonymously, which is enough for public buckets.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::remote::Request;
+
+pub fn is_object_url(path: &Path) -> bool {
+    let path = path.as_os_str().as_encoded_bytes();
+    path.starts_with(b"s3://") || path.starts_with(b"gs://")
+}
+
+// Percent-encode the bytes of a key that are not unreserved, as signing
+// expects.
+fn encode_key(key: &[u8]) -> String {
+    let mut encoded = String::new();
+    for &b in key {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+// A value of a curl config file.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+// The access key, secret key and session token of the profile in
+// ~/.aws/credentials.
+fn profile_credentials() -> Option<(String, String, Option<String>)> {
+    let path = non_empty_var("AWS_SHARED_CREDENTIALS_FILE")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".aws/credentials")))?;
+    let profile = non_empty_var("AWS_PROFILE").unwrap_or_else(|| "default".to_string());
+    let text = fs::read_to_string(path).ok()?;
+    let (mut key, mut secret, mut token) = (None, None, None);
+    let mut in_profile = false;
+    for line in text.lines().map(str::trim) {
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_profile = section.trim() == profile;
+        } else if let Some((name, value)) = line.split_once('=').filter(|_| in_profile) {
+            let value = Some(value.trim().to_string());
+            match name.trim() {
+                "aws_access_key_id" => key = value,
+                "aws_secret_access_key" => secret = value,
+                "aws_session_token" => token = value,
+                _ => {}
+            }
+        }
+    }
+    Some((key?, secret?, token))
+}
+
+fn s3_request(bucket: &str, key: &str) -> Request {
+    let region = non_empty_var("AWS_REGION")
+        .or_else(|| non_empty_var("AWS_DEFAULT_REGION"))
+        .unwrap_or_else(|| "us-east-1".to_string());
+    let url =
+        match non_empty_var("AWS_ENDPOINT_URL_S3").or_else(|| non_empty_var("AWS_ENDPOINT_URL")) {
+            Some(endpoint) => format!("{}/{}/{}", endpoint.trim_end_matches('/'), bucket, key),
+            None => format!("https://{}.s3.{}.amazonaws.com/{}", bucket, region, key),
+        };
+    let credentials = match (
+        non_empty_var("AWS_ACCESS_KEY_ID"),
+        non_empty_var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        (Some(key), Some(secret)) => Some((key, secret, non_empty_var("AWS_SESSION_TOKEN"))),
+        _ => profile_credentials(),
+    };
+    let mut config = String::new();
+    if let Some((key, secret, token)) = credentials {
+        config += &format!("aws-sigv4 = {}\n", quote(&format!("aws:amz:{}:s3", region)));
+        config += &format!("user = {}\n", quote(&format!("{}:{}", key, secret)));
+        if let Some(token) = token {
+            config += &format!(
+                "header = {}\n",
+                quote(&format!("x-amz-security-token: {}", token))
+            );
+        }
+    }
+    Request {
+        url: url.into(),
+        config,
+    }
+}
+
+fn gcs_request(bucket: &str, key: &str) -> Request {
+    let mut config = String::new();
+    if let Some(token) = non_empty_var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+        config += &format!(
+            "header = {}\n",
+            quote(&format!("Authorization: Bearer {}", token))
+        );
+    }
+    Request {
+        url: format!("https://storage.googleapis.com/{}/{}", bucket, key).into(),
+        config,
+    }
+}
+
+pub fn request(url: &Path) -> io::Result<Request> {
+    let url = url.as_os_str().as_encoded_bytes();
+    let (scheme, rest) = url.split_at(5);
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Not an object URL: {}", String::from_utf8_lossy(url)),
+        )
+    };
+    let (bucket, key) = rest
+        .iter()
+        .position(|&b| b == b'/')
+        .map(|i| (&rest[..i], &rest[i + 1..]))
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .ok_or_else(invalid)?;
+    let bucket = std::str::from_utf8(bucket).map_err(|_| invalid())?;
+    let key = encode_key(key);
+    Ok(match scheme {
+        b"s3://" => s3_request(bucket, &key),
+        _ => gcs_request(bucket, &key),
+    })
+}
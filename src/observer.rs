@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use crate::Extracted;
+
+/// Receives what happens during an extraction by an [`Extractor`], for
+/// applications showing progress and messages in their own way. All methods
+/// do nothing by default. They may be called from the threads writing file
+/// data.
+///
+/// [`Extractor`]: crate::Extractor
+pub trait ExtractObserver: Send + Sync {
+    /// An entry is about to be extracted. `name` is its path in the archive
+    /// and `size` its uncompressed size.
+    fn entry_started(&self, name: &Path, size: u64) {
+        let _ = (name, size);
+    }
+
+    /// `bytes` more of file data have been written, counting all entries.
+    fn bytes_written(&self, bytes: u64) {
+        let _ = bytes;
+    }
+
+    /// An entry is not extracted, for `reason`: "ignored", "duplicate" or
+    /// the kind of a special file.
+    fn entry_skipped(&self, name: &Path, reason: &str) {
+        let _ = (name, reason);
+    }
+
+    /// A warning the `exzip` command would print, such as a damaged entry.
+    fn warning(&self, message: &str) {
+        let _ = message;
+    }
+
+    /// The extraction of the archive is over.
+    fn finished(&self, result: Result<&Extracted, &anyhow::Error>) {
+        let _ = result;
+    }
+}
@@ -0,0 +1,432 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, IsTerminal as _, LineWriter, Write as _};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::interrupt;
+use crate::json;
+use crate::observer::ExtractObserver;
+use crate::progress;
+use crate::Extracted;
+
+// How many times -q was given.
+static QUIET: AtomicU8 = AtomicU8::new(0);
+// --output=json: stdout carries only events.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+// --log-file, which gets every line whatever -q and --output say.
+static LOG: Mutex<Option<LineWriter<File>>> = Mutex::new(None);
+
+static STDOUT_CLOSED: AtomicBool = AtomicBool::new(false);
+static STDERR_CLOSED: AtomicBool = AtomicBool::new(false);
+
+// Whether to color stdout and stderr.
+static COLOR_STDOUT: AtomicBool = AtomicBool::new(false);
+static COLOR_STDERR: AtomicBool = AtomicBool::new(false);
+
+// Width of the label column of entry lines, e.g. "Skip      name".
+const LABEL_WIDTH: usize = 9;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    // Skipped entries, in yellow.
+    Skip,
+    // Errors, in red.
+    Error,
+    // The final rename and summary, in green.
+    Done,
+}
+
+impl Style {
+    fn code(self) -> Option<&'static str> {
+        match self {
+            Style::Plain => None,
+            Style::Skip => Some("33"),
+            Style::Error => Some("1;31"),
+            Style::Done => Some("32"),
+        }
+    }
+}
+
+// `always` and `never` are --color; otherwise color where the stream is a
+// terminal and NO_COLOR is not set.
+pub fn set_color(always: bool, never: bool) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let auto = |is_terminal: bool| is_terminal && !no_color && !is_json();
+    COLOR_STDOUT.store(
+        always || (!never && auto(io::stdout().is_terminal())),
+        Ordering::Relaxed,
+    );
+    COLOR_STDERR.store(
+        always || (!never && auto(io::stderr().is_terminal())),
+        Ordering::Relaxed,
+    );
+}
+
+fn paint(text: &str, style: Style, color: bool) -> String {
+    match style.code().filter(|_| color) {
+        Some(code) => format!("\x1b[{}m{}\x1b[0m", code, text),
+        None => text.to_string(),
+    }
+}
+
+// For text printed on stdout.
+pub fn paint_stdout(text: &str, style: Style) -> String {
+    paint(text, style, COLOR_STDOUT.load(Ordering::Relaxed))
+}
+
+pub fn paint_stderr(text: &str, style: Style) -> String {
+    paint(text, style, COLOR_STDERR.load(Ordering::Relaxed))
+}
+
+pub fn set_quiet(level: u8) {
+    QUIET.store(level, Ordering::Relaxed);
+}
+
+pub fn quiet() -> u8 {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+// Stdout carries the results: the entries extracted and the summary.
+// Warnings and errors go to stderr. An extraction reports through its
+// Reporter, given first as in say!(args.reporter, "..."), so that one with
+// an ExtractObserver prints nothing and gives the warnings to its observer.
+
+// A line about each entry or step, hidden with -q. The progress bar is
+// erased first if it is shown.
+macro_rules! say {
+    ($fmt:literal $($arg:tt)*) => {
+        $crate::output::say_line(format_args!($fmt $($arg)*))
+    };
+    ($reporter:expr, $($arg:tt)*) => {
+        $reporter.say(format_args!($($arg)*))
+    };
+}
+pub(crate) use say;
+
+// Warnings and damaged entries on stderr, hidden with -qq. These become
+// warning events with --output=json. Warnings, summaries and diagnostics are
+// translated with tr!.
+macro_rules! notice {
+    ($fmt:literal $($arg:tt)*) => {
+        $crate::output::notice_line(format_args!("{}", $crate::i18n::tr!($fmt $($arg)*)))
+    };
+    ($reporter:expr, $($arg:tt)*) => {
+        $reporter.notice(format_args!("{}", $crate::i18n::tr!($($arg)*)))
+    };
+}
+pub(crate) use notice;
+
+// The final summary on stdout, hidden with -qq.
+macro_rules! summary {
+    ($fmt:literal $($arg:tt)*) => {
+        $crate::output::summary_line(format_args!("{}", $crate::i18n::tr!($fmt $($arg)*)))
+    };
+    ($reporter:expr, $($arg:tt)*) => {
+        $reporter.summary(format_args!("{}", $crate::i18n::tr!($($arg)*)))
+    };
+}
+pub(crate) use summary;
+
+// An entry line with a label column, e.g. status!(Style::Skip, "Skip", "{}",
+// path). Hidden with -q like say!.
+macro_rules! status {
+    ($style:expr, $label:literal, $($arg:tt)*) => {
+        $crate::output::status_line($style, $label, format_args!($($arg)*))
+    };
+    ($reporter:expr, $style:expr, $label:literal, $($arg:tt)*) => {
+        $reporter.status($style, $label, format_args!($($arg)*))
+    };
+}
+pub(crate) use status;
+
+// Output that is the result itself, such as the bench table; never hidden.
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        $crate::output::out_line(format_args!($($arg)*))
+    };
+}
+pub(crate) use outln;
+
+// A diagnostic line on stderr.
+macro_rules! diag {
+    ($fmt:literal $($arg:tt)*) => {
+        $crate::output::write_stderr(format_args!("{}", $crate::i18n::tr!($fmt $($arg)*)))
+    };
+    ($reporter:expr, $($arg:tt)*) => {
+        $reporter.diag(format_args!("{}", $crate::i18n::tr!($($arg)*)))
+    };
+}
+pub(crate) use diag;
+
+// "Error: ..." on stderr.
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::output::write_stderr(format_args!(
+            "{} {}",
+            $crate::output::paint_stderr(
+                &$crate::i18n::tr!("Error:"),
+                $crate::output::Style::Error
+            ),
+            format_args!($($arg)*)
+        ))
+    };
+}
+pub(crate) use error;
+
+// Print a line to stdout. Once stdout is a closed pipe, as in
+// `exzip big.zip | head`, nothing more is printed there and the extraction
+// is interrupted like with Ctrl-C, instead of panicking in println!.
+pub fn write_stdout(line: fmt::Arguments) {
+    if STDOUT_CLOSED.load(Ordering::Relaxed) {
+        return;
+    }
+    progress::clear();
+    if let Err(err) = writeln!(io::stdout().lock(), "{}", line) {
+        if err.kind() == io::ErrorKind::BrokenPipe {
+            STDOUT_CLOSED.store(true, Ordering::Relaxed);
+            interrupt::interrupt();
+        }
+    }
+}
+
+// Diagnostics are dropped once stderr is closed; they are no reason to stop.
+pub fn write_stderr(line: fmt::Arguments) {
+    log(line);
+    if STDERR_CLOSED.load(Ordering::Relaxed) {
+        return;
+    }
+    progress::clear();
+    if let Err(err) = writeln!(io::stderr().lock(), "{}", line) {
+        if err.kind() == io::ErrorKind::BrokenPipe {
+            STDERR_CLOSED.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+// Start --log-file. The children of --jobs append to the parent's log.
+pub fn open_log(path: &Path, append: bool) -> io::Result<()> {
+    if !append {
+        File::create(path)?;
+    }
+    // Appending keeps the lines of concurrent processes whole.
+    let file = File::options().create(true).append(true).open(path)?;
+    *LOG.lock().unwrap() = Some(LineWriter::new(file));
+    Ok(())
+}
+
+// Without the colors, which belong to the terminal.
+fn strip_escapes(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+fn log(line: fmt::Arguments) {
+    let mut log = LOG.lock().unwrap();
+    if let Some(writer) = log.as_mut() {
+        let _ = writeln!(
+            writer,
+            "{} {}",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            strip_escapes(&line.to_string())
+        );
+    }
+}
+
+pub fn out_line(line: fmt::Arguments) {
+    log(line);
+    write_stdout(line);
+}
+
+pub fn summary_line(line: fmt::Arguments) {
+    log(line);
+    if quiet() < 2 && !is_json() {
+        write_stdout(format_args!(
+            "{}",
+            paint_stdout(&line.to_string(), Style::Done)
+        ));
+    }
+}
+
+pub fn say_line(line: fmt::Arguments) {
+    log(line);
+    if quiet() == 0 && !is_json() {
+        write_stdout(line);
+    }
+}
+
+pub fn status_line(style: Style, label: &str, line: fmt::Arguments) {
+    let padding = " ".repeat(LABEL_WIDTH.saturating_sub(label.len()));
+    say_line(format_args!(
+        "{}{} {}",
+        paint_stdout(label, style),
+        padding,
+        line
+    ));
+}
+
+pub fn notice_line(line: fmt::Arguments) {
+    if quiet() >= 2 {
+        log(line);
+        return;
+    }
+    if is_json() {
+        log(line);
+        Event::new("warning")
+            .str("message", &line.to_string())
+            .emit();
+    } else {
+        write_stderr(line);
+    }
+}
+
+// Where an extraction reports what happens: printed as the exzip command
+// prints it, and told to the observer of an Extractor if there is one.
+// Clones report to the same observer, from the threads writing file data
+// too.
+#[derive(Clone, Default)]
+pub struct Reporter {
+    print: bool,
+    observer: Option<Arc<dyn ExtractObserver>>,
+}
+
+impl Reporter {
+    pub const TERMINAL: Reporter = Reporter {
+        print: true,
+        observer: None,
+    };
+
+    // Nothing is printed; warnings go to the observer.
+    pub fn observed(observer: Option<Arc<dyn ExtractObserver>>) -> Self {
+        Reporter {
+            print: false,
+            observer,
+        }
+    }
+
+    pub fn say(&self, line: fmt::Arguments) {
+        if self.print {
+            say_line(line);
+        }
+    }
+
+    pub fn status(&self, style: Style, label: &str, line: fmt::Arguments) {
+        if self.print {
+            status_line(style, label, line);
+        }
+    }
+
+    pub fn summary(&self, line: fmt::Arguments) {
+        if self.print {
+            summary_line(line);
+        }
+    }
+
+    pub fn notice(&self, line: fmt::Arguments) {
+        if self.print {
+            notice_line(line);
+        }
+        if let Some(observer) = &self.observer {
+            observer.warning(&line.to_string());
+        }
+    }
+
+    pub fn diag(&self, line: fmt::Arguments) {
+        if self.print {
+            write_stderr(line);
+        }
+        if let Some(observer) = &self.observer {
+            observer.warning(&line.to_string());
+        }
+    }
+
+    pub fn emit(&self, event: Event) {
+        if self.print {
+            event.emit();
+        }
+    }
+
+    pub fn entry_started(&self, name: &Path, size: u64) {
+        if let Some(observer) = &self.observer {
+            observer.entry_started(name, size);
+        }
+    }
+
+    pub fn bytes_written(&self, bytes: usize) {
+        if let Some(observer) = &self.observer {
+            observer.bytes_written(bytes as u64);
+        }
+    }
+
+    pub fn entry_skipped(&self, name: &Path, reason: &str) {
+        if let Some(observer) = &self.observer {
+            observer.entry_skipped(name, reason);
+        }
+    }
+
+    pub fn finished(&self, result: Result<&Extracted, &anyhow::Error>) {
+        if let Some(observer) = &self.observer {
+            observer.finished(result);
+        }
+    }
+}
+
+// The causes of `err` on one line, as {:#} gives them but without a cause
+// repeating the one before, as wrapped I/O errors do.
+pub fn error_message(err: &anyhow::Error) -> String {
+    let mut causes: Vec<String> = Vec::new();
+    for cause in err.chain() {
+        let cause = cause.to_string();
+        if causes.last().is_none_or(|last| !last.ends_with(&cause)) {
+            causes.push(cause);
+        }
+    }
+    causes.join(": ")
+}
+
+// One line of --output=json, e.g. {"event":"entry-done","path":...}.
+pub struct Event(json::Object);
+
+impl Event {
+    pub fn new(kind: &str) -> Self {
+        Event(json::Object::new().str("event", kind))
+    }
+
+    pub fn str(self, key: &str, value: &str) -> Self {
+        Event(self.0.str(key, value))
+    }
+
+    pub fn num(self, key: &str, value: u64) -> Self {
+        Event(self.0.num(key, value))
+    }
+
+    pub fn bool(self, key: &str, value: bool) -> Self {
+        Event(self.0.bool(key, value))
+    }
+
+    // Print the event if --output=json is in effect.
+    pub fn emit(self) {
+        if is_json() {
+            write_stdout(format_args!("{}", self.0.finish()));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, Result};
+
+use crate::output::Reporter;
+use crate::{copy_entry, open_archive, CopyOptions, CopyOutcome};
+
+pub struct CopyJob {
+    pub id: usize,
+    pub index: usize,
+    pub outfile: File,
+    pub limit: u64,
+}
+
+// Decompresses file entries on worker threads, each with its own handle to
+// the archive. Files are created by the caller, which keeps every naming and
+// policy decision on the main thread.
+pub struct CopyPool {
+    sender: Option<SyncSender<CopyJob>>,
+    results: Receiver<(usize, Result<CopyOutcome>)>,
+    workers: Vec<JoinHandle<()>>,
+    abort: Arc<AtomicBool>,
+    written: Arc<AtomicU64>,
+}
+
+fn run_worker(
+    zipfile: &Path,
+    jobs: &Mutex<Receiver<CopyJob>>,
+    results: &Sender<(usize, Result<CopyOutcome>)>,
+    abort: &AtomicBool,
+    written: &AtomicU64,
+    use_mmap: bool,
+    options: CopyOptions,
+) {
+    let mut archive = open_archive(zipfile, &Reporter::default(), use_mmap);
+    let archive_file = File::open(zipfile);
+    loop {
+        let Ok(mut job) = jobs.lock().unwrap().recv() else {
+            return;
+        };
+        if abort.load(Ordering::SeqCst) {
+            return;
+        }
+        let result = match (&mut archive, &archive_file) {
+            (Ok(archive), Ok(archive_file)) => archive
+                .by_index(job.index)
+                .map_err(anyhow::Error::from)
+                .and_then(|mut file| {
+                    copy_entry(
+                        &mut file,
+                        archive_file,
+                        &mut job.outfile,
+                        job.limit,
+                        &options,
+                    )
+                }),
+            (Err(err), _) => Err(anyhow!("Failed to open the archive: {}", err)),
+            (_, Err(err)) => Err(anyhow!("Failed to open the archive: {}", err)),
+        };
+        if let Ok(outcome) = &result {
+            written.fetch_add(outcome.written, Ordering::SeqCst);
+        }
+        if results.send((job.id, result)).is_err() {
+            return;
+        }
+    }
+}
+
+impl CopyPool {
+    pub fn new(zipfile: &Path, threads: usize, use_mmap: bool, options: &CopyOptions) -> Self {
+        // A bounded queue keeps the number of files open at once in check.
+        let (sender, jobs) = mpsc::sync_channel::<CopyJob>(threads);
+        let (result_sender, results) = mpsc::channel();
+        let jobs = Arc::new(Mutex::new(jobs));
+        let abort = Arc::new(AtomicBool::new(false));
+        let written = Arc::new(AtomicU64::new(0));
+        let workers = (0..threads)
+            .map(|_| {
+                let zipfile = PathBuf::from(zipfile);
+                let jobs = Arc::clone(&jobs);
+                let results = result_sender.clone();
+                let abort = Arc::clone(&abort);
+                let written = Arc::clone(&written);
+                let options = options.clone();
+                thread::spawn(move || {
+                    run_worker(
+                        &zipfile, &jobs, &results, &abort, &written, use_mmap, options,
+                    )
+                })
+            })
+            .collect();
+        CopyPool {
+            sender: Some(sender),
+            results,
+            workers,
+            abort,
+            written,
+        }
+    }
+
+    // Bytes written by finished jobs. Jobs still running are not counted, so
+    // the output size limit may be exceeded by what is in flight.
+    pub fn written(&self) -> u64 {
+        self.written.load(Ordering::SeqCst)
+    }
+
+    pub fn submit(&self, job: CopyJob) {
+        // Workers only stop early once aborted, when the job is moot anyway.
+        let _ = self.sender.as_ref().unwrap().send(job);
+    }
+
+    // Wait for every submitted job and return the results by job id.
+    pub fn finish(mut self) -> Vec<(usize, Result<CopyOutcome>)> {
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            worker.join().expect("Extraction thread panicked");
+        }
+        self.results.try_iter().collect()
+    }
+}
+
+impl Drop for CopyPool {
+    // Dropped without finish() when the extraction fails part way.
+    fn drop(&mut self) {
+        self.abort.store(true, Ordering::SeqCst);
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
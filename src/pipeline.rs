@@ -0,0 +1,55 @@
+use std::io::{Read, Write};
+use std::sync::mpsc;
+use std::thread;
+
+use anyhow::Result;
+
+use crate::interrupt::InterruptibleReader;
+use crate::CopyOutcome;
+
+// Like copy_through, but the writes happen on another thread so that
+// inflating the next chunk overlaps with writing the previous one. Up to
+// `pipeline_buffer` bytes of decompressed data wait in the queue.
+pub fn pipelined_copy<R, W>(
+    mut reader: InterruptibleReader<R>,
+    writer: &mut W,
+    chunk_size: usize,
+    pipeline_buffer: u64,
+) -> Result<CopyOutcome>
+where
+    R: Read,
+    W: Write + Send + ?Sized,
+{
+    let depth = (pipeline_buffer / chunk_size as u64).max(1) as usize;
+    thread::scope(|scope| {
+        let (full_sender, full) = mpsc::sync_channel::<Vec<u8>>(depth);
+        let (empty_sender, empty) = mpsc::channel::<Vec<u8>>();
+        let write_thread = scope.spawn(move || {
+            for buf in full {
+                writer.write_all(&buf)?;
+                // Hand the buffer back for reuse.
+                let _ = empty_sender.send(buf);
+            }
+            writer.flush()
+        });
+
+        let read_result = (|| loop {
+            let mut buf = empty
+                .try_recv()
+                .unwrap_or_else(|_| Vec::with_capacity(chunk_size));
+            buf.clear();
+            let length = (&mut reader)
+                .take(chunk_size as u64)
+                .read_to_end(&mut buf)?;
+            // The writer only hangs up after a failure, reported below.
+            if length == 0 || full_sender.send(buf).is_err() || length < chunk_size {
+                return Ok::<_, anyhow::Error>(());
+            }
+        })();
+        drop(full_sender);
+
+        write_thread.join().expect("Write thread panicked")?;
+        read_result?;
+        Ok(reader.finish())
+    })
+}
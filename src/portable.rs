@@ -0,0 +1,43 @@
+use std::path::{Path, PathBuf};
+
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// Rewrite a path so that every component is valid on Windows and exFAT.
+pub fn portable_path(path: &Path) -> PathBuf {
+    path.iter()
+        .map(|name| portable_name(&name.to_string_lossy()))
+        .collect()
+}
+
+fn portable_name(name: &str) -> String {
+    if name == "." || name == ".." {
+        return name.to_owned();
+    }
+    let mut result: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' | '\\' => '_',
+            c if c.is_ascii_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    // Trailing dots and spaces are silently stripped by Windows.
+    let trimmed_len = result.trim_end_matches(['.', ' ']).len();
+    let trailing = result.len() - trimmed_len;
+    result.truncate(trimmed_len);
+    result.extend(std::iter::repeat_n('_', trailing));
+
+    // Device names are reserved regardless of the extension.
+    let stem = result.split('.').next().unwrap();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        result.insert(stem.len(), '_');
+    }
+    result
+}
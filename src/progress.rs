@@ -0,0 +1,200 @@
+use std::io::{self, IsTerminal as _, Write as _};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::limits::format_size;
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 24;
+// The throughput is measured over windows of this length and averaged with
+// a weight of SMOOTHING on the latest window.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+const SMOOTHING: f64 = 0.3;
+// Entries at least this large show their bytes, not only a percentage.
+const LARGE_ENTRY: u64 = 64 * 1024 * 1024;
+
+// A progress bar on stderr (--progress): the bytes written of the whole
+// archive and of the entry being extracted. Updated from every thread
+// copying file data, so with --threads the entry is the one started last.
+struct Progress {
+    total: u64,
+    done: u64,
+    entry: String,
+    entry_size: u64,
+    entry_done: u64,
+    last_draw: Option<Instant>,
+    shown: bool,
+    sample_start: Instant,
+    sample_done: u64,
+    // Bytes per second, once a window has passed.
+    rate: Option<f64>,
+}
+
+static PROGRESS: Mutex<Option<Progress>> = Mutex::new(None);
+
+// Show a bar for `total` bytes, if stderr is a terminal.
+pub fn start(total: u64) {
+    if !io::stderr().is_terminal() {
+        return;
+    }
+    *PROGRESS.lock().unwrap() = Some(Progress {
+        total,
+        done: 0,
+        entry: String::new(),
+        entry_size: 0,
+        entry_done: 0,
+        last_draw: None,
+        shown: false,
+        sample_start: Instant::now(),
+        sample_done: 0,
+        rate: None,
+    });
+}
+
+pub fn start_entry(name: &str, size: u64) {
+    let mut progress = PROGRESS.lock().unwrap();
+    let Some(progress) = progress.as_mut() else {
+        return;
+    };
+    progress.entry = name.to_string();
+    progress.entry_size = size;
+    progress.entry_done = 0;
+    progress.draw();
+}
+
+// Account for `bytes` of file data written.
+pub fn advance(bytes: usize) {
+    let mut progress = PROGRESS.lock().unwrap();
+    let Some(progress) = progress.as_mut() else {
+        return;
+    };
+    progress.done += bytes as u64;
+    progress.entry_done += bytes as u64;
+    progress.draw();
+}
+
+// Erase the bar so that a line can be printed; it is drawn again on the
+// next update.
+pub fn clear() {
+    let mut progress = PROGRESS.lock().unwrap();
+    if let Some(progress) = progress.as_mut().filter(|progress| progress.shown) {
+        let _ = write!(io::stderr(), "\r\x1b[K");
+        progress.shown = false;
+    }
+}
+
+pub fn finish() {
+    clear();
+    *PROGRESS.lock().unwrap() = None;
+}
+
+fn terminal_width() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+        && size.ws_col > 0
+    {
+        size.ws_col as usize
+    } else {
+        80
+    }
+}
+
+fn percent(done: u64, total: u64) -> u64 {
+    if total == 0 {
+        100
+    } else {
+        (done.min(total) as f64 * 100.0 / total as f64) as u64
+    }
+}
+
+// East Asian wide characters take two columns; names are often Japanese.
+fn truncate_to_width(line: &str, width: usize) -> &str {
+    let mut columns = 0;
+    for (index, c) in line.char_indices() {
+        columns += if c >= '\u{1100}' { 2 } else { 1 };
+        if columns > width {
+            return &line[..index];
+        }
+    }
+    line
+}
+
+// "1:05" or "2:03:45".
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+impl Progress {
+    fn sample(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.sample_start);
+        if elapsed < SAMPLE_INTERVAL {
+            return;
+        }
+        let rate = (self.done - self.sample_done) as f64 / elapsed.as_secs_f64();
+        self.rate = Some(match self.rate {
+            Some(average) => SMOOTHING * rate + (1.0 - SMOOTHING) * average,
+            None => rate,
+        });
+        self.sample_start = now;
+        self.sample_done = self.done;
+    }
+
+    fn draw(&mut self) {
+        let now = Instant::now();
+        if self
+            .last_draw
+            .is_some_and(|last| now.duration_since(last) < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+        self.sample(now);
+
+        let filled = (percent(self.done, self.total) as usize * BAR_WIDTH) / 100;
+        let mut line = format!(
+            "[{}{}] {:>3}% {} / {}",
+            "=".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            percent(self.done, self.total),
+            format_size(self.done),
+            format_size(self.total)
+        );
+        if let Some(rate) = self.rate.filter(|rate| *rate > 0.0) {
+            let remaining = self.total.saturating_sub(self.done) as f64 / rate;
+            line.push_str(&format!(
+                ", {}/s, ETA {}",
+                format_size(rate as u64),
+                format_duration(remaining as u64)
+            ));
+        }
+        if !self.entry.is_empty() {
+            line.push_str("  ");
+            if self.entry_size >= LARGE_ENTRY {
+                line.push_str(&format!(
+                    "{:>3}% ({} / {}) ",
+                    percent(self.entry_done, self.entry_size),
+                    format_size(self.entry_done.min(self.entry_size)),
+                    format_size(self.entry_size)
+                ));
+            } else if self.entry_size > 0 {
+                line.push_str(&format!(
+                    "{:>3}% ",
+                    percent(self.entry_done, self.entry_size)
+                ));
+            }
+            line.push_str(&self.entry);
+        }
+        // Stay on one line, or the next redraw cannot erase it.
+        let line = truncate_to_width(&line, terminal_width().saturating_sub(1));
+
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[K{}", line);
+        let _ = stderr.flush();
+        self.shown = true;
+    }
+}
@@ -0,0 +1,274 @@
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use cap_std::fs::Dir;
+use flate2::bufread::DeflateDecoder;
+
+use crate::beneath;
+use crate::control_chars::display_path;
+use crate::encoding::ZipEncoding;
+use crate::ignore::IgnoreRules;
+use crate::interrupt::{InterruptibleReader, ReadError};
+use crate::name_policy::{check_traversal, NamePolicy, Placement};
+use crate::output::{notice, status, Reporter, Style};
+use crate::{copy_through, limits_from_options, sanitize_path, Options, DEFAULT_BUFFER_SIZE};
+
+const LOCAL_HEADER_SIGNATURE: &[u8; 4] = b"PK\x03\x04";
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+
+const FLAG_ENCRYPTED: u16 = 1 << 0;
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+const FLAG_UTF8: u16 = 1 << 11;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+#[derive(Default)]
+pub struct RecoveryReport {
+    pub recovered: usize,
+    pub damaged: Vec<(PathBuf, String)>,
+}
+
+impl RecoveryReport {
+    pub fn print(&self, reporter: &Reporter) {
+        notice!(
+            reporter,
+            "Recovery report: {} entries recovered, {} damaged",
+            self.recovered,
+            self.damaged.len()
+        );
+        for (path, reason) in &self.damaged {
+            notice!(reporter, "  {}: {}", display_path(path), reason);
+        }
+    }
+}
+
+struct LocalHeader {
+    flags: u16,
+    method: u16,
+    crc32: u32,
+    compressed_size: u32,
+    name: Vec<u8>,
+    data_start: u64,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+// Find the next local file header signature at or after `pos`.
+fn find_signature<R: Read + Seek>(reader: &mut R, pos: u64) -> io::Result<Option<u64>> {
+    let sig_len = LOCAL_HEADER_SIGNATURE.len();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut base = pos;
+    reader.seek(SeekFrom::Start(pos))?;
+    let mut filled = 0usize;
+    loop {
+        let length = reader.read(&mut buf[filled..])?;
+        if length == 0 {
+            return Ok(None);
+        }
+        filled += length;
+        if let Some(i) = buf[..filled]
+            .windows(sig_len)
+            .position(|window| window == LOCAL_HEADER_SIGNATURE)
+        {
+            return Ok(Some(base + i as u64));
+        }
+        // Keep the tail in case the signature straddles two reads.
+        let keep = filled.min(sig_len - 1);
+        buf.copy_within(filled - keep..filled, 0);
+        base += (filled - keep) as u64;
+        filled = keep;
+    }
+}
+
+fn read_local_header<R: Read + Seek>(reader: &mut R, start: u64) -> io::Result<LocalHeader> {
+    let mut fixed = [0u8; 30];
+    reader.seek(SeekFrom::Start(start))?;
+    reader.read_exact(&mut fixed)?;
+    let name_length = read_u16(&fixed, 26) as usize;
+    let extra_length = read_u16(&fixed, 28) as u64;
+    let mut name = vec![0u8; name_length];
+    reader.read_exact(&mut name)?;
+    Ok(LocalHeader {
+        flags: read_u16(&fixed, 6),
+        method: read_u16(&fixed, 8),
+        crc32: read_u32(&fixed, 14),
+        compressed_size: read_u32(&fixed, 18),
+        name,
+        data_start: start + fixed.len() as u64 + name_length as u64 + extra_length,
+    })
+}
+
+// The CRC from the data descriptor following the entry data, whose
+// signature is optional.
+fn read_descriptor_crc<R: Read + Seek>(reader: &mut R, pos: u64) -> io::Result<u32> {
+    let mut bytes = [0u8; 8];
+    reader.seek(SeekFrom::Start(pos))?;
+    reader.read_exact(&mut bytes)?;
+    if read_u32(&bytes, 0) == DATA_DESCRIPTOR_SIGNATURE {
+        Ok(read_u32(&bytes, 4))
+    } else {
+        Ok(read_u32(&bytes, 0))
+    }
+}
+
+// Without an explicit encoding, each name is tried as UTF-8 and Shift_JIS
+// before falling back to CP437, like detect_filename_encoding.
+fn decode_name(name: &[u8], flags: u16, encoding: Option<ZipEncoding>) -> String {
+    if flags & FLAG_UTF8 != 0 {
+        return String::from_utf8_lossy(name).into_owned();
+    }
+    if let Some(encoding) = encoding {
+        return encoding.decode_lossy(name);
+    }
+    [encoding_rs::UTF_8, encoding_rs::SHIFT_JIS]
+        .iter()
+        .find_map(|candidate| {
+            let (decoded, _, malformed) = candidate.decode(name);
+            (!malformed).then(|| decoded.into_owned())
+        })
+        .unwrap_or_else(|| ZipEncoding::Cp437.decode_lossy(name))
+}
+
+// Extract whatever can be found by walking the local file headers, for
+// archives whose central directory is missing or unreadable. Only names and
+// file data are recovered.
+pub fn salvage<R: BufRead + Seek>(
+    reader: &mut R,
+    dst_root: &Dir,
+    encoding: Option<ZipEncoding>,
+    ignore_rules: &IgnoreRules,
+    names_taken: &mut NamePolicy,
+    options: &Options,
+) -> Result<RecoveryReport> {
+    let limits = limits_from_options(options);
+    let cancel = &options.cancel;
+    let reporter = &options.reporter;
+    let mut report = RecoveryReport::default();
+    let mut written_total = 0u64;
+    let mut pos = 0u64;
+    let mut entries = 0;
+    while let Some(start) = find_signature(reader, pos)? {
+        pos = start + LOCAL_HEADER_SIGNATURE.len() as u64;
+        entries += 1;
+        limits.check_entries(entries)?;
+        let header = match read_local_header(reader, start) {
+            Ok(header) => header,
+            Err(err) => {
+                let name = PathBuf::from(format!("(header at {})", start));
+                report.damaged.push((name, err.to_string()));
+                break;
+            }
+        };
+        let decoded_name = decode_name(&header.name, header.flags, encoding);
+        let sanitized = sanitize_path(Path::new(&decoded_name));
+        check_traversal(options, Path::new(&decoded_name), sanitized.as_deref())?;
+        let Some(entry_name) = sanitized else {
+            let name = PathBuf::from(format!("(header at {})", start));
+            report.damaged.push((name, "Malformed name".to_string()));
+            continue;
+        };
+        if entry_name == Path::new("") || ignore_rules.is_ignored(&entry_name) {
+            continue;
+        }
+        let path = names_taken.rewrite(&entry_name, &entry_name)?;
+        if decoded_name.ends_with('/') {
+            status!(
+                reporter,
+                Style::Plain,
+                "Extract",
+                "{}",
+                display_path(&entry_name)
+            );
+            dst_root.create_dir_all(&path)?;
+            report.recovered += 1;
+            pos = header.data_start;
+            continue;
+        }
+
+        let has_descriptor = header.flags & FLAG_DATA_DESCRIPTOR != 0;
+        let compressed_size = (!has_descriptor && header.compressed_size != u32::MAX)
+            .then_some(header.compressed_size as u64);
+        let problem = if header.flags & FLAG_ENCRYPTED != 0 {
+            Some("Encrypted entry")
+        } else if header.method == METHOD_STORED && compressed_size.is_none() {
+            Some("Stored entry of unknown size")
+        } else if header.method != METHOD_STORED && header.method != METHOD_DEFLATED {
+            Some("Unsupported compression method")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            report.damaged.push((entry_name, problem.to_string()));
+            continue;
+        }
+        let path = match names_taken.place(&entry_name, path)? {
+            Placement::New(path) => path,
+            // Created with O_TRUNC, so the file needs no removing.
+            Placement::Overwrite(path) => path,
+            Placement::Skip => continue,
+        };
+
+        status!(
+            reporter,
+            Style::Plain,
+            "Extract",
+            "{}",
+            display_path(&entry_name)
+        );
+        if let Some(parent) = path.parent() {
+            dst_root.create_dir_all(parent)?;
+        }
+        let mut outfile = beneath::create_file(dst_root, &path)?;
+        let limit = limits.entry_limit(written_total, compressed_size.unwrap_or(0));
+        reader.seek(SeekFrom::Start(header.data_start))?;
+        let mut data = reader.by_ref().take(compressed_size.unwrap_or(u64::MAX));
+        let mut copy = |reader: &mut dyn Read| {
+            let reader = InterruptibleReader::new(reader, limit, cancel).with_reporter(reporter);
+            copy_through(reader, &mut outfile, DEFAULT_BUFFER_SIZE)
+        };
+        let (outcome, consumed) = if header.method == METHOD_STORED {
+            let outcome = copy(&mut data);
+            (outcome, compressed_size.unwrap())
+        } else {
+            let mut decoder = DeflateDecoder::new(data);
+            let outcome = copy(&mut decoder);
+            (outcome, decoder.total_in())
+        };
+        if cancel.is_cancelled() {
+            bail!("Interrupted");
+        }
+        let outcome = match outcome {
+            Ok(outcome) => outcome,
+            Err(err) if ReadError::find(&err).is_some() => {
+                written_total += ReadError::find(&err).unwrap().read;
+                report.damaged.push((entry_name, err.to_string()));
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        written_total += outcome.written;
+        pos = header.data_start + consumed;
+
+        let expected_crc32 = if has_descriptor {
+            read_descriptor_crc(reader, pos).ok()
+        } else {
+            Some(header.crc32)
+        };
+        if expected_crc32 == Some(outcome.crc32) {
+            report.recovered += 1;
+        } else {
+            report
+                .damaged
+                .push((entry_name, "CRC mismatch".to_string()));
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Seek, SeekFrom, Write as _};
+use std::os::unix::ffi::OsStringExt as _;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, Once};
+
+use anyhow::{Context as _, Result};
+use tempfile::TempDir;
+
+#[cfg(feature = "object-storage")]
+use crate::object_storage;
+use crate::output::say;
+use crate::tempfile_utils::tempdir_with_prefix_in;
+
+const DOWNLOAD_PREFIX: &str = ".exzip-download-";
+// Range requests fetch this much at first, doubling while reads are
+// sequential, and this many of the ranges fetched are kept.
+const MIN_FETCH_SIZE: u64 = 64 * 1024;
+const MAX_FETCH_SIZE: u64 = 8 * 1024 * 1024;
+const CACHED_RANGES: usize = 16;
+
+// Removed at exit, which every command ends with.
+static DOWNLOADS: Mutex<Vec<TempDir>> = Mutex::new(Vec::new());
+
+pub fn is_url(path: &Path) -> bool {
+    #[cfg(feature = "object-storage")]
+    if object_storage::is_object_url(path) {
+        return true;
+    }
+    let path = path.as_os_str().as_encoded_bytes();
+    path.starts_with(b"https://") || path.starts_with(b"http://")
+}
+
+// What curl is run with for a URL: the HTTP(S) URL and lines of a config
+// file, given on stdin to keep credentials out of the process list.
+pub struct Request {
+    pub url: OsString,
+    pub config: String,
+}
+
+fn request(url: &Path) -> io::Result<Request> {
+    #[cfg(feature = "object-storage")]
+    if object_storage::is_object_url(url) {
+        return object_storage::request(url);
+    }
+    Ok(Request {
+        url: url.as_os_str().to_os_string(),
+        config: String::new(),
+    })
+}
+
+// Run curl for `request` and return what it wrote to stdout.
+fn curl(
+    request: &Request,
+    args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+) -> io::Result<Vec<u8>> {
+    let mut child = Command::new("curl")
+        .args([
+            "--fail",
+            "--location",
+            "--silent",
+            "--show-error",
+            "--config",
+            "-",
+        ])
+        .args(args)
+        .arg(&request.url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let config = child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(request.config.as_bytes());
+    let output = child.wait_with_output()?;
+    config?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "Failed to fetch {}",
+            request.url.to_string_lossy()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+// The last segment of the URL's path, percent-decoded.
+pub fn url_file_name(url: &Path) -> PathBuf {
+    let url = url.as_os_str().as_encoded_bytes();
+    let url = url.split(|&b| b == b'?' || b == b'#').next().unwrap();
+    let name = url.rsplit(|&b| b == b'/').next().unwrap();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < name.len() {
+        let hex = name
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) if name[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(name[i]);
+                i += 1;
+            }
+        }
+    }
+    if decoded.is_empty() || decoded.contains(&b'/') || decoded == b"." || decoded == b".." {
+        return PathBuf::from("download");
+    }
+    PathBuf::from(OsString::from_vec(decoded))
+}
+
+// Whether `path` was downloaded by download(), in this process or in the
+// parent of --jobs.
+pub fn is_download(path: &Path) -> bool {
+    let dir = path.parent();
+    dir.and_then(Path::file_name).is_some_and(|name| {
+        name.as_encoded_bytes()
+            .starts_with(DOWNLOAD_PREFIX.as_bytes())
+    }) && dir.and_then(Path::parent) == Some(std::env::temp_dir().as_path())
+}
+
+extern "C" fn remove_downloads() {
+    if let Ok(mut downloads) = DOWNLOADS.lock() {
+        downloads.clear();
+    }
+}
+
+// Download `url` to a temporary directory, keeping its file name, and
+// return the path of the file.
+pub fn download(url: &Path) -> Result<PathBuf> {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe {
+        libc::atexit(remove_downloads);
+    });
+    let request = request(url)?;
+    let dir = tempdir_with_prefix_in(&std::env::temp_dir(), DOWNLOAD_PREFIX)?;
+    let path = dir.path().join(url_file_name(url));
+    DOWNLOADS.lock().unwrap().push(dir);
+
+    say!("Downloading {}", url.display());
+    curl(&request, [OsStr::new("--output"), path.as_os_str()])
+        .with_context(|| format!("Failed to download {}", url.display()))?;
+    Ok(path)
+}
+
+// Reads a URL with HTTP range requests, so that only the parts of an
+// archive looked at are transferred.
+pub struct RemoteFile {
+    request: Request,
+    len: u64,
+    pos: u64,
+    // The ranges fetched, with their starts, the latest first.
+    ranges: VecDeque<(u64, Vec<u8>)>,
+}
+
+impl RemoteFile {
+    pub fn open(url: &Path) -> io::Result<Self> {
+        let request = request(url)?;
+        // The total length is in the Content-Range of the last response,
+        // after any redirects.
+        let headers = curl(
+            &request,
+            [
+                "--range",
+                "0-0",
+                "--dump-header",
+                "-",
+                "--output",
+                "/dev/null",
+            ],
+        )?;
+        let len = String::from_utf8_lossy(&headers)
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(name, _)| name.eq_ignore_ascii_case("content-range"))
+            .filter_map(|(_, value)| value.trim().rsplit_once('/')?.1.parse().ok())
+            .next_back()
+            .ok_or_else(|| {
+                io::Error::other(format!("{} does not support range requests", url.display()))
+            })?;
+        Ok(RemoteFile {
+            request,
+            len,
+            pos: 0,
+            ranges: VecDeque::new(),
+        })
+    }
+
+    // Make the range containing `pos` the first one, fetching it if needed.
+    fn fetch(&mut self, pos: u64) -> io::Result<()> {
+        let contains =
+            |(start, data): &(u64, Vec<u8>)| (*start..*start + data.len() as u64).contains(&pos);
+        if let Some(i) = self.ranges.iter().position(contains) {
+            let range = self.ranges.remove(i).unwrap();
+            self.ranges.push_front(range);
+            return Ok(());
+        }
+        let size = match self.ranges.front() {
+            Some((start, data)) if start + data.len() as u64 == pos => {
+                (data.len() as u64 * 2).min(MAX_FETCH_SIZE)
+            }
+            _ => MIN_FETCH_SIZE,
+        };
+        let end = (pos + size).min(self.len) - 1;
+        let data = curl(&self.request, ["--range", &format!("{}-{}", pos, end)])?;
+        if data.len() as u64 != end - pos + 1 {
+            return Err(io::Error::other(format!(
+                "{} returned a wrong range",
+                self.request.url.to_string_lossy()
+            )));
+        }
+        self.ranges.truncate(CACHED_RANGES - 1);
+        self.ranges.push_front((pos, data));
+        Ok(())
+    }
+}
+
+impl Read for RemoteFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        self.fetch(self.pos)?;
+        let (start, data) = &self.ranges[0];
+        let offset = (self.pos - start) as usize;
+        let len = buf.len().min(data.len() - offset);
+        buf[..len].copy_from_slice(&data[offset..offset + len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for RemoteFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = pos.ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,108 @@
+use std::fs::Permissions;
+use std::io::{self, Write as _};
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tempfile::NamedTempFile;
+
+use crate::json;
+use crate::name_map::NameMapEntry;
+
+// What happened to one archive, for --report.
+pub struct ArchiveReport<'a> {
+    pub zipfile: &'a Path,
+    pub target_path: &'a Path,
+    pub encoding: &'static str,
+    pub inner_root: &'a Path,
+    pub entries: &'a [NameMapEntry],
+    pub timings: &'a [(&'static str, Duration)],
+}
+
+// A JSON array with an object per archive. The whole file is replaced
+// after each archive, so that it is complete even if a later one aborts.
+pub struct Report {
+    path: PathBuf,
+    archives: Vec<String>,
+}
+
+fn disposition(entry: &NameMapEntry) -> &'static str {
+    match (&entry.path, entry.intact) {
+        (_, Some(false)) => "failed",
+        (Some(_), _) => "extracted",
+        (None, _) => "skipped",
+    }
+}
+
+impl Report {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let report = Report {
+            path: path.to_path_buf(),
+            archives: Vec::new(),
+        };
+        report.write()?;
+        Ok(report)
+    }
+
+    pub fn add(&mut self, archive: &ArchiveReport) -> io::Result<()> {
+        let entries = archive.entries.iter().map(|entry| {
+            let mut object = json::Object::new()
+                .str("name", &entry.name.to_string_lossy())
+                .str("disposition", disposition(entry));
+            if let Some(path) = &entry.path {
+                let path = match path {
+                    path if path == Path::new(".") => archive.target_path.to_path_buf(),
+                    path => archive.target_path.join(path),
+                };
+                object = object.str("path", &path.to_string_lossy());
+            }
+            object = object.num("size", entry.size);
+            if let Some(intact) = entry.intact {
+                object = object.bool("crc_ok", intact);
+            }
+            object.finish()
+        });
+        let timings = archive
+            .timings
+            .iter()
+            .fold(json::Object::new(), |object, (phase, time)| {
+                object.raw(phase, &format!("{:.6}", time.as_secs_f64()))
+            });
+        let object = json::Object::new()
+            .str("archive", &archive.zipfile.to_string_lossy())
+            .str("target", &archive.target_path.to_string_lossy())
+            .str("encoding", archive.encoding)
+            .str("inner_root", &archive.inner_root.to_string_lossy())
+            .raw("entries", &json::array(entries))
+            .raw("timings", &timings.finish());
+        self.archives.push(object.finish());
+        self.write()
+    }
+
+    pub fn add_failure(&mut self, zipfile: &Path, error: &str) -> io::Result<()> {
+        let object = json::Object::new()
+            .str("archive", &zipfile.to_string_lossy())
+            .str("error", error);
+        self.archives.push(object.finish());
+        self.write()
+    }
+
+    fn write(&self) -> io::Result<()> {
+        let parent = match self.path.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        let mut file = NamedTempFile::new_in(parent)?;
+        writeln!(file, "[")?;
+        for (i, archive) in self.archives.iter().enumerate() {
+            let separator = if i + 1 < self.archives.len() { "," } else { "" };
+            writeln!(file, "{}{}", archive, separator)?;
+        }
+        writeln!(file, "]")?;
+        // Temporary files are private; a report is not.
+        file.as_file()
+            .set_permissions(Permissions::from_mode(0o644))?;
+        file.persist(&self.path)?;
+        Ok(())
+    }
+}
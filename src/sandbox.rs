@@ -0,0 +1,121 @@
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+
+use anyhow::{bail, Context as _, Result};
+
+// Access rights from <linux/landlock.h>.
+const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const ACCESS_FS_V1: u64 = (1 << 13) - 1;
+const ACCESS_FS_REFER: u64 = 1 << 13;
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+const CREATE_RULESET_VERSION: u32 = 1 << 0;
+const RULE_PATH_BENEATH: libc::c_int = 1;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+fn abi_version() -> io::Result<i64> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0usize,
+            CREATE_RULESET_VERSION,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ret)
+}
+
+fn add_rule(ruleset: &OwnedFd, path: &Path, allowed_access: u64) -> io::Result<()> {
+    let parent = File::open(path)?;
+    let attr = PathBeneathAttr {
+        allowed_access,
+        parent_fd: parent.as_raw_fd(),
+    };
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset.as_raw_fd(),
+            RULE_PATH_BENEATH,
+            &attr as *const PathBeneathAttr,
+            0u32,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Restrict this process with Landlock so that the whole filesystem stays
+// readable but only the directories in `writable_dirs` can be modified.
+// The restriction is irreversible and inherited by child processes.
+pub fn restrict_writes(writable_dirs: &[&Path]) -> Result<()> {
+    let abi = match abi_version() {
+        Ok(abi) => abi,
+        Err(err)
+            if matches!(
+                err.raw_os_error(),
+                Some(libc::ENOSYS) | Some(libc::EOPNOTSUPP)
+            ) =>
+        {
+            bail!("Landlock is not available on this system")
+        }
+        Err(err) => return Err(err).context("Failed to query Landlock"),
+    };
+
+    // Rename and link across directories are always denied with ABI v1.
+    let mut handled_access_fs = ACCESS_FS_V1;
+    if abi >= 2 {
+        handled_access_fs |= ACCESS_FS_REFER;
+    }
+    if abi >= 3 {
+        handled_access_fs |= ACCESS_FS_TRUNCATE;
+    }
+
+    let attr = RulesetAttr { handled_access_fs };
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr as *const RulesetAttr,
+            std::mem::size_of::<RulesetAttr>(),
+            0u32,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error()).context("Failed to create Landlock ruleset");
+    }
+    let ruleset = unsafe { OwnedFd::from_raw_fd(ret as i32) };
+
+    let read_access = ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+    add_rule(&ruleset, Path::new("/"), read_access).context("Failed to add Landlock rule")?;
+    for dir in writable_dirs {
+        add_rule(&ruleset, dir, handled_access_fs & !ACCESS_FS_EXECUTE)
+            .with_context(|| format!("Failed to add Landlock rule for {}", dir.display()))?;
+    }
+
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(io::Error::last_os_error()).context("Failed to set no_new_privs");
+    }
+    let ret = unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error()).context("Failed to enforce Landlock ruleset");
+    }
+    Ok(())
+}
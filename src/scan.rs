@@ -0,0 +1,131 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::ignore::{glob_match, matches_pattern};
+use crate::stale::STAGING_PREFIX;
+
+// A local file header, or the end of central directory of an empty archive.
+const ZIP_SIGNATURES: [[u8; 4]; 2] = [*b"PK\x03\x04", *b"PK\x05\x06"];
+
+// Zip files under other extensions, such as .jar or .cbz, are found by their
+// signature. A file without an extension would be its own target.
+fn is_archive(path: &Path) -> bool {
+    let Some(extension) = path.extension() else {
+        return false;
+    };
+    if extension.eq_ignore_ascii_case("zip") {
+        return true;
+    }
+    let mut signature = [0; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut signature))
+        .is_ok_and(|()| ZIP_SIGNATURES.contains(&signature))
+}
+
+// The archives under `dir`, sorted. `include` and `exclude` are globs
+// matched against paths relative to `dir` as ignore patterns are; an
+// excluded directory is not entered. Symbolic links are not followed.
+pub fn find_archives(
+    dir: &Path,
+    include: &[String],
+    exclude: &[String],
+) -> io::Result<Vec<PathBuf>> {
+    let mut archives = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            let path = entry.path();
+            let relative = path.strip_prefix(dir).unwrap();
+            if exclude
+                .iter()
+                .any(|pattern| matches_pattern(pattern, relative))
+            {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                // Extractions in progress.
+                if !entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(STAGING_PREFIX)
+                {
+                    dirs.push(path);
+                }
+            } else if file_type.is_file()
+                && (include.is_empty()
+                    || include
+                        .iter()
+                        .any(|pattern| matches_pattern(pattern, relative)))
+                && is_archive(&path)
+            {
+                archives.push(path);
+            }
+        }
+    }
+    archives.sort();
+    Ok(archives)
+}
+
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+// As glob_match, with `**/` matching no directory as well, as in shells.
+fn glob_match_path(pattern: &str, text: &str) -> bool {
+    glob_match(pattern, text)
+        || pattern
+            .match_indices("**/")
+            .any(|(i, _)| glob_match_path(&format!("{}{}", &pattern[..i], &pattern[i + 3..]), text))
+}
+
+// Files matching a glob such as `downloads/**/*.zip`, sorted, for globs the
+// shell did not expand. Only the directories the glob can reach are read.
+pub fn expand_glob(pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let components: Vec<&str> = pattern.split('/').collect();
+    let literal = components
+        .iter()
+        .position(|component| is_glob(component))
+        .unwrap_or(components.len());
+    let base = match components[..literal].join("/") {
+        base if base.is_empty() && pattern.starts_with('/') => "/".to_string(),
+        base if base.is_empty() => ".".to_string(),
+        base => base,
+    };
+    let rest = components[literal..].join("/");
+    let max_depth = if rest.contains("**") {
+        usize::MAX
+    } else {
+        components.len() - literal
+    };
+
+    let mut files = Vec::new();
+    if !Path::new(&base).is_dir() {
+        return Ok(files);
+    }
+    let mut dirs = vec![(PathBuf::from(&base), 1)];
+    while let Some((current, depth)) = dirs.pop() {
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() && depth < max_depth {
+                dirs.push((path, depth + 1));
+            } else if file_type.is_file() {
+                let relative = path.strip_prefix(&base).unwrap();
+                if glob_match_path(&rest, &relative.to_string_lossy()) {
+                    // Written as the glob was, without "./" in front.
+                    files.push(if literal == 0 && !pattern.starts_with('/') {
+                        relative.to_path_buf()
+                    } else {
+                        path
+                    });
+                }
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
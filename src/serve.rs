@@ -0,0 +1,316 @@
+use std::fs;
+use std::io::{self, BufRead as _, BufReader, Write as _};
+use std::os::unix::fs::{FileTypeExt as _, PermissionsExt as _};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context as _, Result};
+use clap::ValueEnum as _;
+use nix::unistd::geteuid;
+use rustix::net::sockopt::get_socket_peercred;
+
+use crate::interrupt::{interrupted, CancellationToken};
+use crate::json::{self, Value};
+use crate::observer::ExtractObserver;
+use crate::output::{error, error_message, notice};
+use crate::{ConflictPolicy, ExtractOptions, Extractor};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// Bytes written are reported at most this often.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// JSON-RPC 2.0 error codes.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const EXTRACTION_FAILED: i64 = -32000;
+
+// The sending half of a connection. A failed write means the client is
+// gone, which cancels its extraction.
+struct Connection {
+    stream: Mutex<UnixStream>,
+    cancel: CancellationToken,
+}
+
+impl Connection {
+    fn send(&self, message: json::Object) {
+        let mut line = message.finish();
+        line.push('\n');
+        if self
+            .stream
+            .lock()
+            .unwrap()
+            .write_all(line.as_bytes())
+            .is_err()
+        {
+            self.cancel.cancel();
+        }
+    }
+
+    fn notify(&self, method: &str, params: json::Object) {
+        self.send(
+            json::Object::new()
+                .str("jsonrpc", "2.0")
+                .str("method", method)
+                .raw("params", &params.finish()),
+        );
+    }
+
+    fn reply(&self, id: &Value, result: Result<json::Object, (i64, String)>) {
+        let message = json::Object::new()
+            .str("jsonrpc", "2.0")
+            .raw("id", &id.to_json());
+        self.send(match result {
+            Ok(result) => message.raw("result", &result.finish()),
+            Err((code, text)) => message.raw(
+                "error",
+                &json::Object::new()
+                    .raw("code", &code.to_string())
+                    .str("message", &text)
+                    .finish(),
+            ),
+        });
+    }
+}
+
+// Streams what happens during an extraction to the client as notifications
+// carrying the id of the request.
+struct Reporter {
+    connection: Arc<Connection>,
+    id: String,
+    written: Mutex<(u64, Option<Instant>)>,
+}
+
+impl Reporter {
+    fn params(&self) -> json::Object {
+        json::Object::new().raw("id", &self.id)
+    }
+}
+
+impl ExtractObserver for Reporter {
+    fn entry_started(&self, name: &Path, size: u64) {
+        self.connection.notify(
+            "entry-start",
+            self.params()
+                .str("name", &name.to_string_lossy())
+                .num("size", size),
+        );
+    }
+
+    fn bytes_written(&self, bytes: u64) {
+        if interrupted() {
+            self.connection.cancel.cancel();
+        }
+        let mut written = self.written.lock().unwrap();
+        written.0 += bytes;
+        if written
+            .1
+            .is_some_and(|last| last.elapsed() < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+        written.1 = Some(Instant::now());
+        self.connection
+            .notify("progress", self.params().num("written", written.0));
+    }
+
+    fn entry_skipped(&self, name: &Path, reason: &str) {
+        self.connection.notify(
+            "skip",
+            self.params()
+                .str("name", &name.to_string_lossy())
+                .str("reason", reason),
+        );
+    }
+
+    fn warning(&self, message: &str) {
+        self.connection
+            .notify("warning", self.params().str("message", message));
+    }
+}
+
+fn absolute_path(params: &Value, key: &str) -> Result<Option<PathBuf>, String> {
+    let Some(value) = params.get(key) else {
+        return Ok(None);
+    };
+    let path = value
+        .as_str()
+        .map(PathBuf::from)
+        .ok_or_else(|| format!("{} must be a string", key))?;
+    // Relative to what the client has in mind, not the server.
+    if !path.is_absolute() {
+        return Err(format!("{} must be an absolute path", key));
+    }
+    Ok(Some(path))
+}
+
+fn extract_options(params: &Value) -> Result<(PathBuf, ExtractOptions), String> {
+    let archive = absolute_path(params, "archive")?.ok_or("archive is required")?;
+    let mut options = ExtractOptions::default();
+    if let Some(destination) = absolute_path(params, "destination")? {
+        options = options.destination(destination);
+    }
+    if let Some(encoding) = params.get("encoding") {
+        let encoding = encoding.as_str().ok_or("encoding must be a string")?;
+        options = options
+            .encoding(encoding)
+            .ok_or_else(|| format!("Unknown encoding {}", encoding))?;
+    }
+    if let Some(policy) = params.get("on_conflict") {
+        let policy = policy
+            .as_str()
+            .and_then(|policy| ConflictPolicy::from_str(policy, false).ok())
+            .ok_or("on_conflict must be replace, merge, skip, number or backup")?;
+        options = options.on_conflict(policy);
+    }
+    if let Some(threads) = params.get("threads") {
+        let threads = threads.as_u64().ok_or("threads must be a number")?;
+        options = options.threads(threads as usize);
+    }
+    if let Some(keep_junk) = params.get("keep_junk") {
+        options = options.keep_junk(keep_junk.as_bool().ok_or("keep_junk must be a boolean")?);
+    }
+    if let Some(patterns) = params.get("ignore_patterns") {
+        let patterns: Option<Vec<&str>> = match patterns {
+            Value::Array(patterns) => patterns.iter().map(Value::as_str).collect(),
+            _ => None,
+        };
+        options = options
+            .ignore_patterns(&patterns.ok_or("ignore_patterns must be an array of strings")?);
+    }
+    Ok((archive, options))
+}
+
+fn extract(
+    connection: &Arc<Connection>,
+    id: &Value,
+    params: &Value,
+) -> Result<json::Object, (i64, String)> {
+    let (archive, options) =
+        extract_options(params).map_err(|message| (INVALID_PARAMS, message))?;
+    let reporter = Reporter {
+        connection: Arc::clone(connection),
+        id: id.to_json(),
+        written: Mutex::new((0, None)),
+    };
+    let options = options
+        .cancellation(connection.cancel.clone())
+        .observer(Arc::new(reporter));
+    match Extractor::new(options).extract(&archive) {
+        Ok(Some(extracted)) => {
+            let mut result = json::Object::new()
+                .str("target", &extracted.target_path.to_string_lossy())
+                .num("entries", extracted.entries as u64)
+                .bool("skipped", false);
+            if let Some(encoding) = extracted.encoding {
+                result = result.str("encoding", encoding);
+            }
+            Ok(result)
+        }
+        Ok(None) => Ok(json::Object::new().bool("skipped", true)),
+        Err(err) => Err((EXTRACTION_FAILED, error_message(&err))),
+    }
+}
+
+// Each line from the client is a JSON-RPC request; the one method is
+// "extract".
+fn serve_connection(stream: UnixStream) -> io::Result<()> {
+    let connection = Arc::new(Connection {
+        stream: Mutex::new(stream.try_clone()?),
+        cancel: CancellationToken::new(),
+    });
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(request) = json::parse(&line) else {
+            connection.reply(&Value::Null, Err((PARSE_ERROR, "Parse error".to_string())));
+            continue;
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let params = request
+            .get("params")
+            .cloned()
+            .unwrap_or(Value::Object(Vec::new()));
+        let result = match request.get("method").and_then(Value::as_str) {
+            Some("extract") => extract(&connection, &id, &params),
+            Some(method) => Err((METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+            None => Err((METHOD_NOT_FOUND, "No method".to_string())),
+        };
+        // Notifications, without an id, get no reply.
+        if request.get("id").is_some() {
+            connection.reply(&id, result);
+        }
+        if connection.cancel.is_cancelled() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Requests run with the server's privileges, so only the user running it
+// may make them: the socket is accessible to its owner alone, and since
+// not every system honours that for sockets, connections from other users
+// are also refused.
+fn is_own_user(stream: &UnixStream) -> io::Result<bool> {
+    Ok(get_socket_peercred(stream)?.uid.as_raw() == geteuid().as_raw())
+}
+
+// A socket left behind by a server that is no longer running is replaced.
+fn bind(socket: &Path) -> Result<UnixListener> {
+    if let Ok(metadata) = fs::symlink_metadata(socket) {
+        if !metadata.file_type().is_socket() {
+            bail!("{} exists and is not a socket", socket.display());
+        }
+        if UnixStream::connect(socket).is_ok() {
+            bail!("{} is in use by another server", socket.display());
+        }
+        fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+// Take extraction requests on the Unix socket `socket` until interrupted.
+pub fn serve(socket: &Path) -> Result<()> {
+    let listener = bind(socket)?;
+    listener.set_nonblocking(true)?;
+    notice!("Listening on {}", socket.display());
+    while !interrupted() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                match is_own_user(&stream) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        notice!("Refused a connection from another user");
+                        continue;
+                    }
+                    Err(err) => {
+                        error!("{}", err);
+                        continue;
+                    }
+                }
+                stream.set_nonblocking(false)?;
+                thread::spawn(move || {
+                    if let Err(err) = serve_connection(stream) {
+                        error!("{}", err);
+                    }
+                });
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => {
+                let _ = fs::remove_file(socket);
+                return Err(err.into());
+            }
+        }
+    }
+    fs::remove_file(socket)?;
+    Ok(())
+}
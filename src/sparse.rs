@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::fs::{FileExt as _, MetadataExt as _};
+
+// Writes to a new file at increasing offsets, leaving a hole for each
+// filesystem block of zeros instead of writing it. The runs of data between
+// holes are written at once. finish() sets the length, in case the file ends
+// in a hole.
+pub struct SparseWriter<'a> {
+    file: &'a File,
+    block_size: u64,
+    pos: u64,
+}
+
+impl<'a> SparseWriter<'a> {
+    pub fn new(file: &'a File) -> io::Result<Self> {
+        let block_size = file.metadata()?.blksize().max(512);
+        Ok(SparseWriter {
+            file,
+            block_size,
+            pos: 0,
+        })
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        if self.file.metadata()?.len() < self.pos {
+            self.file.set_len(self.pos)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for SparseWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The start of the data not written yet.
+        let mut start = 0;
+        let mut offset = 0;
+        while offset < buf.len() {
+            let pos = self.pos + offset as u64;
+            let end = buf
+                .len()
+                .min(offset + (self.block_size - pos % self.block_size) as usize);
+            let block = &buf[offset..end];
+            if block.len() as u64 == self.block_size && block.iter().all(|&b| b == 0) {
+                self.file
+                    .write_all_at(&buf[start..offset], self.pos + start as u64)?;
+                start = end;
+            }
+            offset = end;
+        }
+        self.file
+            .write_all_at(&buf[start..], self.pos + start as u64)?;
+        self.pos += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn write_sparse(chunks: &[&[u8]]) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sparse");
+        let file = File::create(&path).unwrap();
+        let mut writer = SparseWriter::new(&file).unwrap();
+        for chunk in chunks {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish().unwrap();
+        fs::read(&path).unwrap()
+    }
+
+    #[test]
+    fn keeps_the_data_around_holes() {
+        let data: Vec<u8> = [vec![1; 100], vec![0; 64 * 1024], vec![2; 100]].concat();
+        assert_eq!(write_sparse(&[&data]), data);
+    }
+
+    #[test]
+    fn sets_the_length_when_ending_in_a_hole() {
+        let data: Vec<u8> = [vec![1; 10], vec![0; 64 * 1024]].concat();
+        assert_eq!(write_sparse(&[&data]), data);
+    }
+
+    #[test]
+    fn writes_blocks_split_across_writes() {
+        let data: Vec<u8> = (0..200_000).map(|i| (i / 5000 % 2 * i) as u8).collect();
+        let chunks: Vec<&[u8]> = data.chunks(3001).collect();
+        assert_eq!(write_sparse(&chunks), data);
+    }
+}
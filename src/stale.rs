@@ -0,0 +1,93 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use rustix::fs::{flock, FlockOperation};
+
+pub const STAGING_PREFIX: &str = "exzip-";
+
+// Staging directories without a live lock are only considered abandoned
+// after this long, so that runs of older versions are left alone.
+const STALE_AGE: Duration = Duration::from_secs(60 * 60);
+
+fn lock_path(staging_dir: &Path) -> PathBuf {
+    let mut name = OsString::from(staging_dir.as_os_str());
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+// An exclusive lock held next to a staging directory while it is in use.
+// The kernel releases it when the process dies, even by SIGKILL.
+pub struct StagingLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl StagingLock {
+    pub fn acquire(staging_dir: &Path) -> io::Result<Self> {
+        let path = lock_path(staging_dir);
+        let file = File::create(&path)?;
+        flock(&file, FlockOperation::NonBlockingLockExclusive)?;
+        Ok(StagingLock { path, _file: file })
+    }
+}
+
+impl Drop for StagingLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn is_locked(staging_dir: &Path) -> io::Result<bool> {
+    let file = match File::open(lock_path(staging_dir)) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    match flock(&file, FlockOperation::NonBlockingLockShared) {
+        Ok(()) => Ok(false),
+        Err(rustix::io::Errno::WOULDBLOCK) => Ok(true),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Staging directories in `parent` left behind by runs that were killed.
+pub fn find_stale(parent: &Path) -> io::Result<Vec<PathBuf>> {
+    let now = SystemTime::now();
+    let mut stale = Vec::new();
+    for entry in fs::read_dir(parent)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        // tempfile appends six random characters to the prefix.
+        let is_staging_name = name.strip_prefix(STAGING_PREFIX).is_some_and(|suffix| {
+            suffix.len() == 6 && suffix.bytes().all(|b| b.is_ascii_alphanumeric())
+        });
+        if !is_staging_name {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if !metadata.is_dir() {
+            continue;
+        }
+        let age = now.duration_since(metadata.modified()?).unwrap_or_default();
+        if age < STALE_AGE || is_locked(&entry.path())? {
+            continue;
+        }
+        stale.push(entry.path());
+    }
+    stale.sort();
+    Ok(stale)
+}
+
+pub fn remove_stale(staging_dir: &Path) -> io::Result<()> {
+    fs::remove_dir_all(staging_dir)?;
+    match fs::remove_file(lock_path(staging_dir)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,75 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use anyhow::Result;
+
+use crate::interrupt::CancellationToken;
+use crate::{Extracted, Extractor};
+
+#[derive(Default)]
+struct State {
+    result: Option<Result<Option<Extracted>>>,
+    waker: Option<Waker>,
+}
+
+/// An extraction running on a thread of its own, started with
+/// [`Extractor::extract_async`]. Awaiting it gives what
+/// [`Extractor::extract`] returns.
+///
+/// It needs no particular async runtime: rather than being built on tokio's
+/// `spawn_blocking` and `tokio::fs`, the crate does not depend on tokio, and
+/// the task is a plain [`Future`] woken from its thread. Under tokio it
+/// behaves like a `spawn_blocking` handle, keeping the blocking file I/O off
+/// the runtime's workers.
+///
+/// Tasks may overlap, each reporting to the observer of its own
+/// [`Extractor`]; tasks of one `Extractor` share its observer and its
+/// cancellation token. Dropping the task does not stop the extraction;
+/// [`cancel`](Self::cancel) does.
+pub struct ExtractTask {
+    state: Arc<Mutex<State>>,
+    cancel: CancellationToken,
+}
+
+impl ExtractTask {
+    pub(crate) fn spawn(extractor: Extractor, zipfile: PathBuf) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let cancel = extractor.cancellation_token().clone();
+        let task_state = Arc::clone(&state);
+        thread::spawn(move || {
+            let result = extractor.extract(&zipfile);
+            let mut state = task_state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        ExtractTask { state, cancel }
+    }
+
+    /// Stop the extraction, which then fails with "Interrupted". This
+    /// cancels the token of the [`ExtractOptions`](crate::ExtractOptions)
+    /// too.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+}
+
+impl Future for ExtractTask {
+    type Output = Result<Option<Extracted>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::interrupt::CancellationToken;
+
+// A token bucket shared by every thread writing file data (--bwlimit).
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+static BUCKET: OnceLock<Mutex<Bucket>> = OnceLock::new();
+
+// Allow up to `rate` bytes per second, with bursts of up to one second.
+pub fn set_limit(rate: u64) {
+    let rate = rate.max(1) as f64;
+    let _ = BUCKET.set(Mutex::new(Bucket {
+        rate,
+        tokens: rate,
+        last: Instant::now(),
+    }));
+}
+
+// Account for `bytes` written, sleeping while over the limit.
+pub fn throttle(bytes: usize, cancel: &CancellationToken) {
+    let Some(bucket) = BUCKET.get() else {
+        return;
+    };
+    let wait = {
+        let mut bucket = bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * bucket.rate).min(bucket.rate);
+        bucket.last = now;
+        // Going into debt makes later writers, on any thread, wait too.
+        bucket.tokens -= bytes as f64;
+        if bucket.tokens >= 0.0 {
+            return;
+        }
+        Duration::from_secs_f64(-bucket.tokens / bucket.rate)
+    };
+    // Sleep in slices so that cancellation is noticed.
+    let until = Instant::now() + wait;
+    while !cancel.is_cancelled() {
+        let now = Instant::now();
+        if now >= until {
+            break;
+        }
+        thread::sleep((until - now).min(Duration::from_millis(100)));
+    }
+}
@@ -0,0 +1,115 @@
+use std::env;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::fs::DirBuilderExt as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use chrono::Local;
+
+use crate::unique_path;
+
+fn home_dir() -> Result<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .context("HOME is not set")
+}
+
+// Percent-encoded as the Path key of a .trashinfo file wants it.
+fn percent_encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+// The home trash of the freedesktop.org specification, or ~/.Trash on macOS.
+fn trash_dir() -> Result<PathBuf> {
+    if cfg!(target_os = "macos") {
+        return Ok(home_dir()?.join(".Trash"));
+    }
+    let data_home = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".local").join("share"),
+    };
+    Ok(data_home.join("Trash"))
+}
+
+// Move `path` to the trash of the user (--remove-archive=trash). Being a
+// rename, this fails for files on another filesystem.
+pub fn move_to_trash(path: &Path) -> Result<()> {
+    let path = fs::canonicalize(path)?;
+    let name = Path::new(path.file_name().context("Not a file")?);
+    let trash = trash_dir()?;
+
+    if cfg!(target_os = "macos") {
+        let mut destination = trash.join(name);
+        if destination.exists() {
+            destination = unique_path(&destination, |candidate| candidate.exists());
+        }
+        return fs::rename(&path, &destination)
+            .with_context(|| format!("Failed to move {} to the trash", path.display()));
+    }
+
+    let files = trash.join("files");
+    let info = trash.join("info");
+    for dir in [&files, &info] {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+
+    // The info file is created first to claim the name.
+    let info_path = |name: &Path| {
+        let mut info_name = name.as_os_str().to_os_string();
+        info_name.push(".trashinfo");
+        info.join(info_name)
+    };
+    let mut trashed = files.join(name);
+    let (info_path, mut info_file) = loop {
+        let candidate = info_path(Path::new(trashed.file_name().unwrap()));
+        if !trashed.exists() {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&candidate)
+            {
+                Ok(file) => break (candidate, file),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(err) => {
+                    return Err(err)
+                        .with_context(|| format!("Failed to create {}", candidate.display()))
+                }
+            }
+        }
+        trashed = unique_path(&files.join(name), |candidate| {
+            candidate.exists() || info_path(Path::new(candidate.file_name().unwrap())).exists()
+        });
+    };
+    let written = write!(
+        info_file,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(&path),
+        Local::now().format("%Y-%m-%dT%H:%M:%S")
+    );
+    let moved = written
+        .context("Failed to write the trash information")
+        .and_then(|()| {
+            fs::rename(&path, &trashed)
+                .with_context(|| format!("Failed to move {} to the trash", path.display()))
+        });
+    if moved.is_err() {
+        let _ = fs::remove_file(&info_path);
+    }
+    moved
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::cli::Args;
+use crate::control_chars::display_path;
+use crate::encoding::get_encoding;
+use crate::ignore::IgnoreRules;
+use crate::name_index::{decode_names, read_raw_names};
+use crate::output::{outln, paint_stdout, Reporter, Style};
+use crate::{detect_filename_encoding, get_inner_root, open_archive, target_path};
+
+#[derive(Default)]
+struct Node {
+    children: BTreeMap<OsString, Node>,
+    is_dir: bool,
+    // Every entry at or below this node is skipped.
+    skipped: bool,
+}
+
+impl Node {
+    fn insert(&mut self, path: &Path, is_dir: bool, skipped: bool) {
+        let mut node = self;
+        let mut names = path.iter().peekable();
+        while let Some(name) = names.next() {
+            let is_last = names.peek().is_none();
+            node = node.children.entry(name.to_os_string()).or_insert(Node {
+                children: BTreeMap::new(),
+                is_dir: false,
+                skipped: true,
+            });
+            node.is_dir |= !is_last || is_dir;
+            node.skipped &= skipped;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    dirs: usize,
+    files: usize,
+    skipped: usize,
+}
+
+fn print_children(node: &Node, prefix: &str, counts: &mut Counts) {
+    let last = node.children.len().saturating_sub(1);
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let (branch, indent) = if i == last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let mut line = display_path(Path::new(name));
+        if child.is_dir {
+            line.push('/');
+        }
+        if child.skipped {
+            line = paint_stdout(&format!("{} (skipped)", line), Style::Skip);
+            counts.skipped += 1;
+        } else if child.is_dir {
+            counts.dirs += 1;
+        } else {
+            counts.files += 1;
+        }
+        outln!("{}{}{}", prefix, branch, line);
+        // The contents of a skipped directory are all skipped.
+        if !child.skipped {
+            print_children(child, &format!("{}{}", prefix, indent), counts);
+        }
+    }
+}
+
+// Print the layout extraction would create: names decoded and sanitized,
+// the common root directory stripped, and junk marked as skipped.
+pub fn print_tree(zipfile: &Path, args: &Args) -> Result<()> {
+    let mut archive = open_archive(zipfile, &Reporter::TERMINAL, !args.no_mmap)?;
+    let raw_names = read_raw_names(&mut archive)?;
+    let encoding = match args.oenc.as_deref() {
+        Some(encoding_name) => get_encoding(encoding_name).unwrap(),
+        None => detect_filename_encoding(&raw_names),
+    };
+    let names = decode_names(&raw_names, encoding);
+    let ignore_rules = IgnoreRules::new(args.no_ignore, args.keep_macosx, &args.ignore_pattern);
+    let inner_root = get_inner_root(&names, &ignore_rules)?;
+
+    let mut root = Node::default();
+    for name in &names {
+        let Some(path) = name.path()? else {
+            continue;
+        };
+        // Skipped entries outside the root are shown where they are.
+        let stripped = path.strip_prefix(&inner_root).unwrap_or(path);
+        if stripped.as_os_str().is_empty() {
+            continue;
+        }
+        root.insert(stripped, name.is_dir, ignore_rules.is_ignored(path));
+    }
+
+    outln!("{}/", target_path(zipfile, &args.options()).display());
+    let mut counts = Counts::default();
+    print_children(&root, "", &mut counts);
+    outln!(
+        "\n{} directories, {} files, {} skipped",
+        counts.dirs,
+        counts.files,
+        counts.skipped
+    );
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::io::{self, Read as _};
+use std::path::Path;
+
+use cap_std::fs::Dir;
+
+// For --skip-unchanged: hard link a file of the directory being replaced
+// into the staging directory when its size and CRC match the entry, instead
+// of writing the same data again. The file is only read when the size
+// matches. Returns false when the file must be extracted.
+//
+// The link shares the inode with the old file, so attributes set on it
+// afterwards also change the old one, which is removed on success anyway.
+pub fn link_unchanged(
+    previous: &Dir,
+    dst_root: &Dir,
+    path: &Path,
+    size: u64,
+    crc32: u32,
+) -> io::Result<bool> {
+    let Ok(metadata) = previous.symlink_metadata(path) else {
+        return Ok(false);
+    };
+    if !metadata.is_file() || metadata.len() != size {
+        return Ok(false);
+    }
+    let mut file = match previous.open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buf = vec![0u8; 128 * 1024];
+    loop {
+        let length = file.read(&mut buf)?;
+        if length == 0 {
+            break;
+        }
+        hasher.update(&buf[..length]);
+    }
+    if hasher.finalize() != crc32 {
+        return Ok(false);
+    }
+    match previous.hard_link(path, dst_root, path) {
+        Ok(()) => Ok(true),
+        // Across filesystems, or where hard links are not allowed.
+        Err(err)
+            if err.kind() == io::ErrorKind::CrossesDevices
+                || err.kind() == io::ErrorKind::PermissionDenied =>
+        {
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    }
+}
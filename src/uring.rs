@@ -0,0 +1,306 @@
+use std::ffi::{c_void, CString};
+use std::mem::size_of;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::io::{AsRawFd as _, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::SystemTime;
+
+use anyhow::{bail, Context as _, Result};
+use cap_std::fs::Dir;
+use rustix::fs::{Mode, OFlags, ResolveFlags};
+use rustix::io::Errno;
+use rustix::io_uring::{
+    io_uring_enter, io_uring_params, io_uring_register, io_uring_setup, io_uring_sqe, open_how,
+    IoringEnterFlags, IoringOp, IoringRegisterOp, IoringSqeFlags, IORING_OFF_CQ_RING,
+    IORING_OFF_SQES, IORING_OFF_SQ_RING,
+};
+use rustix::mm::{mmap, munmap, MapFlags, ProtFlags};
+
+use crate::beneath;
+
+const RING_ENTRIES: u32 = 256;
+// Each file takes an open, a write and a close, linked in this order.
+const MAX_BATCH_FILES: usize = RING_ENTRIES as usize / 3;
+const MAX_BATCH_BYTES: usize = 64 * 1024 * 1024;
+// Larger files are written the usual way; batching only pays off when the
+// system calls cost more than the data.
+pub const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+struct Mapping {
+    ptr: NonNull<c_void>,
+    len: usize,
+}
+
+impl Mapping {
+    fn map(fd: &OwnedFd, offset: u64, len: usize) -> Result<Self> {
+        let ptr = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                ProtFlags::READ | ProtFlags::WRITE,
+                MapFlags::SHARED | MapFlags::POPULATE,
+                fd,
+                offset,
+            )?
+        };
+        Ok(Mapping {
+            ptr: NonNull::new(ptr).context("io_uring mapping failed")?,
+            len,
+        })
+    }
+
+    // The ring fields are shared with the kernel.
+    unsafe fn at<T>(&self, offset: u32) -> *mut T {
+        self.ptr.as_ptr().cast::<u8>().add(offset as usize).cast()
+    }
+
+    unsafe fn atomic(&self, offset: u32) -> &AtomicU32 {
+        AtomicU32::from_ptr(self.at(offset))
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = munmap(self.ptr.as_ptr(), self.len);
+        }
+    }
+}
+
+struct QueuedFile {
+    path: PathBuf,
+    c_path: CString,
+    how: Box<open_how>,
+    data: Vec<u8>,
+    times: Option<(Option<SystemTime>, Option<SystemTime>)>,
+}
+
+// Writes small files with io_uring (--io-backend=uring), creating, writing
+// and closing a batch of files with one system call. Files are opened with
+// the same openat2 restrictions as beneath::create_file, and their times are
+// set once the batch is written.
+pub struct UringWriter {
+    ring: OwnedFd,
+    params: io_uring_params,
+    sq_ring: Mapping,
+    cq_ring: Mapping,
+    sqes: Mapping,
+    queue: Vec<QueuedFile>,
+    queued_bytes: usize,
+}
+
+impl UringWriter {
+    pub fn new() -> Result<Self> {
+        let mut params = io_uring_params::default();
+        let ring = match io_uring_setup(RING_ENTRIES, &mut params) {
+            Ok(ring) => ring,
+            Err(Errno::NOSYS | Errno::PERM) => bail!("io_uring is not available"),
+            Err(err) => return Err(err.into()),
+        };
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * size_of::<u32>();
+        let cq_len = params.cq_off.cqes as usize
+            + params.cq_entries as usize * size_of::<rustix::io_uring::io_uring_cqe>();
+        let sq_ring = Mapping::map(&ring, IORING_OFF_SQ_RING, sq_len)?;
+        let cq_ring = Mapping::map(&ring, IORING_OFF_CQ_RING, cq_len)?;
+        let sqes = Mapping::map(
+            &ring,
+            IORING_OFF_SQES,
+            params.sq_entries as usize * size_of::<io_uring_sqe>(),
+        )?;
+
+        // An empty table for the files opened by the ring, so that the
+        // write and close can refer to the file the open before them made.
+        let files = [-1i32; MAX_BATCH_FILES];
+        unsafe {
+            io_uring_register(
+                &ring,
+                IoringRegisterOp::RegisterFiles,
+                files.as_ptr().cast(),
+                files.len() as u32,
+            )
+            .context("Failed to register the io_uring file table")?;
+        }
+
+        Ok(UringWriter {
+            ring,
+            params,
+            sq_ring,
+            cq_ring,
+            sqes,
+            queue: Vec::new(),
+            queued_bytes: 0,
+        })
+    }
+
+    // Queue a file whose data has been checked. The batch is written by
+    // flush(), or when the next file does not fit.
+    pub fn queue(&mut self, dst_root: &Dir, path: &Path, data: Vec<u8>) -> Result<()> {
+        if self.queue.len() >= MAX_BATCH_FILES || self.queued_bytes >= MAX_BATCH_BYTES {
+            self.flush(dst_root)?;
+        }
+        // No O_CLOEXEC, which the kernel rejects for files opened into the
+        // ring's table; they never become descriptors of the process.
+        let flags = OFlags::WRONLY | OFlags::CREATE | OFlags::TRUNC | OFlags::NOFOLLOW;
+        let how = Box::new(open_how {
+            flags: flags.bits() as u64,
+            mode: Mode::from_bits_truncate(0o666).bits() as u64,
+            resolve: ResolveFlags::BENEATH | ResolveFlags::NO_SYMLINKS,
+        });
+        self.queued_bytes += data.len();
+        self.queue.push(QueuedFile {
+            path: path.to_path_buf(),
+            c_path: CString::new(path.as_os_str().as_bytes())?,
+            how,
+            data,
+            times: None,
+        });
+        Ok(())
+    }
+
+    // Set the times of the file queued last once it is written.
+    pub fn set_times(&mut self, accessed: Option<SystemTime>, modified: Option<SystemTime>) {
+        self.queue.last_mut().unwrap().times = Some((accessed, modified));
+    }
+
+    // Write every queued file and wait for the result.
+    pub fn flush(&mut self, dst_root: &Dir) -> Result<()> {
+        if self.queue.is_empty() {
+            return Ok(());
+        }
+        let dir_fd = dst_root.as_raw_fd();
+        let sq_off = self.params.sq_off;
+        let mut sqes = Vec::with_capacity(self.queue.len() * 3);
+        for (slot, file) in self.queue.iter().enumerate() {
+            let index = slot as u64 * 3;
+            let mut open = io_uring_sqe {
+                opcode: IoringOp::Openat2,
+                flags: IoringSqeFlags::IO_LINK,
+                fd: dir_fd,
+                ..Default::default()
+            };
+            open.addr_or_splice_off_in.addr = (file.c_path.as_ptr() as *mut c_void).into();
+            open.len.len = size_of::<open_how>() as u32;
+            open.off_or_addr2.addr2 = (&*file.how as *const open_how as *mut c_void).into();
+            open.splice_fd_in_or_file_index.file_index = slot as u32 + 1;
+            open.user_data.u64_ = index;
+
+            let mut write = io_uring_sqe {
+                opcode: IoringOp::Write,
+                flags: IoringSqeFlags::IO_LINK | IoringSqeFlags::FIXED_FILE,
+                fd: slot as i32,
+                ..Default::default()
+            };
+            write.addr_or_splice_off_in.addr = (file.data.as_ptr() as *mut c_void).into();
+            write.len.len = file.data.len() as u32;
+            write.off_or_addr2.off = 0;
+            write.user_data.u64_ = index + 1;
+
+            let mut close = io_uring_sqe {
+                opcode: IoringOp::Close,
+                ..Default::default()
+            };
+            close.splice_fd_in_or_file_index.file_index = slot as u32 + 1;
+            close.user_data.u64_ = index + 2;
+
+            sqes.extend([open, write, close]);
+        }
+
+        unsafe {
+            let mask = *self.sq_ring.at::<u32>(sq_off.ring_mask);
+            let array = self.sq_ring.at::<u32>(sq_off.array);
+            let sqe_base = self.sqes.ptr.as_ptr().cast::<io_uring_sqe>();
+            let tail = self.sq_ring.atomic(sq_off.tail);
+            let mut next = tail.load(Ordering::Acquire);
+            for sqe in &sqes {
+                let position = next & mask;
+                sqe_base.add(position as usize).write(*sqe);
+                array.add(position as usize).write(position);
+                next = next.wrapping_add(1);
+            }
+            tail.store(next, Ordering::Release);
+        }
+
+        let total = sqes.len() as u32;
+        let mut first_error = None;
+        let mut completed = 0u32;
+        let mut to_submit = total;
+        while completed < total {
+            let submitted = unsafe {
+                io_uring_enter(
+                    &self.ring,
+                    to_submit,
+                    1,
+                    IoringEnterFlags::GETEVENTS,
+                    ptr::null(),
+                    0,
+                )
+            };
+            match submitted {
+                Ok(submitted) => to_submit -= submitted.min(to_submit),
+                Err(Errno::INTR) => continue,
+                Err(err) => {
+                    // The kernel may still use the buffers.
+                    std::mem::forget(std::mem::take(&mut self.queue));
+                    return Err(err).context("io_uring_enter failed");
+                }
+            }
+            completed += self.reap(&mut first_error);
+        }
+
+        let queue = std::mem::take(&mut self.queue);
+        self.queued_bytes = 0;
+        if let Some((index, err)) = first_error {
+            let file = &queue[(index / 3) as usize];
+            return Err(err).with_context(|| format!("Failed to write {}", file.path.display()));
+        }
+        for file in queue {
+            if let Some((accessed, modified)) = file.times {
+                beneath::set_times(dst_root, &file.path, accessed, modified)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Take the completions available and return how many there were.
+    fn reap(&mut self, first_error: &mut Option<(u64, std::io::Error)>) -> u32 {
+        let cq_off = self.params.cq_off;
+        let mut count = 0;
+        unsafe {
+            let mask = *self.cq_ring.at::<u32>(cq_off.ring_mask);
+            let cqes = self
+                .cq_ring
+                .at::<rustix::io_uring::io_uring_cqe>(cq_off.cqes);
+            let head = self.cq_ring.atomic(cq_off.head);
+            let tail = self.cq_ring.atomic(cq_off.tail).load(Ordering::Acquire);
+            let mut current = head.load(Ordering::Relaxed);
+            while current != tail {
+                let cqe = &*cqes.add((current & mask) as usize);
+                let index = cqe.user_data.u64_();
+                let error = if cqe.res < 0 {
+                    Some(std::io::Error::from_raw_os_error(-cqe.res))
+                } else if index % 3 == 1
+                    && cqe.res as usize != self.queue[(index / 3) as usize].data.len()
+                {
+                    Some(std::io::Error::from(std::io::ErrorKind::WriteZero))
+                } else {
+                    None
+                };
+                // Operations after a failed one in the chain are cancelled;
+                // the first failure is the interesting one.
+                if let Some(error) = error {
+                    if first_error.as_ref().is_none_or(|(first, _)| index < *first)
+                        && error.raw_os_error() != Some(Errno::CANCELED.raw_os_error())
+                    {
+                        *first_error = Some((index, error));
+                    }
+                }
+                current = current.wrapping_add(1);
+                count += 1;
+            }
+            head.store(current, Ordering::Release);
+        }
+        count
+    }
+}
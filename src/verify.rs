@@ -0,0 +1,82 @@
+use std::io;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use zip::ZipArchive;
+
+use crate::control_chars::display_path;
+use crate::encoding::get_encoding;
+use crate::interrupt::{interrupted, process_token};
+use crate::name_index::{decode_names, read_raw_names};
+use crate::output::{notice, say, summary, Reporter};
+use crate::{detect_filename_encoding, interruptable_copy, open_archive, DEFAULT_BUFFER_SIZE};
+
+// Decompress every entry to a sink and check its CRC and size.
+// Returns whether all entries passed.
+pub fn test_archive(zipfile: &Path, encoding_name: Option<&str>, use_mmap: bool) -> Result<bool> {
+    say!("test {}", zipfile.display());
+
+    let mut archive = open_archive(zipfile, &Reporter::TERMINAL, use_mmap)?;
+    let raw_names = read_raw_names(&mut archive)?;
+    let encoding = match encoding_name {
+        Some(encoding_name) => get_encoding(encoding_name).unwrap(),
+        None => detect_filename_encoding(&raw_names),
+    };
+    let names = decode_names(&raw_names, encoding);
+
+    let mut failures = 0usize;
+    for (i, name) in names.iter().enumerate() {
+        let name = &name.decoded;
+        match test_entry(&mut archive, i) {
+            Ok(()) => say!("OK     {}", display_path(name)),
+            Err(err) => {
+                notice!("FAILED {}: {:#}", display_path(name), err);
+                failures += 1;
+            }
+        }
+
+        if interrupted() {
+            bail!("Interrupted");
+        }
+    }
+
+    if failures == 0 {
+        summary!("{} entries OK", archive.len());
+    } else {
+        summary!("{} of {} entries FAILED", failures, archive.len());
+    }
+    Ok(failures == 0)
+}
+
+fn test_entry<R>(archive: &mut ZipArchive<R>, index: usize) -> Result<()>
+where
+    R: io::Read + io::Seek,
+{
+    // Fails here for unsupported compression methods and encryption.
+    let mut file = archive.by_index(index)?;
+    if file.is_dir() {
+        return Ok(());
+    }
+    let outcome = interruptable_copy(
+        &mut file,
+        &mut io::sink(),
+        u64::MAX,
+        DEFAULT_BUFFER_SIZE,
+        process_token(),
+    )?;
+    if outcome.crc32 != file.crc32() {
+        bail!(
+            "CRC mismatch (expected {:08x}, got {:08x})",
+            file.crc32(),
+            outcome.crc32
+        );
+    }
+    if outcome.written != file.size() {
+        bail!(
+            "size mismatch (expected {}, got {})",
+            file.size(),
+            outcome.written
+        );
+    }
+    Ok(())
+}
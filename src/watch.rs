@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::os::fd::AsFd as _;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context as _, Result};
+use rustix::fs::inotify::{inotify_add_watch, inotify_init, CreateFlags, WatchFlags};
+use rustix::io::Errno;
+
+use crate::cli::{extract, Args};
+use crate::interrupt::interrupted;
+use crate::output::{error, notice};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// The size of struct inotify_event without the name.
+const EVENT_HEADER_SIZE: usize = 16;
+
+// The mask and file name of each event in `buffer`.
+fn parse_events(buffer: &[u8]) -> Vec<(WatchFlags, OsString)> {
+    let mut events = Vec::new();
+    let mut rest = buffer;
+    while rest.len() >= EVENT_HEADER_SIZE {
+        let field =
+            |offset: usize| u32::from_ne_bytes(rest[offset..offset + 4].try_into().unwrap());
+        let mask = WatchFlags::from_bits_retain(field(4));
+        let len = field(12) as usize;
+        // The name is padded with NULs.
+        let name = &rest[EVENT_HEADER_SIZE..EVENT_HEADER_SIZE + len];
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(len)];
+        events.push((mask, OsStr::from_bytes(name).to_os_string()));
+        rest = &rest[EVENT_HEADER_SIZE + len..];
+    }
+    events
+}
+
+fn is_zip_name(name: &OsStr) -> bool {
+    Path::new(name)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+// Extract zip files as they are completed in `dir`, written and closed or
+// moved in, until interrupted. An archive is only taken once nothing has
+// happened to it for `settle`, as downloads may be written in several goes.
+pub fn watch(dir: &Path, settle: Duration, args: &Args) -> Result<()> {
+    let inotify = inotify_init(CreateFlags::CLOEXEC | CreateFlags::NONBLOCK)?;
+    inotify_add_watch(
+        inotify.as_fd(),
+        dir,
+        WatchFlags::CLOSE_WRITE
+            | WatchFlags::MOVED_TO
+            | WatchFlags::MODIFY
+            | WatchFlags::DELETE_SELF,
+    )
+    .with_context(|| format!("Failed to watch {}", dir.display()))?;
+    notice!("Watching {}", dir.display());
+
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    let mut buffer = vec![0; 64 * 1024];
+    while !interrupted() {
+        match rustix::io::read(&inotify, &mut buffer) {
+            Ok(len) => {
+                for (mask, name) in parse_events(&buffer[..len]) {
+                    if mask.contains(WatchFlags::DELETE_SELF) {
+                        bail!("{} was removed", dir.display());
+                    }
+                    if !is_zip_name(&name) {
+                        continue;
+                    }
+                    let path = dir.join(name);
+                    // Writing again puts off an archive closed before.
+                    if mask.intersects(WatchFlags::CLOSE_WRITE | WatchFlags::MOVED_TO)
+                        || pending.contains_key(&path)
+                    {
+                        pending.insert(path, Instant::now());
+                    }
+                }
+            }
+            Err(Errno::AGAIN) => thread::sleep(POLL_INTERVAL),
+            Err(Errno::INTR) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= settle)
+            .map(|(path, _)| path.clone())
+            .collect();
+        settled.sort();
+        for path in settled {
+            pending.remove(&path);
+            // Removed or renamed away in the meantime.
+            if !path.is_file() {
+                continue;
+            }
+            if let Err(err) = extract(&path, args, None, None) {
+                error!("{:?}", err);
+            }
+            if interrupted() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
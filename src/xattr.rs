@@ -0,0 +1,67 @@
+use std::io;
+use std::path::Path;
+
+use cap_std::fs::Dir;
+use rustix::fs::{fsetxattr, getxattr, XattrFlags};
+use rustix::io::Errno;
+
+pub const QUARANTINE: &[u8] = b"com.apple.quarantine";
+
+// Names outside the Linux namespaces are placed in the "user." namespace.
+fn xattr_name(name: &[u8]) -> String {
+    const NAMESPACES: [&str; 4] = ["user.", "trusted.", "security.", "system."];
+    let name = String::from_utf8_lossy(name);
+    if NAMESPACES.iter().any(|ns| name.starts_with(ns)) {
+        name.into_owned()
+    } else {
+        format!("user.{}", name)
+    }
+}
+
+// Apply extended attributes to a file or directory inside `dir`.
+pub fn set_xattrs(
+    dir: &Dir,
+    path: &Path,
+    is_dir: bool,
+    attributes: &[(Vec<u8>, Vec<u8>)],
+) -> io::Result<()> {
+    if attributes.is_empty() {
+        return Ok(());
+    }
+    let set_all = |fd: rustix::fd::BorrowedFd| -> io::Result<()> {
+        for (name, value) in attributes {
+            fsetxattr(fd, xattr_name(name).as_str(), value, XattrFlags::empty())?;
+        }
+        Ok(())
+    };
+    use rustix::fd::AsFd as _;
+    if is_dir {
+        set_all(dir.open_dir(path)?.as_fd())
+    } else {
+        set_all(dir.open(path)?.as_fd())
+    }
+}
+
+// Read an extended attribute of a file outside the extraction directory.
+// Returns None if the attribute or xattr support is missing.
+pub fn get_xattr(path: &Path, name: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let name = xattr_name(name);
+    loop {
+        let size = match getxattr(path, name.as_str(), &mut []) {
+            Ok(size) => size,
+            Err(Errno::NODATA) | Err(Errno::NOTSUP) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let mut value = vec![0u8; size];
+        match getxattr(path, name.as_str(), &mut value) {
+            Ok(length) => {
+                value.truncate(length);
+                return Ok(Some(value));
+            }
+            // The attribute grew in between
+            Err(Errno::RANGE) => continue,
+            Err(Errno::NODATA) | Err(Errno::NOTSUP) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
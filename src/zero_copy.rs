@@ -0,0 +1,84 @@
+use std::fs::File;
+use std::os::unix::fs::FileExt as _;
+
+use anyhow::{bail, Result};
+use rustix::fs::copy_file_range;
+use rustix::io::Errno;
+use zip::read::ZipFile;
+use zip::CompressionMethod;
+
+use crate::checksum::{Algorithm, Hasher};
+use crate::interrupt::CancellationToken;
+use crate::output::Reporter;
+use crate::progress;
+use crate::throttle::throttle;
+use crate::CopyOutcome;
+
+const CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+// Copy a stored entry straight from the archive file with copy_file_range,
+// so that the kernel copies in place or shares the extents (reflink).
+// The data is still read once for the CRC check and any digest. Returns None when the
+// entry is compressed or the filesystems cannot do it.
+pub fn copy_stored(
+    file: &ZipFile,
+    archive_file: &File,
+    outfile: &File,
+    limit: u64,
+    digest: Option<Algorithm>,
+    cancel: &CancellationToken,
+    reporter: &Reporter,
+) -> Result<Option<CopyOutcome>> {
+    if file.compression() != CompressionMethod::Stored || file.size() != file.compressed_size() {
+        return Ok(None);
+    }
+    let start = file.data_start();
+    let end = start + file.size();
+    if end > archive_file.metadata()?.len() {
+        return Ok(None);
+    }
+    if file.size() > limit {
+        bail!("Exceeded the extraction size limit of {} bytes", limit);
+    }
+
+    let mut offset = start;
+    while offset < end {
+        let length = (end - offset).min(CHUNK_SIZE) as usize;
+        match copy_file_range(archive_file, Some(&mut offset), outfile, None, length) {
+            Ok(0) => bail!("Unexpected end of the archive"),
+            Ok(length) => {
+                throttle(length, cancel);
+                progress::advance(length);
+                reporter.bytes_written(length);
+            }
+            Err(Errno::XDEV | Errno::NOSYS | Errno::INVAL | Errno::OPNOTSUPP)
+                if offset == start =>
+            {
+                return Ok(None);
+            }
+            Err(err) => return Err(err.into()),
+        }
+        if cancel.is_cancelled() {
+            bail!("Interrupted");
+        }
+    }
+
+    let mut hasher = crc32fast::Hasher::new();
+    let mut digest = digest.map(Hasher::new);
+    let mut buf = vec![0u8; 128 * 1024];
+    let mut offset = start;
+    while offset < end {
+        let length = (end - offset).min(buf.len() as u64) as usize;
+        archive_file.read_exact_at(&mut buf[..length], offset)?;
+        hasher.update(&buf[..length]);
+        if let Some(digest) = &mut digest {
+            digest.update(&buf[..length]);
+        }
+        offset += length as u64;
+    }
+    Ok(Some(CopyOutcome {
+        written: file.size(),
+        crc32: hasher.finalize(),
+        digest: digest.map(Hasher::finalize),
+    }))
+}
@@ -1,29 +1,103 @@
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use chrono::{DateTime, Local, LocalResult, TimeZone};
+use chrono::{DateTime, FixedOffset, Local, LocalResult, NaiveDate, TimeZone};
 use zip::read::ZipFile;
 
 use crate::encoding::ZipEncoding;
+use crate::extra_field::{
+    find_extra_field, parse_extended_timestamp, parse_infozip_unix_new, parse_ntfs,
+    parse_pkware_unix, EXTENDED_TIMESTAMP, INFOZIP_UNIX_NEW, NTFS, PKWARE_UNIX,
+};
+
+// The timezone in which DOS timestamps were recorded.
+#[derive(Clone, Copy, Debug)]
+pub enum DosTimezone {
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl FromStr for DosTimezone {
+    type Err = String;
+
+    // Accepts "local", "UTC", "Z" or an offset such as "+09:00", "-0500".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "local" => return Ok(DosTimezone::Local),
+            "utc" | "z" => return Ok(DosTimezone::Fixed(FixedOffset::east_opt(0).unwrap())),
+            _ => {}
+        }
+        let invalid = || format!("invalid timezone: {} (expected local, UTC or +HH:MM)", s);
+        let (sign, rest) = match s.as_bytes().first() {
+            Some(b'+') => (1, &s[1..]),
+            Some(b'-') => (-1, &s[1..]),
+            _ => return Err(invalid()),
+        };
+        let digits = rest.replace(':', "");
+        if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let hours: i32 = digits[..2].parse().unwrap();
+        let minutes: i32 = digits[2..].parse().unwrap();
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(DosTimezone::Fixed)
+            .ok_or_else(invalid)
+    }
+}
+
+// Unix file types other than regular files, directories and symlinks.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpecialKind {
+    Fifo,
+    CharDevice,
+    BlockDevice,
+    Socket,
+}
+
+impl fmt::Display for SpecialKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SpecialKind::Fifo => "FIFO",
+            SpecialKind::CharDevice => "character device",
+            SpecialKind::BlockDevice => "block device",
+            SpecialKind::Socket => "socket",
+        })
+    }
+}
+
+pub struct EntryTimes {
+    pub accessed: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+}
 
 pub trait ZipFileExt<'a> {
-    fn decoded_name_lossy(&self, encoding: ZipEncoding) -> PathBuf;
     fn is_utf8(&self) -> bool;
+    fn is_symlink(&self) -> bool;
+    fn special_kind(&self) -> Option<SpecialKind>;
+    fn hardlink_target(&self, encoding: ZipEncoding) -> Option<PathBuf>;
+    fn unix_owner(&self) -> Option<(u32, u32)>;
 
-    fn last_modified_chrono(&self) -> LocalResult<DateTime<Local>>;
+    fn last_modified_chrono(&self, timezone: DosTimezone) -> LocalResult<DateTime<FixedOffset>>;
+    fn entry_times(&self, timezone: DosTimezone) -> EntryTimes;
 }
 
 impl<'a> ZipFileExt<'a> for ZipFile<'a> {
-    fn decoded_name_lossy(&self, encoding: ZipEncoding) -> PathBuf {
-        if self.is_utf8() {
-            return PathBuf::from(self.name());
+    fn hardlink_target(&self, encoding: ZipEncoding) -> Option<PathBuf> {
+        if self.is_dir() || self.is_symlink() {
+            return None;
         }
-        match encoding {
-            ZipEncoding::Cp437 => PathBuf::from(self.name()),
-            ZipEncoding::EncodingRs(encoding) => {
-                let (decoded_name_cow, _, _malformed) = encoding.decode(self.name_raw());
-                let decoded_name = decoded_name_cow.as_ref();
-                PathBuf::from(decoded_name)
-            }
+        let extra = find_extra_field(self.extra_data(), PKWARE_UNIX)?;
+        let link_target = parse_pkware_unix(extra)?.link_target;
+        if link_target.is_empty() {
+            return None;
+        }
+        if self.is_utf8() {
+            Some(PathBuf::from(String::from_utf8_lossy(link_target).as_ref()))
+        } else {
+            Some(PathBuf::from(encoding.decode_lossy(link_target)))
         }
     }
 
@@ -36,18 +110,144 @@ impl<'a> ZipFileExt<'a> for ZipFile<'a> {
         // access to the zip file reader.
         // https://github.com/zip-rs/zip/blob/3e88fe66c941d411cff5cf49778ba08c2ed93801/src/read.rs#L671
         let (utf8_cow, _encoding, malformed) = encoding_rs::UTF_8.decode(self.name_raw());
-        return !malformed && self.name() == utf8_cow;
+        !malformed && self.name() == utf8_cow
+    }
+
+    fn is_symlink(&self) -> bool {
+        const S_IFMT: u32 = 0o170000;
+        const S_IFLNK: u32 = 0o120000;
+        matches!(self.unix_mode(), Some(mode) if mode & S_IFMT == S_IFLNK)
     }
 
-    fn last_modified_chrono(&self) -> LocalResult<DateTime<Local>> {
+    fn special_kind(&self) -> Option<SpecialKind> {
+        const S_IFMT: u32 = 0o170000;
+        match self.unix_mode()? & S_IFMT {
+            0o010000 => Some(SpecialKind::Fifo),
+            0o020000 => Some(SpecialKind::CharDevice),
+            0o060000 => Some(SpecialKind::BlockDevice),
+            0o140000 => Some(SpecialKind::Socket),
+            _ => None,
+        }
+    }
+
+    fn unix_owner(&self) -> Option<(u32, u32)> {
+        let extra = self.extra_data();
+        if let Some(owner) =
+            find_extra_field(extra, INFOZIP_UNIX_NEW).and_then(parse_infozip_unix_new)
+        {
+            return Some(owner);
+        }
+        let unix = parse_pkware_unix(find_extra_field(extra, PKWARE_UNIX)?)?;
+        Some((unix.uid.into(), unix.gid.into()))
+    }
+
+    fn last_modified_chrono(&self, timezone: DosTimezone) -> LocalResult<DateTime<FixedOffset>> {
         let zip_dt = self.last_modified();
-        Local.with_ymd_and_hms(
+        let Some(naive) = NaiveDate::from_ymd_opt(
             zip_dt.year().into(),
             zip_dt.month().into(),
             zip_dt.day().into(),
-            zip_dt.hour().into(),
-            zip_dt.minute().into(),
-            zip_dt.second().into(),
         )
+        .and_then(|date| {
+            date.and_hms_opt(
+                zip_dt.hour().into(),
+                zip_dt.minute().into(),
+                zip_dt.second().into(),
+            )
+        }) else {
+            return LocalResult::None;
+        };
+        match timezone {
+            DosTimezone::Local => Local
+                .from_local_datetime(&naive)
+                .map(|datetime| datetime.fixed_offset()),
+            DosTimezone::Fixed(offset) => offset.from_local_datetime(&naive),
+        }
+    }
+
+    fn entry_times(&self, timezone: DosTimezone) -> EntryTimes {
+        // NTFS times are the most precise. The creation time is also
+        // stored there, but Linux provides no way to set it.
+        if let Some(ntfs) = find_extra_field(self.extra_data(), NTFS).and_then(parse_ntfs) {
+            return EntryTimes {
+                accessed: filetime(ntfs.atime),
+                modified: filetime(ntfs.mtime),
+            };
+        }
+
+        let timestamp = find_extra_field(self.extra_data(), EXTENDED_TIMESTAMP)
+            .and_then(parse_extended_timestamp);
+        let (ut_mtime, ut_atime) = match timestamp {
+            Some(timestamp) => (timestamp.mtime, timestamp.atime),
+            None => (None, None),
+        };
+
+        // Fall back to the DOS timestamp.
+        // for DST overlap, select the earliest datetime of ambiguous one.
+        // Some zip files contain invalid mtime such as 1980-00-00 00:00:00.
+        // In such case, we do not set the mtime.
+        let modified = ut_mtime.map(unix_time).or_else(|| {
+            self.last_modified_chrono(timezone)
+                .earliest(/* for DST overlap */)
+                .map(SystemTime::from)
+        });
+        EntryTimes {
+            accessed: ut_atime.map(unix_time),
+            modified,
+        }
+    }
+}
+
+fn unix_time(secs: i32) -> SystemTime {
+    let offset = Duration::from_secs(secs.unsigned_abs().into());
+    if secs >= 0 {
+        UNIX_EPOCH + offset
+    } else {
+        UNIX_EPOCH - offset
+    }
+}
+
+// A zero FILETIME means the time is not recorded.
+fn filetime(value: u64) -> Option<SystemTime> {
+    const INTERVALS_PER_SEC: u64 = 10_000_000;
+    const SECS_1601_TO_1970: u64 = 11_644_473_600;
+    if value == 0 {
+        return None;
+    }
+    let since_1601 = Duration::new(
+        value / INTERVALS_PER_SEC,
+        (value % INTERVALS_PER_SEC * 100) as u32,
+    );
+    let epoch_offset = Duration::from_secs(SECS_1601_TO_1970);
+    match since_1601.checked_sub(epoch_offset) {
+        Some(after_epoch) => UNIX_EPOCH.checked_add(after_epoch),
+        None => UNIX_EPOCH.checked_sub(epoch_offset - since_1601),
+    }
+}
+
+// The zip crate only exposes the central directory extra field. Some
+// fields (e.g. OS/2 extended attributes) carry their data only in the
+// local header.
+pub fn read_local_extra_field<R>(reader: &mut R, header_start: u64) -> io::Result<Vec<u8>>
+where
+    R: Read + Seek,
+{
+    const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+    // signature(4) version(2) flags(2) method(2) time(2) date(2) crc(4)
+    // compressed_size(4) size(4) name_length(2) extra_length(2)
+    let mut header = [0u8; 30];
+    reader.seek(SeekFrom::Start(header_start))?;
+    reader.read_exact(&mut header)?;
+    if u32::from_le_bytes(header[0..4].try_into().unwrap()) != LOCAL_FILE_HEADER_SIGNATURE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid local file header",
+        ));
     }
+    let name_length = u16::from_le_bytes([header[26], header[27]]);
+    let extra_length = u16::from_le_bytes([header[28], header[29]]);
+    reader.seek(SeekFrom::Current(name_length.into()))?;
+    let mut extra = vec![0u8; extra_length.into()];
+    reader.read_exact(&mut extra)?;
+    Ok(extra)
 }